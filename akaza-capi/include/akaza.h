@@ -0,0 +1,110 @@
+#ifndef AKAZA_H
+#define AKAZA_H
+
+/* This file is generated by cbindgen. Do not edit it manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// かな漢字変換エンジン。
+typedef struct AkazaEngine AkazaEngine;
+
+// 変換結果。文節ごとの候補リストを保持する。
+typedef struct AkazaResult AkazaResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// 直前に呼び出した関数で発生したエラーのメッセージを返す。
+// エラーが発生していない場合は NULL を返す。
+// 返却された文字列は、同じスレッドで次に akaza の関数を呼び出すまで有効です。
+const char *akaza_last_error(void);
+
+// 設定ファイルを元に変換エンジンを作成する。
+// `config_path` が NULL の場合は `~/.config/akaza/config.yml` を読む。
+// ユーザーの学習データは XDG データディレクトリから読み込まれる。
+// 失敗した場合は NULL を返す。
+//
+// # Safety
+// `config_path` は NULL か、NUL 終端された UTF-8 文字列である必要がある。
+struct AkazaEngine *akaza_engine_new(const char *config_path);
+
+// 変換エンジンを解放する。
+//
+// # Safety
+// `engine` は `akaza_engine_new` が返したポインタか NULL である必要がある。
+void akaza_engine_free(struct AkazaEngine *engine);
+
+// よみがなをかな漢字変換する。
+// 結果は `akaza_result_free` で解放すること。失敗した場合は NULL を返す。
+//
+// # Safety
+// `engine` は有効なエンジン、`yomi` は NUL 終端された UTF-8 文字列である必要がある。
+struct AkazaResult *akaza_engine_convert(const struct AkazaEngine *engine,
+                                         const char *yomi);
+
+// ユーザーが選択した候補を学習する。
+// `selected` には、文節ごとに選択された候補のインデックスを `clause_count` 個渡す。
+// 成功した場合は 0、失敗した場合は -1 を返す。
+//
+// # Safety
+// `engine` と `result` は有効なポインタ、`selected` は `clause_count` 個の要素をもつ配列である必要がある。
+int akaza_engine_learn(struct AkazaEngine *engine,
+                       const struct AkazaResult *result,
+                       const size_t *selected,
+                       size_t clause_count);
+
+// 学習結果をユーザーデータファイルに書き出す。
+// 成功した場合は 0、失敗した場合は -1 を返す。
+//
+// # Safety
+// `engine` は有効なエンジンである必要がある。
+int akaza_engine_save_user_data(const struct AkazaEngine *engine);
+
+// 変換結果を解放する。
+//
+// # Safety
+// `result` は `akaza_engine_convert` が返したポインタか NULL である必要がある。
+void akaza_result_free(struct AkazaResult *result);
+
+// 文節の数を返す。
+//
+// # Safety
+// `result` は有効な変換結果である必要がある。
+size_t akaza_result_clause_count(const struct AkazaResult *result);
+
+// 指定された文節の候補数を返す。
+//
+// # Safety
+// `result` は有効な変換結果である必要がある。
+size_t akaza_result_candidate_count(const struct AkazaResult *result, size_t clause);
+
+// 候補の表記を返す。範囲外の場合は NULL を返す。
+// 返却された文字列は `result` が解放されるまで有効です。
+//
+// # Safety
+// `result` は有効な変換結果である必要がある。
+const char *akaza_result_surface(const struct AkazaResult *result, size_t clause, size_t index);
+
+// 候補のよみがなを返す。範囲外の場合は NULL を返す。
+// 返却された文字列は `result` が解放されるまで有効です。
+//
+// # Safety
+// `result` は有効な変換結果である必要がある。
+const char *akaza_result_yomi(const struct AkazaResult *result, size_t clause, size_t index);
+
+// 候補のコストを返す。範囲外の場合は NaN を返す。
+//
+// # Safety
+// `result` は有効な変換結果である必要がある。
+float akaza_result_cost(const struct AkazaResult *result, size_t clause, size_t index);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* AKAZA_H */
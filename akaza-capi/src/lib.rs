@@ -0,0 +1,412 @@
+//! libakaza を C/C++ から利用するための C API です。
+//!
+//! ヘッダファイル `include/akaza.h` は、`make -C akaza-capi` で cbindgen によって生成します。
+//!
+//! 基本的な使い方は以下の通り。
+//!
+//! ```c
+//! AkazaEngine *engine = akaza_engine_new(NULL);
+//! AkazaResult *result = akaza_engine_convert(engine, "わたしのなまえはなかのです");
+//! for (size_t i = 0; i < akaza_result_clause_count(result); i++) {
+//!     printf("%s", akaza_result_surface(result, i, 0));
+//! }
+//! akaza_result_free(result);
+//! akaza_engine_free(engine);
+//! ```
+//!
+//! 返却される文字列はすべて UTF-8 です。
+//! エラーが発生した場合、NULL または負の値を返すので、`akaza_last_error()` で詳細を取得してください。
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, bail, Result};
+use log::error;
+
+use libakaza::config::Config;
+use libakaza::engine::base::HenkanEngine;
+use libakaza::engine::bigram_word_viterbi_engine::{
+    BigramWordViterbiEngine, BigramWordViterbiEngineBuilder,
+};
+use libakaza::graph::candidate::Candidate;
+use libakaza::kana_kanji::marisa_kana_kanji_dict::MarisaKanaKanjiDict;
+use libakaza::lm::system_bigram::MarisaSystemBigramLM;
+use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
+use libakaza::user_side_data::user_data::UserData;
+
+/// かな漢字変換エンジン。
+pub struct AkazaEngine {
+    engine:
+        BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM, MarisaKanaKanjiDict>,
+}
+
+/// 変換結果。文節ごとの候補リストを保持する。
+pub struct AkazaResult {
+    clauses: Vec<Vec<ResultCandidate>>,
+}
+
+struct ResultCandidate {
+    candidate: Candidate,
+    surface: CString,
+    yomi: CString,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(err: anyhow::Error) {
+    error!("{}", err);
+    let msg = CString::new(err.to_string().replace('\0', ""))
+        .unwrap_or_else(|_| CString::new("unknown error").unwrap());
+    LAST_ERROR.with(|it| *it.borrow_mut() = Some(msg));
+}
+
+/// `f` を実行して、エラーが発生したら `LAST_ERROR` に記録して `on_error` を返す。
+/// 成功したときに前回のエラーが残らないように、実行する前に `LAST_ERROR` を消しておく。
+/// Rust のパニックが FFI の境界を越えて巻き戻らないように、パニックもエラーとして扱う。
+fn ffi_call<T>(on_error: T, f: impl FnOnce() -> Result<T>) -> T {
+    LAST_ERROR.with(|it| *it.borrow_mut() = None);
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            set_last_error(err);
+            on_error
+        }
+        Err(payload) => {
+            let msg = payload
+                .downcast_ref::<&str>()
+                .map(|it| it.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(anyhow!("panic: {}", msg));
+            on_error
+        }
+    }
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> Result<&'a str> {
+    if s.is_null() {
+        bail!("Unexpected NULL string");
+    }
+    Ok(CStr::from_ptr(s).to_str()?)
+}
+
+fn build_engine(config_path: Option<&str>) -> Result<AkazaEngine> {
+    let config = match config_path {
+        Some(path) => Config::load_from_file(path)?,
+        None => Config::load()?,
+    };
+    let user_data = match UserData::load_from_default_path() {
+        Ok(user_data) => user_data,
+        Err(err) => {
+            error!("Cannot load user data: {}", err);
+            UserData::default()
+        }
+    };
+    let engine = BigramWordViterbiEngineBuilder::new(config.engine)
+        .user_data(Arc::new(Mutex::new(user_data)))
+        .build()?;
+    Ok(AkazaEngine { engine })
+}
+
+fn convert(engine: &AkazaEngine, yomi: &str) -> Result<AkazaResult> {
+    let clauses = engine
+        .engine
+        .convert(yomi, None)?
+        .into_iter()
+        .map(|clause| {
+            clause
+                .into_iter()
+                .map(|candidate| {
+                    Ok(ResultCandidate {
+                        surface: CString::new(candidate.surface_with_dynamic())?,
+                        yomi: CString::new(candidate.yomi.as_str())?,
+                        candidate,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(AkazaResult { clauses })
+}
+
+unsafe fn get_candidate<'a>(
+    result: *const AkazaResult,
+    clause: usize,
+    index: usize,
+) -> Option<&'a ResultCandidate> {
+    let result = result.as_ref()?;
+    result.clauses.get(clause)?.get(index)
+}
+
+/// 直前に呼び出した関数で発生したエラーのメッセージを返す。
+/// エラーが発生していない場合は NULL を返す。
+/// 返却された文字列は、同じスレッドで次に akaza の関数を呼び出すまで有効です。
+#[no_mangle]
+pub extern "C" fn akaza_last_error() -> *const c_char {
+    panic::catch_unwind(|| {
+        LAST_ERROR.with(|it| match it.borrow().as_ref() {
+            Some(msg) => msg.as_ptr(),
+            None => ptr::null(),
+        })
+    })
+    .unwrap_or(ptr::null())
+}
+
+/// 設定ファイルを元に変換エンジンを作成する。
+/// `config_path` が NULL の場合は `~/.config/akaza/config.yml` を読む。
+/// ユーザーの学習データは XDG データディレクトリから読み込まれる。
+/// 失敗した場合は NULL を返す。
+///
+/// # Safety
+/// `config_path` は NULL か、NUL 終端された UTF-8 文字列である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_engine_new(config_path: *const c_char) -> *mut AkazaEngine {
+    ffi_call(ptr::null_mut(), || {
+        let config_path = if config_path.is_null() {
+            None
+        } else {
+            Some(to_str(config_path)?)
+        };
+        Ok(Box::into_raw(Box::new(build_engine(config_path)?)))
+    })
+}
+
+/// 変換エンジンを解放する。
+///
+/// # Safety
+/// `engine` は `akaza_engine_new` が返したポインタか NULL である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_engine_free(engine: *mut AkazaEngine) {
+    ffi_call((), || {
+        if !engine.is_null() {
+            drop(Box::from_raw(engine));
+        }
+        Ok(())
+    })
+}
+
+/// よみがなをかな漢字変換する。
+/// 結果は `akaza_result_free` で解放すること。失敗した場合は NULL を返す。
+///
+/// # Safety
+/// `engine` は有効なエンジン、`yomi` は NUL 終端された UTF-8 文字列である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_engine_convert(
+    engine: *const AkazaEngine,
+    yomi: *const c_char,
+) -> *mut AkazaResult {
+    ffi_call(ptr::null_mut(), || {
+        let engine = engine.as_ref().ok_or_else(|| anyhow!("engine is NULL"))?;
+        Ok(Box::into_raw(Box::new(convert(engine, to_str(yomi)?)?)))
+    })
+}
+
+/// ユーザーが選択した候補を学習する。
+/// `selected` には、文節ごとに選択された候補のインデックスを `clause_count` 個渡す。
+/// 成功した場合は 0、失敗した場合は -1 を返す。
+///
+/// # Safety
+/// `engine` と `result` は有効なポインタ、`selected` は `clause_count` 個の要素をもつ配列である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_engine_learn(
+    engine: *mut AkazaEngine,
+    result: *const AkazaResult,
+    selected: *const usize,
+    clause_count: usize,
+) -> c_int {
+    ffi_call(-1, || {
+        let engine = engine.as_mut().ok_or_else(|| anyhow!("engine is NULL"))?;
+        if selected.is_null() && clause_count > 0 {
+            bail!("selected is NULL");
+        }
+
+        let mut candidates = Vec::with_capacity(clause_count);
+        for clause in 0..clause_count {
+            let index = *selected.add(clause);
+            let candidate = get_candidate(result, clause, index).ok_or_else(|| {
+                anyhow!(
+                    "Invalid candidate index: clause={}, index={}",
+                    clause,
+                    index
+                )
+            })?;
+            candidates.push(candidate.candidate.clone());
+        }
+        engine.engine.learn(&candidates);
+        Ok(0)
+    })
+}
+
+/// 学習結果をユーザーデータファイルに書き出す。
+/// 成功した場合は 0、失敗した場合は -1 を返す。
+///
+/// # Safety
+/// `engine` は有効なエンジンである必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_engine_save_user_data(engine: *const AkazaEngine) -> c_int {
+    ffi_call(-1, || {
+        let engine = engine.as_ref().ok_or_else(|| anyhow!("engine is NULL"))?;
+        engine
+            .engine
+            .user_data
+            .lock()
+            .map_err(|err| anyhow!("Cannot get mutex for saving user data: {}", err))?
+            .write_user_files()?;
+        Ok(0)
+    })
+}
+
+/// 変換結果を解放する。
+///
+/// # Safety
+/// `result` は `akaza_engine_convert` が返したポインタか NULL である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_result_free(result: *mut AkazaResult) {
+    ffi_call((), || {
+        if !result.is_null() {
+            drop(Box::from_raw(result));
+        }
+        Ok(())
+    })
+}
+
+/// 文節の数を返す。
+///
+/// # Safety
+/// `result` は有効な変換結果である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_result_clause_count(result: *const AkazaResult) -> usize {
+    ffi_call(0, || Ok(result.as_ref().map_or(0, |it| it.clauses.len())))
+}
+
+/// 指定された文節の候補数を返す。
+///
+/// # Safety
+/// `result` は有効な変換結果である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_result_candidate_count(
+    result: *const AkazaResult,
+    clause: usize,
+) -> usize {
+    ffi_call(0, || {
+        Ok(result
+            .as_ref()
+            .and_then(|it| it.clauses.get(clause))
+            .map_or(0, |it| it.len()))
+    })
+}
+
+/// 候補の表記を返す。範囲外の場合は NULL を返す。
+/// 返却された文字列は `result` が解放されるまで有効です。
+///
+/// # Safety
+/// `result` は有効な変換結果である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_result_surface(
+    result: *const AkazaResult,
+    clause: usize,
+    index: usize,
+) -> *const c_char {
+    ffi_call(ptr::null(), || {
+        Ok(get_candidate(result, clause, index).map_or(ptr::null(), |it| it.surface.as_ptr()))
+    })
+}
+
+/// 候補のよみがなを返す。範囲外の場合は NULL を返す。
+/// 返却された文字列は `result` が解放されるまで有効です。
+///
+/// # Safety
+/// `result` は有効な変換結果である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_result_yomi(
+    result: *const AkazaResult,
+    clause: usize,
+    index: usize,
+) -> *const c_char {
+    ffi_call(ptr::null(), || {
+        Ok(get_candidate(result, clause, index).map_or(ptr::null(), |it| it.yomi.as_ptr()))
+    })
+}
+
+/// 候補のコストを返す。範囲外の場合は NaN を返す。
+///
+/// # Safety
+/// `result` は有効な変換結果である必要がある。
+#[no_mangle]
+pub unsafe extern "C" fn akaza_result_cost(
+    result: *const AkazaResult,
+    clause: usize,
+    index: usize,
+) -> f32 {
+    ffi_call(f32::NAN, || {
+        Ok(get_candidate(result, clause, index).map_or(f32::NAN, |it| it.candidate.cost))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_accessors() -> anyhow::Result<()> {
+        let candidate = Candidate::new("わたし", "私", 1.5);
+        let result = AkazaResult {
+            clauses: vec![vec![ResultCandidate {
+                surface: CString::new(candidate.surface.as_str())?,
+                yomi: CString::new(candidate.yomi.as_str())?,
+                candidate,
+            }]],
+        };
+
+        unsafe {
+            assert_eq!(akaza_result_clause_count(&result), 1);
+            assert_eq!(akaza_result_candidate_count(&result, 0), 1);
+            assert_eq!(akaza_result_candidate_count(&result, 1), 0);
+            assert_eq!(
+                CStr::from_ptr(akaza_result_surface(&result, 0, 0)).to_str()?,
+                "私"
+            );
+            assert_eq!(
+                CStr::from_ptr(akaza_result_yomi(&result, 0, 0)).to_str()?,
+                "わたし"
+            );
+            assert_eq!(akaza_result_cost(&result, 0, 0), 1.5);
+            assert!(akaza_result_surface(&result, 0, 1).is_null());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ffi_call_catches_panic() {
+        let got = ffi_call(-1, || -> Result<c_int> { panic!("boom") });
+        assert_eq!(got, -1);
+        let msg = unsafe { CStr::from_ptr(akaza_last_error()) };
+        assert_eq!(msg.to_str().unwrap(), "panic: boom");
+    }
+
+    #[test]
+    fn test_last_error_is_cleared() {
+        let path = CString::new("/path/to/missing/config.yml").unwrap();
+        unsafe {
+            assert!(akaza_engine_new(path.as_ptr()).is_null());
+            assert!(!akaza_last_error().is_null());
+            // 成功したら、前回のエラーは残らない。
+            assert_eq!(akaza_result_clause_count(ptr::null()), 0);
+            assert!(akaza_last_error().is_null());
+        }
+    }
+
+    #[test]
+    fn test_engine_new_error() {
+        let path = CString::new("/path/to/missing/config.yml").unwrap();
+        unsafe {
+            assert!(akaza_engine_new(path.as_ptr()).is_null());
+            assert!(!akaza_last_error().is_null());
+        }
+    }
+}
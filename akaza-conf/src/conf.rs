@@ -1,26 +1,36 @@
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use gtk::glib::signal::Inhibit;
 use gtk::prelude::*;
-use gtk::{Application, ApplicationWindow, Button, Label, Notebook};
+use gtk::{Application, ApplicationWindow, Button, Label, Notebook, Widget};
 use gtk4 as gtk;
 use gtk4::gio::ApplicationFlags;
 use gtk4::{
     ComboBoxText, FileChooserAction, FileChooserDialog, Grid, ResponseType, ScrolledWindow, Window,
 };
-use log::{error, info};
+use log::{error, info, warn};
 
-use libakaza::config::{Config, DictConfig, DictEncoding, DictType, DictUsage, EngineConfig};
+use libakaza::config::{
+    Config, ConfigIssue, DictConfig, DictEncoding, DictType, DictUsage, EngineConfig,
+    CONFIG_VERSION,
+};
+
+use crate::conversion_pane::build_conversion_pane;
+use crate::keymap_pane::build_keymap_pane;
+use crate::romkan_pane::build_romkan_pane;
+use crate::user_dict_pane::build_user_dict_pane;
 
 pub fn open_configuration_window() -> Result<()> {
-    let config = Arc::new(Mutex::new(Config::load()?));
+    let (config, issues) = Config::load_with_issues()?;
+    let config = Arc::new(Mutex::new(config));
     let app = Application::new(Some("com.github.akaza.config"), ApplicationFlags::empty());
 
     app.connect_activate(move |app| {
-        connect_activate(app, config.clone()).unwrap();
+        if let Err(err) = connect_activate(app, config.clone(), &issues) {
+            error!("Cannot open the configuration window: {:#}", err);
+        }
     });
 
     let v: Vec<String> = Vec::new();
@@ -28,7 +38,11 @@ pub fn open_configuration_window() -> Result<()> {
     Ok(())
 }
 
-fn connect_activate(app: &Application, config: Arc<Mutex<Config>>) -> Result<()> {
+fn connect_activate(
+    app: &Application,
+    config: Arc<Mutex<Config>>,
+    issues: &[ConfigIssue],
+) -> Result<()> {
     let window = ApplicationWindow::builder()
         .application(app)
         .default_width(520)
@@ -38,18 +52,54 @@ fn connect_activate(app: &Application, config: Arc<Mutex<Config>>) -> Result<()>
 
     let notebook = Notebook::builder().vexpand(true).hexpand(true).build();
     notebook.append_page(
-        &build_core_pane(config.clone())?,
+        &pane_or_error(build_core_pane(config.clone())),
         Some(&Label::new(Some("基本設定"))),
     );
     notebook.append_page(
-        &build_dict_pane(config.clone())?,
+        &pane_or_error(build_dict_pane(config.clone())),
         Some(&Label::new(Some("辞書"))),
     );
+    notebook.append_page(
+        &pane_or_error(build_keymap_pane(config.clone())),
+        Some(&Label::new(Some("キーマップ"))),
+    );
+    notebook.append_page(
+        &pane_or_error(build_romkan_pane(config.clone())),
+        Some(&Label::new(Some("ローマ字テーブル"))),
+    );
+    notebook.append_page(
+        &pane_or_error(build_user_dict_pane()),
+        Some(&Label::new(Some("ユーザー辞書"))),
+    );
+    notebook.append_page(
+        &pane_or_error(build_conversion_pane(config.clone())),
+        Some(&Label::new(Some("変換テスト"))),
+    );
     notebook.append_page(&build_about_pane(), Some(&Label::new(Some("アバウト"))));
 
     let grid = Grid::builder().build();
 
-    grid.attach(&notebook, 0, 0, 6, 1);
+    // 設定ファイルに問題があれば、保存しなおす前に気づけるように表示しておく。
+    if !issues.is_empty() {
+        grid.attach(
+            &Label::builder()
+                .label(
+                    issues
+                        .iter()
+                        .map(|it| it.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                )
+                .xalign(0_f32)
+                .wrap(true)
+                .build(),
+            0,
+            0,
+            6,
+            1,
+        );
+    }
+    grid.attach(&notebook, 0, 1, 6, 1);
 
     let ok_button = Button::with_label("OK");
     let config = config;
@@ -58,34 +108,23 @@ fn connect_activate(app: &Application, config: Arc<Mutex<Config>>) -> Result<()>
         // TODO: 保存処理
         let config = config.lock().unwrap();
         let config = Config {
+            version: CONFIG_VERSION,
             keymap: config.keymap.to_string(),
             romkan: config.romkan.to_string(),
             engine: EngineConfig {
                 model: config.engine.model.to_string(),
                 dicts: config.engine.dicts.clone(),
                 dict_cache: true,
+                mmap: config.engine.mmap,
+                learning_half_life_days: config.engine.learning_half_life_days,
+                providers: config.engine.providers.clone(),
+                date_time: config.engine.date_time.clone(),
             },
         };
         info!("Saving config: {}", serde_yaml::to_string(&config).unwrap());
 
+        // ibus-akaza が設定ファイルの変更を検知して読み直すので、保存するだけでよい。
         config.save().unwrap();
-
-        // 最後に ibus restart をしちゃおう。設定の再読み込みとか実装するのは大変。
-        let output = Command::new("ibus").arg("restart").output().unwrap();
-
-        if !output.status.success() {
-            error!(
-                "Cannot run `ibus restart`: out={}, err={}",
-                String::from_utf8(output.stdout).unwrap(),
-                String::from_utf8(output.stderr).unwrap()
-            );
-        } else {
-            info!(
-                "Ran `ibus restart`: out={}, err={}",
-                String::from_utf8(output.stdout).unwrap(),
-                String::from_utf8(output.stderr).unwrap()
-            );
-        }
     });
     let cancel_button = Button::with_label("Cancel");
     {
@@ -95,8 +134,8 @@ fn connect_activate(app: &Application, config: Arc<Mutex<Config>>) -> Result<()>
             window_clone.close();
         });
     }
-    grid.attach(&ok_button, 4, 1, 1, 1);
-    grid.attach(&cancel_button, 5, 1, 1, 1);
+    grid.attach(&ok_button, 4, 2, 1, 1);
+    grid.attach(&cancel_button, 5, 2, 1, 1);
 
     window.set_child(Some(&grid));
 
@@ -111,13 +150,61 @@ fn connect_activate(app: &Application, config: Arc<Mutex<Config>>) -> Result<()>
     Ok(())
 }
 
+/// ペインを作れなかったときは、ほかのペインは使えるように、エラーをペインの中に表示する。
+fn pane_or_error<W: IsA<Widget>>(pane: Result<W>) -> Widget {
+    match pane {
+        Ok(pane) => pane.upcast(),
+        Err(err) => {
+            warn!("Cannot build the pane: {:#}", err);
+            Label::builder()
+                .label(&format!("読み込めませんでした: {:#}", err))
+                .xalign(0_f32)
+                .yalign(0_f32)
+                .wrap(true)
+                .selectable(true)
+                .build()
+                .upcast()
+        }
+    }
+}
+
+/// akaza-conf で編集したキーマップやローマ字テーブルの名前。
+pub(crate) const USER_FILE_NAME: &str = "user";
+
+/// 選択中のキーマップやローマ字テーブルのベース名を調べる。
+/// chain には、extends をたどったファイルのパスを渡す。
+/// akaza-conf で保存したものなら、extends しているものをベースにする。
+pub(crate) fn detect_base_name(chain: &[String]) -> String {
+    let name = |path: &String| {
+        Path::new(path)
+            .file_stem()
+            .map(|it| it.to_string_lossy().to_string())
+    };
+    match chain.first().and_then(name) {
+        Some(name) if name != USER_FILE_NAME => name,
+        _ => chain
+            .get(1)
+            .and_then(name)
+            .unwrap_or_else(|| "default".to_string()),
+    }
+}
+
+/// ベースにするキーマップやローマ字テーブルのファイルを探す。
+/// kind は "keymap" か "romkan"。
+pub(crate) fn find_base_path(kind: &str, base_name: &str) -> Result<String> {
+    let path = xdg::BaseDirectories::with_prefix("akaza")?
+        .find_data_file(format!("{}/{}.yml", kind, base_name))
+        .ok_or_else(|| anyhow!("Cannot find {}: {}", kind, base_name))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
 #[derive(Debug)]
-struct PathConfItem {
-    name: String,
-    path: String,
+pub(crate) struct PathConfItem {
+    pub(crate) name: String,
+    pub(crate) path: String,
 }
 
-fn get_list<P>(path: &str, filter: P) -> Vec<PathConfItem>
+pub(crate) fn get_list<P>(path: &str, filter: P) -> Vec<PathConfItem>
 where
     P: FnMut(&&PathBuf) -> bool,
 {
@@ -264,6 +351,7 @@ fn build_dict_pane(config: Arc<Mutex<Config>>) -> Result<ScrolledWindow> {
             for usage in vec![
                 DictUsage::Normal,
                 DictUsage::SingleTerm,
+                DictUsage::Emoji,
                 DictUsage::Disabled,
             ] {
                 cbt.append(Some(usage.as_str()), usage.text_jp());
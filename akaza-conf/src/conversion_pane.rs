@@ -0,0 +1,266 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use gtk::prelude::*;
+use gtk::{Button, Entry, Label};
+use gtk4 as gtk;
+use gtk4::{Grid, ScrolledWindow};
+use log::{info, warn};
+
+use libakaza::config::{Config, EngineConfig};
+use libakaza::engine::base::HenkanEngine;
+use libakaza::engine::bigram_word_viterbi_engine::{
+    BigramWordViterbiEngine, BigramWordViterbiEngineBuilder,
+};
+use libakaza::kana_kanji::marisa_kana_kanji_dict::MarisaKanaKanjiDict;
+use libakaza::lm::system_bigram::MarisaSystemBigramLM;
+use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
+use libakaza::romkan::RomKanConverter;
+use libakaza::user_side_data::user_data::UserData;
+
+/// 学習データの表示件数
+const TOP_N: usize = 30;
+
+type Engine =
+    BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM, MarisaKanaKanjiDict>;
+
+/// 変換のテストと、学習データの確認をする画面。
+///
+/// 保存前の設定でエンジンを作るので、辞書やモデルの変更を反映する前に変換結果を確かめられる。
+#[derive(Clone)]
+struct ConversionPane {
+    config: Arc<Mutex<Config>>,
+    user_data: Arc<Mutex<UserData>>,
+    /// エンジンと、そのエンジンを作ったときの設定
+    engine: Arc<Mutex<Option<(EngineConfig, Engine)>>>,
+    /// ローマ字かな変換器と、そのテーブルのパス
+    romkan: Arc<Mutex<Option<(String, RomKanConverter)>>>,
+    input: Entry,
+    result: Label,
+    candidates: Grid,
+    unigram: Grid,
+    bigram: Grid,
+}
+
+pub(crate) fn build_conversion_pane(config: Arc<Mutex<Config>>) -> Result<Grid> {
+    let pane = ConversionPane {
+        config,
+        user_data: Arc::new(Mutex::new(UserData::load_from_default_path()?)),
+        engine: Arc::new(Mutex::new(None)),
+        romkan: Arc::new(Mutex::new(None)),
+        input: Entry::builder()
+            .placeholder_text("読み(ローマ字も可)")
+            .hexpand(true)
+            .build(),
+        result: Label::builder().xalign(0_f32).selectable(true).build(),
+        candidates: Grid::builder().column_spacing(10).row_spacing(4).build(),
+        unigram: Grid::builder().column_spacing(10).row_spacing(4).build(),
+        bigram: Grid::builder().column_spacing(10).row_spacing(4).build(),
+    };
+
+    let grid = Grid::builder().column_spacing(10).row_spacing(4).build();
+
+    {
+        let pane_clone = pane.clone();
+        pane.input.connect_activate(move |_| pane_clone.convert());
+    }
+    grid.attach(&pane.input, 0, 0, 1, 1);
+    {
+        let convert_button = Button::with_label("変換");
+        let pane_clone = pane.clone();
+        convert_button.connect_clicked(move |_| pane_clone.convert());
+        grid.attach(&convert_button, 1, 0, 1, 1);
+    }
+    grid.attach(&pane.result, 0, 1, 2, 1);
+
+    let candidates_scroll = ScrolledWindow::builder()
+        .vexpand(true)
+        .hexpand(true)
+        .build();
+    candidates_scroll.set_child(Some(&pane.candidates));
+    grid.attach(&candidates_scroll, 0, 2, 2, 1);
+
+    grid.attach(
+        &Label::builder()
+            .label("よく使われた単語")
+            .xalign(0_f32)
+            .build(),
+        0,
+        3,
+        1,
+        1,
+    );
+    {
+        let reload_button = Button::with_label("読み直す");
+        let pane_clone = pane.clone();
+        reload_button.connect_clicked(move |_| pane_clone.reload_stats());
+        grid.attach(&reload_button, 1, 3, 1, 1);
+    }
+
+    let stats_grid = Grid::builder().column_spacing(20).build();
+    stats_grid.attach(&pane.unigram, 0, 0, 1, 1);
+    stats_grid.attach(&pane.bigram, 1, 0, 1, 1);
+    let stats_scroll = ScrolledWindow::builder()
+        .vexpand(true)
+        .hexpand(true)
+        .build();
+    stats_scroll.set_child(Some(&stats_grid));
+    grid.attach(&stats_scroll, 0, 4, 2, 1);
+
+    pane.refresh_stats();
+    Ok(grid)
+}
+
+impl ConversionPane {
+    /// 入力された読みを変換して、変換結果と候補の一覧を表示する。
+    fn convert(&self) {
+        while let Some(child) = self.candidates.first_child() {
+            self.candidates.remove(&child);
+        }
+
+        let src = self.input.text().to_string();
+        if src.is_empty() {
+            self.result.set_text("");
+            return;
+        }
+
+        let result = (|| -> Result<_> {
+            let yomi = self.to_hiragana(&src)?;
+            self.prepare_engine()?;
+            let engine = self.engine.lock().unwrap();
+            let (_, engine) = engine.as_ref().unwrap();
+            Ok(engine.convert(&yomi, None)?)
+        })();
+
+        match result {
+            Ok(clauses) => {
+                self.result.set_text(
+                    &clauses
+                        .iter()
+                        .map(|clause| clause[0].surface_with_dynamic())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                );
+                for (i, clause) in clauses.iter().enumerate() {
+                    let row = i as i32;
+                    self.candidates.attach(
+                        &Label::builder()
+                            .label(&clause[0].yomi)
+                            .xalign(0_f32)
+                            .build(),
+                        0,
+                        row,
+                        1,
+                        1,
+                    );
+                    self.candidates.attach(
+                        &Label::builder()
+                            .label(
+                                &clause
+                                    .iter()
+                                    .map(|it| it.surface_with_dynamic())
+                                    .collect::<Vec<_>>()
+                                    .join(" "),
+                            )
+                            .xalign(0_f32)
+                            .wrap(true)
+                            .build(),
+                        1,
+                        row,
+                        1,
+                        1,
+                    );
+                }
+            }
+            Err(err) => {
+                warn!("Cannot convert {}: {}", src, err);
+                self.result
+                    .set_text(&format!("変換できませんでした: {}", err));
+            }
+        }
+    }
+
+    /// 入力がローマ字なら、設定中のローマ字テーブルでかなにする。
+    fn to_hiragana(&self, src: &str) -> Result<String> {
+        let romkan_path = self.config.lock().unwrap().romkan.clone();
+        let mut romkan = self.romkan.lock().unwrap();
+        if romkan.as_ref().map(|(path, _)| path) != Some(&romkan_path) {
+            *romkan = Some((romkan_path.clone(), RomKanConverter::new(&romkan_path)?));
+        }
+        Ok(romkan.as_ref().unwrap().1.to_hiragana(src))
+    }
+
+    /// 設定が変わっていたらエンジンを作り直す。
+    /// 辞書の読み込みには時間がかかるので、変わっていなければ使いまわす。
+    fn prepare_engine(&self) -> Result<()> {
+        let config = self.config.lock().unwrap().engine.clone();
+        let mut engine = self.engine.lock().unwrap();
+        if engine.as_ref().map(|(c, _)| c) != Some(&config) {
+            info!("Building the engine for the conversion test");
+            let built = BigramWordViterbiEngineBuilder::new(config.clone())
+                .user_data(self.user_data.clone())
+                .build()?;
+            *engine = Some((config, built));
+        }
+        Ok(())
+    }
+
+    /// ibus-akaza が学習した結果を読み直す。
+    fn reload_stats(&self) {
+        match UserData::load_from_default_path() {
+            Ok(user_data) => {
+                *self.user_data.lock().unwrap() = user_data;
+                // エンジンはユーザー辞書から作ったかなトライや半減期の設定を持っているので、
+                // 次の変換のときに作り直す。
+                *self.engine.lock().unwrap() = None;
+            }
+            Err(err) => {
+                warn!("Cannot load user data: {}", err);
+            }
+        }
+        self.refresh_stats();
+    }
+
+    fn refresh_stats(&self) {
+        let bundle = self.user_data.lock().unwrap().export_bundle();
+
+        for (grid, title, entries) in [
+            (&self.unigram, "ユニグラム", bundle.top_unigram(TOP_N)),
+            (&self.bigram, "バイグラム", bundle.top_bigram(TOP_N)),
+        ] {
+            while let Some(child) = grid.first_child() {
+                grid.remove(&child);
+            }
+            grid.attach(
+                &Label::builder().label(title).xalign(0_f32).build(),
+                0,
+                0,
+                2,
+                1,
+            );
+            for (i, (key, entry)) in entries.iter().enumerate() {
+                let row = i as i32 + 1;
+                grid.attach(
+                    &Label::builder()
+                        .label(&key.replace('\t', " "))
+                        .xalign(0_f32)
+                        .build(),
+                    0,
+                    row,
+                    1,
+                    1,
+                );
+                grid.attach(
+                    &Label::builder()
+                        .label(&format!("{:.1}", entry.count))
+                        .xalign(1_f32)
+                        .build(),
+                    1,
+                    row,
+                    1,
+                    1,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use gtk::prelude::*;
+use gtk::{Button, ComboBoxText, Label};
+use gtk4 as gtk;
+use gtk4::gdk::ModifierType;
+use gtk4::glib::signal::Inhibit;
+use gtk4::{EventControllerKey, Grid, PropagationPhase, ScrolledWindow};
+use log::{info, warn};
+
+use libakaza::config::Config;
+use libakaza::keymap::{format_key, KeyBindings, KeyState, Keymap};
+
+use crate::conf::{detect_base_name, find_base_path, get_list, USER_FILE_NAME};
+
+/// キーマップの編集画面。
+///
+/// 編集結果は、選択したキーマップを extends して差分だけを書いたキーマップとして保存する。
+/// ベースのキーマップが更新されても、変更していない割り当てには追従できる。
+#[derive(Clone)]
+struct KeymapPane {
+    config: Arc<Mutex<Config>>,
+    /// ベースにするキーマップの名前と、その割り当て
+    base: Arc<Mutex<(String, KeyBindings)>>,
+    /// 編集中の割り当て
+    edited: Arc<Mutex<KeyBindings>>,
+    list: Grid,
+    state_cbt: ComboBoxText,
+    command_cbt: ComboBoxText,
+    capture_button: Button,
+    /// キー入力待ちかどうか
+    capturing: Arc<Mutex<bool>>,
+    /// 押されたキー("C-S-j" のような形式)
+    captured: Arc<Mutex<Option<String>>>,
+    status: Label,
+}
+
+pub(crate) fn build_keymap_pane(config: Arc<Mutex<Config>>) -> Result<Grid> {
+    let keymap_path = config.lock().unwrap().keymap.clone();
+    let base_name = detect_base_name(&Keymap::extends_chain(&keymap_path));
+    let base = Keymap::load_bindings(&find_base_path("keymap", &base_name)?)?;
+    let edited = Keymap::load_bindings(&keymap_path)?;
+
+    let pane = KeymapPane {
+        config,
+        base: Arc::new(Mutex::new((base_name.clone(), base))),
+        edited: Arc::new(Mutex::new(edited)),
+        list: Grid::builder().column_spacing(10).row_spacing(4).build(),
+        state_cbt: ComboBoxText::new(),
+        command_cbt: ComboBoxText::new(),
+        capture_button: Button::with_label("キーを入力"),
+        capturing: Arc::new(Mutex::new(false)),
+        captured: Arc::new(Mutex::new(None)),
+        status: Label::builder().xalign(0_f32).build(),
+    };
+
+    let grid = Grid::builder().column_spacing(10).row_spacing(4).build();
+
+    grid.attach(
+        &Label::builder()
+            .label("ベースにするキーマップ")
+            .xalign(0_f32)
+            .build(),
+        0,
+        0,
+        1,
+        1,
+    );
+    {
+        let cbt = ComboBoxText::new();
+        let keymaps = get_list("keymap", |f| {
+            f.to_string_lossy().ends_with(".yml")
+                && f.file_stem().unwrap().to_string_lossy() != USER_FILE_NAME
+        });
+        for item in keymaps {
+            cbt.append(Some(&item.name), &item.name);
+        }
+        cbt.set_active_id(Some(&base_name));
+        let pane_clone = pane.clone();
+        cbt.connect_changed(move |f| {
+            if let Some(id) = f.active_id() {
+                pane_clone.change_base(&id);
+            }
+        });
+        grid.attach(&cbt, 1, 0, 3, 1);
+    }
+
+    let scroll = ScrolledWindow::builder()
+        .vexpand(true)
+        .hexpand(true)
+        .build();
+    scroll.set_child(Some(&pane.list));
+    grid.attach(&scroll, 0, 1, 4, 1);
+
+    for state in KeyState::all() {
+        pane.state_cbt.append(Some(state.as_str()), state.text_jp());
+    }
+    pane.state_cbt
+        .set_active_id(Some(KeyState::Composition.as_str()));
+    grid.attach(&pane.state_cbt, 0, 2, 1, 1);
+
+    pane.update_commands();
+    grid.attach(&pane.command_cbt, 1, 2, 1, 1);
+
+    {
+        let pane_clone = pane.clone();
+        pane.capture_button.connect_clicked(move |button| {
+            *pane_clone.capturing.lock().unwrap() = true;
+            button.set_label("キーを押してください...");
+        });
+
+        // ボタン自身が Return や space を処理する前にキーを受けとる。
+        let controller = EventControllerKey::new();
+        controller.set_propagation_phase(PropagationPhase::Capture);
+        let pane_clone = pane.clone();
+        controller.connect_key_pressed(move |_, keyval, _, state| {
+            if !*pane_clone.capturing.lock().unwrap() {
+                return Inhibit(false);
+            }
+            let Some(name) = keyval.to_lower().name() else {
+                return Inhibit(true);
+            };
+            // 修飾キーだけが押された場合は、続けて押されるキーを待つ。
+            if ["Control_", "Shift_", "Alt_", "Meta_", "Super_"]
+                .iter()
+                .any(|it| name.starts_with(it))
+            {
+                return Inhibit(true);
+            }
+
+            let key = format_key(
+                state.contains(ModifierType::CONTROL_MASK),
+                state.contains(ModifierType::SHIFT_MASK),
+                &name,
+            );
+            *pane_clone.capturing.lock().unwrap() = false;
+            pane_clone.capture_button.set_label(&key);
+            *pane_clone.captured.lock().unwrap() = Some(key);
+            Inhibit(true)
+        });
+        pane.capture_button.add_controller(&controller);
+    }
+    grid.attach(&pane.capture_button, 2, 2, 1, 1);
+
+    {
+        let assign_button = Button::with_label("割り当て");
+        let pane_clone = pane.clone();
+        assign_button.connect_clicked(move |_| pane_clone.assign());
+        grid.attach(&assign_button, 3, 2, 1, 1);
+    }
+
+    {
+        let save_button = Button::with_label("保存");
+        let pane_clone = pane.clone();
+        save_button.connect_clicked(move |_| pane_clone.save());
+        grid.attach(&save_button, 3, 3, 1, 1);
+    }
+    grid.attach(&pane.status, 0, 3, 3, 1);
+
+    pane.refresh();
+    Ok(grid)
+}
+
+impl KeymapPane {
+    /// 割り当ての一覧を作り直す。
+    fn refresh(&self) {
+        while let Some(child) = self.list.first_child() {
+            self.list.remove(&child);
+        }
+
+        let base = self.base.lock().unwrap().1.clone();
+        let edited = self.edited.lock().unwrap().clone();
+        for (i, ((state, key), command)) in edited.iter().enumerate() {
+            let row = i as i32;
+            self.list.attach(
+                &Label::builder()
+                    .label(state.text_jp())
+                    .xalign(0_f32)
+                    .build(),
+                0,
+                row,
+                1,
+                1,
+            );
+            self.list.attach(
+                &Label::builder().label(key).xalign(0_f32).build(),
+                1,
+                row,
+                1,
+                1,
+            );
+            // ベースから変更したものには印をつける。
+            let label = if base.get(&(*state, key.clone())) == Some(command) {
+                command.clone()
+            } else {
+                format!("{} *", command)
+            };
+            self.list.attach(
+                &Label::builder().label(&label).xalign(0_f32).build(),
+                2,
+                row,
+                1,
+                1,
+            );
+
+            let delete_button = Button::with_label("削除");
+            {
+                let pane = self.clone();
+                let (state, key) = (*state, key.clone());
+                delete_button.connect_clicked(move |_| pane.delete(state, &key));
+            }
+            self.list.attach(&delete_button, 3, row, 1, 1);
+        }
+    }
+
+    /// コマンドの候補を更新する。
+    fn update_commands(&self) {
+        let active = self.command_cbt.active_id();
+        let commands = self
+            .base
+            .lock()
+            .unwrap()
+            .1
+            .values()
+            .chain(self.edited.lock().unwrap().values())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        self.command_cbt.remove_all();
+        for command in commands {
+            self.command_cbt.append(Some(&command), &command);
+        }
+        if let Some(active) = active {
+            self.command_cbt.set_active_id(Some(&active));
+        }
+    }
+
+    /// ベースのキーマップを切り替える。編集内容は捨てて、ベースの割り当てからやりなおす。
+    fn change_base(&self, base_name: &str) {
+        match find_base_path("keymap", base_name).and_then(|path| Keymap::load_bindings(&path)) {
+            Ok(base) => {
+                info!("Change the base keymap to {}", base_name);
+                *self.edited.lock().unwrap() = base.clone();
+                *self.base.lock().unwrap() = (base_name.to_string(), base);
+                self.status
+                    .set_text(&format!("{} をベースに編集します", base_name));
+            }
+            Err(err) => {
+                warn!("Cannot load keymap {}: {}", base_name, err);
+                self.status
+                    .set_text(&format!("キーマップを読み込めませんでした: {}", err));
+            }
+        }
+        self.update_commands();
+        self.refresh();
+    }
+
+    /// 入力したキーにコマンドを割り当てる。
+    /// 同じ状態で同じキーに別のコマンドが割り当てられていたら、置き換えたことを表示する。
+    fn assign(&self) {
+        let Some(state) = self
+            .state_cbt
+            .active_id()
+            .and_then(|id| KeyState::from(&id).ok())
+        else {
+            self.status.set_text("状態を選択してください");
+            return;
+        };
+        let Some(command) = self.command_cbt.active_id() else {
+            self.status.set_text("コマンドを選択してください");
+            return;
+        };
+        let Some(key) = self.captured.lock().unwrap().clone() else {
+            self.status.set_text("割り当てるキーを入力してください");
+            return;
+        };
+        let command = command.to_string();
+
+        let prev = self
+            .edited
+            .lock()
+            .unwrap()
+            .insert((state, key.clone()), command.clone());
+        match prev {
+            Some(prev) if prev != command => self.status.set_text(&format!(
+                "{}: {} は {} に割り当てられていたので、{} に置き換えました",
+                state.text_jp(),
+                key,
+                prev,
+                command
+            )),
+            _ => self.status.set_text(&format!(
+                "{}: {} を {} に割り当てました",
+                state.text_jp(),
+                key,
+                command
+            )),
+        }
+        self.refresh();
+    }
+
+    fn delete(&self, state: KeyState, key: &str) {
+        self.edited
+            .lock()
+            .unwrap()
+            .remove(&(state, key.to_string()));
+        self.status.set_text(&format!(
+            "{}: {} の割り当てを削除しました",
+            state.text_jp(),
+            key
+        ));
+        self.refresh();
+    }
+
+    /// ベースとの差分をユーザーキーマップとして保存し、設定で選択する。
+    fn save(&self) {
+        let result = (|| -> Result<String> {
+            let (base_name, base) = self.base.lock().unwrap().clone();
+            let keymap = Keymap::from_diff(&base_name, &base, &self.edited.lock().unwrap());
+            let path = xdg::BaseDirectories::with_prefix("akaza")?
+                .place_data_file(format!("keymap/{}.yml", USER_FILE_NAME))?;
+            let path = path.to_string_lossy().to_string();
+            keymap.save(&path)?;
+            Ok(path)
+        })();
+
+        match result {
+            Ok(path) => {
+                self.config.lock().unwrap().keymap = path.clone();
+                self.status.set_text(&format!(
+                    "{} に保存しました。OK を押すと設定に反映されます",
+                    path
+                ));
+            }
+            Err(err) => {
+                warn!("Cannot save keymap: {}", err);
+                self.status
+                    .set_text(&format!("キーマップを保存できませんでした: {}", err));
+            }
+        }
+    }
+}
@@ -1 +1,5 @@
 pub mod conf;
+mod conversion_pane;
+mod keymap_pane;
+mod romkan_pane;
+mod user_dict_pane;
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use gtk::prelude::*;
+use gtk::{Button, ComboBoxText, Entry, Label, SearchEntry};
+use gtk4 as gtk;
+use gtk4::{Grid, ScrolledWindow};
+use log::{info, warn};
+
+use libakaza::config::Config;
+use libakaza::romkan::{find_prefix_conflicts, load_romkan_map, RomKanConfig};
+
+use crate::conf::{detect_base_name, find_base_path, get_list, USER_FILE_NAME};
+
+/// ローマ字かな変換テーブルの編集画面。
+///
+/// キーマップと同様に、選択したテーブルを extends して差分だけを書いたファイルとして保存する。
+#[derive(Clone)]
+struct RomkanPane {
+    config: Arc<Mutex<Config>>,
+    /// ベースにするテーブルの名前と、その内容
+    base: Arc<Mutex<(String, HashMap<String, String>)>>,
+    /// 編集中のテーブル
+    edited: Arc<Mutex<HashMap<String, String>>>,
+    list: Grid,
+    search: SearchEntry,
+    roma: Entry,
+    kana: Entry,
+    status: Label,
+}
+
+pub(crate) fn build_romkan_pane(config: Arc<Mutex<Config>>) -> Result<Grid> {
+    let romkan_path = config.lock().unwrap().romkan.clone();
+    let base_name = detect_base_name(&RomKanConfig::extends_chain(&romkan_path));
+    let base = load_romkan_map(&find_base_path("romkan", &base_name)?)?;
+    let edited = load_romkan_map(&romkan_path)?;
+
+    let pane = RomkanPane {
+        config,
+        base: Arc::new(Mutex::new((base_name.clone(), base))),
+        edited: Arc::new(Mutex::new(edited)),
+        list: Grid::builder().column_spacing(10).row_spacing(4).build(),
+        search: SearchEntry::builder().hexpand(true).build(),
+        roma: Entry::builder().placeholder_text("ローマ字").build(),
+        kana: Entry::builder().placeholder_text("かな").build(),
+        status: Label::builder().xalign(0_f32).build(),
+    };
+
+    let grid = Grid::builder().column_spacing(10).row_spacing(4).build();
+
+    grid.attach(
+        &Label::builder()
+            .label("ベースにするローマ字テーブル")
+            .xalign(0_f32)
+            .build(),
+        0,
+        0,
+        1,
+        1,
+    );
+    {
+        let cbt = ComboBoxText::new();
+        let romkans = get_list("romkan", |f| {
+            f.to_string_lossy().ends_with(".yml")
+                && f.file_stem().unwrap().to_string_lossy() != USER_FILE_NAME
+        });
+        for item in romkans {
+            cbt.append(Some(&item.name), &item.name);
+        }
+        cbt.set_active_id(Some(&base_name));
+        let pane_clone = pane.clone();
+        cbt.connect_changed(move |f| {
+            if let Some(id) = f.active_id() {
+                pane_clone.change_base(&id);
+            }
+        });
+        grid.attach(&cbt, 1, 0, 3, 1);
+    }
+
+    {
+        let pane_clone = pane.clone();
+        pane.search
+            .connect_search_changed(move |_| pane_clone.refresh());
+    }
+    grid.attach(&pane.search, 0, 1, 4, 1);
+
+    let scroll = ScrolledWindow::builder()
+        .vexpand(true)
+        .hexpand(true)
+        .build();
+    scroll.set_child(Some(&pane.list));
+    grid.attach(&scroll, 0, 2, 4, 1);
+
+    grid.attach(&pane.roma, 0, 3, 1, 1);
+    grid.attach(&pane.kana, 1, 3, 1, 1);
+    {
+        let add_button = Button::with_label("追加");
+        let pane_clone = pane.clone();
+        add_button.connect_clicked(move |_| pane_clone.add());
+        grid.attach(&add_button, 2, 3, 1, 1);
+    }
+    {
+        let save_button = Button::with_label("保存");
+        let pane_clone = pane.clone();
+        save_button.connect_clicked(move |_| pane_clone.save());
+        grid.attach(&save_button, 3, 3, 1, 1);
+    }
+    grid.attach(&pane.status, 0, 4, 4, 1);
+
+    pane.refresh();
+    Ok(grid)
+}
+
+impl RomkanPane {
+    /// 検索条件にあうエントリの一覧を作り直す。
+    fn refresh(&self) {
+        while let Some(child) = self.list.first_child() {
+            self.list.remove(&child);
+        }
+
+        let query = self.search.text().to_string();
+        let base = self.base.lock().unwrap().1.clone();
+        let mut entries = self
+            .edited
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(roma, kana)| {
+                query.is_empty() || roma.contains(&query) || kana.contains(&query)
+            })
+            .map(|(roma, kana)| (roma.clone(), kana.clone()))
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        for (i, (roma, kana)) in entries.into_iter().enumerate() {
+            let row = i as i32;
+            self.list.attach(
+                &Label::builder().label(&roma).xalign(0_f32).build(),
+                0,
+                row,
+                1,
+                1,
+            );
+            // ベースから変更したものには印をつける。
+            let label = if base.get(&roma) == Some(&kana) {
+                kana.clone()
+            } else {
+                format!("{} *", kana)
+            };
+            self.list.attach(
+                &Label::builder().label(&label).xalign(0_f32).build(),
+                1,
+                row,
+                1,
+                1,
+            );
+
+            let edit_button = Button::with_label("編集");
+            {
+                let pane = self.clone();
+                let (roma, kana) = (roma.clone(), kana.clone());
+                edit_button.connect_clicked(move |_| {
+                    pane.roma.set_text(&roma);
+                    pane.kana.set_text(&kana);
+                });
+            }
+            self.list.attach(&edit_button, 2, row, 1, 1);
+
+            let delete_button = Button::with_label("削除");
+            {
+                let pane = self.clone();
+                delete_button.connect_clicked(move |_| pane.delete(&roma));
+            }
+            self.list.attach(&delete_button, 3, row, 1, 1);
+        }
+    }
+
+    /// ベースのテーブルを切り替える。編集内容は捨てて、ベースのテーブルからやりなおす。
+    fn change_base(&self, base_name: &str) {
+        match find_base_path("romkan", base_name).and_then(|path| load_romkan_map(&path)) {
+            Ok(base) => {
+                info!("Change the base romkan table to {}", base_name);
+                *self.edited.lock().unwrap() = base.clone();
+                *self.base.lock().unwrap() = (base_name.to_string(), base);
+                self.status
+                    .set_text(&format!("{} をベースに編集します", base_name));
+            }
+            Err(err) => {
+                warn!("Cannot load romkan table {}: {}", base_name, err);
+                self.status
+                    .set_text(&format!("ローマ字テーブルを読み込めませんでした: {}", err));
+            }
+        }
+        self.refresh();
+    }
+
+    /// フォームの内容を追加する。同じローマ字のエントリがあれば上書きする。
+    /// 前方一致で衝突するエントリがあれば警告する。
+    fn add(&self) {
+        let roma = self.roma.text().trim().to_string();
+        let kana = self.kana.text().trim().to_string();
+        if roma.is_empty() || kana.is_empty() {
+            self.status.set_text("ローマ字とかなを入力してください");
+            return;
+        }
+
+        let (prev, conflicts) = {
+            let mut edited = self.edited.lock().unwrap();
+            let prev = edited.insert(roma.clone(), kana.clone());
+            (prev, find_prefix_conflicts(&edited, &roma))
+        };
+
+        let mut message = match prev {
+            Some(prev) if prev != kana => {
+                format!("{}: {} を {} で上書きしました", roma, prev, kana)
+            }
+            _ => format!("{}: {} を追加しました", roma, kana),
+        };
+        if !conflicts.is_empty() {
+            message += &format!("(前方一致するエントリがあります: {})", conflicts.join(", "));
+        }
+        self.status.set_text(&message);
+
+        self.roma.set_text("");
+        self.kana.set_text("");
+        self.refresh();
+    }
+
+    fn delete(&self, roma: &str) {
+        self.edited.lock().unwrap().remove(roma);
+        self.status.set_text(&format!("{} を削除しました", roma));
+        self.refresh();
+    }
+
+    /// ベースとの差分をユーザーのローマ字テーブルとして保存し、設定で選択する。
+    fn save(&self) {
+        let result = (|| -> Result<String> {
+            let (base_name, base) = self.base.lock().unwrap().clone();
+            let romkan = RomKanConfig::from_diff(&base_name, &base, &self.edited.lock().unwrap());
+            let path = xdg::BaseDirectories::with_prefix("akaza")?
+                .place_data_file(format!("romkan/{}.yml", USER_FILE_NAME))?;
+            let path = path.to_string_lossy().to_string();
+            romkan.save(&path)?;
+            Ok(path)
+        })();
+
+        match result {
+            Ok(path) => {
+                self.config.lock().unwrap().romkan = path.clone();
+                self.status.set_text(&format!(
+                    "{} に保存しました。OK を押すと設定に反映されます",
+                    path
+                ));
+            }
+            Err(err) => {
+                warn!("Cannot save romkan table: {}", err);
+                self.status
+                    .set_text(&format!("ローマ字テーブルを保存できませんでした: {}", err));
+            }
+        }
+    }
+}
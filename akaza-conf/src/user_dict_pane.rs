@@ -0,0 +1,337 @@
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use encoding_rs::EUC_JP;
+use gtk::prelude::*;
+use gtk::{Button, Entry, Label, SearchEntry};
+use gtk4 as gtk;
+use gtk4::{FileChooserAction, FileChooserDialog, Grid, ResponseType, ScrolledWindow, Window};
+use log::{info, warn};
+
+use libakaza::dict::skk::read::parse_skkdict;
+use libakaza::dict::skk::write::write_skk_dict;
+use libakaza::user_side_data::user_data::UserData;
+
+/// ユーザー辞書の管理画面。
+///
+/// ユーザー辞書には、ユーザーが登録した単語と、変換時に学習した複合語の両方が入っている。
+/// 変更はすぐにユーザー辞書のファイルに書き出すので、ibus-akaza が検知して読み直す。
+#[derive(Clone)]
+struct UserDictPane {
+    user_data: Arc<Mutex<UserData>>,
+    list: Grid,
+    search: SearchEntry,
+    yomi: Entry,
+    surface: Entry,
+    add_button: Button,
+    /// 編集中のエントリ(読み, 表記)
+    editing: Arc<Mutex<Option<(String, String)>>>,
+    status: Label,
+}
+
+pub(crate) fn build_user_dict_pane() -> Result<Grid> {
+    let user_data = Arc::new(Mutex::new(UserData::load_from_default_path()?));
+
+    let pane = UserDictPane {
+        user_data,
+        list: Grid::builder().column_spacing(10).row_spacing(4).build(),
+        search: SearchEntry::builder().hexpand(true).build(),
+        yomi: Entry::builder().placeholder_text("読み").build(),
+        surface: Entry::builder().placeholder_text("表記").build(),
+        add_button: Button::with_label("追加"),
+        editing: Arc::new(Mutex::new(None)),
+        status: Label::builder().xalign(0_f32).build(),
+    };
+
+    let grid = Grid::builder().column_spacing(10).row_spacing(4).build();
+
+    grid.attach(
+        &Label::builder()
+            .label("ユーザー辞書(登録した単語と、学習した複合語)")
+            .xalign(0_f32)
+            .build(),
+        0,
+        0,
+        4,
+        1,
+    );
+
+    {
+        let pane_clone = pane.clone();
+        pane.search
+            .connect_search_changed(move |_| pane_clone.refresh());
+    }
+    grid.attach(&pane.search, 0, 1, 4, 1);
+
+    let scroll = ScrolledWindow::builder()
+        .vexpand(true)
+        .hexpand(true)
+        .build();
+    scroll.set_child(Some(&pane.list));
+    grid.attach(&scroll, 0, 2, 4, 1);
+
+    grid.attach(&pane.yomi, 0, 3, 1, 1);
+    grid.attach(&pane.surface, 1, 3, 1, 1);
+    {
+        let pane_clone = pane.clone();
+        pane.add_button.connect_clicked(move |_| pane_clone.add());
+    }
+    grid.attach(&pane.add_button, 2, 3, 1, 1);
+    {
+        let cancel_button = Button::with_label("クリア");
+        let pane_clone = pane.clone();
+        cancel_button.connect_clicked(move |_| pane_clone.clear_form());
+        grid.attach(&cancel_button, 3, 3, 1, 1);
+    }
+
+    {
+        let import_button = Button::with_label("SKK 辞書からインポート");
+        let pane_clone = pane.clone();
+        import_button.connect_clicked(move |_| pane_clone.open_import_dialog());
+        grid.attach(&import_button, 0, 4, 2, 1);
+    }
+    {
+        let export_button = Button::with_label("SKK 辞書にエクスポート");
+        let pane_clone = pane.clone();
+        export_button.connect_clicked(move |_| pane_clone.open_export_dialog());
+        grid.attach(&export_button, 2, 4, 2, 1);
+    }
+    grid.attach(&pane.status, 0, 5, 4, 1);
+
+    pane.refresh();
+    Ok(grid)
+}
+
+impl UserDictPane {
+    /// 検索条件にあうエントリの一覧を作り直す。
+    fn refresh(&self) {
+        while let Some(child) = self.list.first_child() {
+            self.list.remove(&child);
+        }
+
+        let query = self.search.text().to_string();
+        let mut entries = self
+            .user_data
+            .lock()
+            .unwrap()
+            .dict
+            .iter()
+            .flat_map(|(yomi, surfaces)| {
+                surfaces
+                    .iter()
+                    .map(|surface| (yomi.clone(), surface.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|(yomi, surface)| {
+                query.is_empty() || yomi.contains(&query) || surface.contains(&query)
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        for (i, (yomi, surface)) in entries.into_iter().enumerate() {
+            let row = i as i32;
+            self.list.attach(
+                &Label::builder().label(&yomi).xalign(0_f32).build(),
+                0,
+                row,
+                1,
+                1,
+            );
+            self.list.attach(
+                &Label::builder().label(&surface).xalign(0_f32).build(),
+                1,
+                row,
+                1,
+                1,
+            );
+
+            let edit_button = Button::with_label("編集");
+            {
+                let pane = self.clone();
+                let (yomi, surface) = (yomi.clone(), surface.clone());
+                edit_button.connect_clicked(move |_| pane.start_edit(&yomi, &surface));
+            }
+            self.list.attach(&edit_button, 2, row, 1, 1);
+
+            let delete_button = Button::with_label("削除");
+            {
+                let pane = self.clone();
+                delete_button.connect_clicked(move |_| pane.delete(&yomi, &surface));
+            }
+            self.list.attach(&delete_button, 3, row, 1, 1);
+        }
+    }
+
+    /// ユーザー辞書に変更を加えて、ファイルに書き出す。
+    /// ibus-akaza が学習した複合語を上書きしてしまわないように、変更する直前にファイルから読み直す。
+    /// 学習中の統計データは ibus-akaza が管理しているので、ここでは書き出さない。
+    fn update<F>(&self, message: &str, f: F)
+    where
+        F: FnOnce(&mut UserData),
+    {
+        let result = {
+            let mut user_data = self.user_data.lock().unwrap();
+            if let Err(err) = user_data.reload_user_dict() {
+                // 初回はファイルがないので、そのまま続ける。
+                info!("Cannot reload user dictionary: {}", err);
+            }
+            f(&mut user_data);
+            user_data.write_user_dict()
+        };
+
+        match result {
+            Ok(_) => self.status.set_text(message),
+            Err(err) => {
+                warn!("Cannot save user dictionary: {}", err);
+                self.status
+                    .set_text(&format!("ユーザー辞書を保存できませんでした: {}", err));
+            }
+        }
+        self.refresh();
+    }
+
+    fn clear_form(&self) {
+        *self.editing.lock().unwrap() = None;
+        self.yomi.set_text("");
+        self.surface.set_text("");
+        self.add_button.set_label("追加");
+    }
+
+    fn start_edit(&self, yomi: &str, surface: &str) {
+        *self.editing.lock().unwrap() = Some((yomi.to_string(), surface.to_string()));
+        self.yomi.set_text(yomi);
+        self.surface.set_text(surface);
+        self.add_button.set_label("更新");
+    }
+
+    /// フォームの内容を登録する。編集中なら、元のエントリを置き換える。
+    fn add(&self) {
+        let yomi = self.yomi.text().trim().to_string();
+        let surface = self.surface.text().trim().to_string();
+        if yomi.is_empty() || surface.is_empty() {
+            self.status.set_text("読みと表記を入力してください");
+            return;
+        }
+
+        let editing = self.editing.lock().unwrap().take();
+        self.clear_form();
+        self.update(
+            &format!("{} /{}/ を登録しました", yomi, surface),
+            |user_data| {
+                if let Some((orig_yomi, orig_surface)) = editing {
+                    info!(
+                        "Update user dictionary: {}/{} -> {}/{}",
+                        orig_yomi, orig_surface, yomi, surface
+                    );
+                    user_data.unregister_word(&orig_yomi, &orig_surface);
+                } else {
+                    info!("Register to user dictionary: {}/{}", yomi, surface);
+                }
+                user_data.register_word(&yomi, &surface);
+            },
+        );
+    }
+
+    fn delete(&self, yomi: &str, surface: &str) {
+        info!("Delete from user dictionary: {}/{}", yomi, surface);
+        self.update(
+            &format!("{} /{}/ を削除しました", yomi, surface),
+            |user_data| user_data.unregister_word(yomi, surface),
+        );
+    }
+
+    /// SKK 辞書の内容をユーザー辞書に追加する。
+    /// UTF-8 として読めなければ EUC-JP として読む。
+    fn import(&self, path: &Path) -> Result<()> {
+        let buf = fs::read(path)?;
+        let src = match String::from_utf8(buf) {
+            Ok(src) => src,
+            Err(err) => EUC_JP.decode(err.as_bytes()).0.to_string(),
+        };
+        let dict = parse_skkdict(&src)?;
+        let count: usize = dict.values().map(|it| it.len()).sum();
+
+        self.update(
+            &format!(
+                "{} から {} 件インポートしました",
+                path.to_string_lossy(),
+                count
+            ),
+            |user_data| {
+                for (yomi, surfaces) in dict {
+                    // register_word は先頭に追加するので、逆順に登録して辞書内の順序を保つ。
+                    for surface in surfaces.iter().rev() {
+                        user_data.register_word(&yomi, surface);
+                    }
+                }
+            },
+        );
+        Ok(())
+    }
+
+    fn export(&self, path: &Path) -> Result<()> {
+        let dict = self.user_data.lock().unwrap().dict.clone();
+        write_skk_dict(&path.to_string_lossy(), vec![dict])
+    }
+
+    fn open_import_dialog(&self) {
+        let dialog = FileChooserDialog::new(
+            Some("インポートする SKK 辞書の選択"),
+            None::<&Window>,
+            FileChooserAction::Open,
+            &[
+                ("開く", ResponseType::Accept),
+                ("キャンセル", ResponseType::Cancel),
+            ],
+        );
+        let pane = self.clone();
+        dialog.connect_response(move |dialog, resp| {
+            if resp == ResponseType::Accept {
+                if let Some(path) = dialog.file().and_then(|it| it.path()) {
+                    if let Err(err) = pane.import(&path) {
+                        warn!("Cannot import {:?}: {}", path, err);
+                        pane.status
+                            .set_text(&format!("インポートできませんでした: {}", err));
+                    }
+                }
+            }
+            dialog.close();
+        });
+        dialog.show();
+    }
+
+    fn open_export_dialog(&self) {
+        let dialog = FileChooserDialog::new(
+            Some("エクスポート先の選択"),
+            None::<&Window>,
+            FileChooserAction::Save,
+            &[
+                ("保存", ResponseType::Accept),
+                ("キャンセル", ResponseType::Cancel),
+            ],
+        );
+        dialog.set_current_name("SKK-JISYO.user");
+        let pane = self.clone();
+        dialog.connect_response(move |dialog, resp| {
+            if resp == ResponseType::Accept {
+                if let Some(path) = dialog.file().and_then(|it| it.path()) {
+                    match pane.export(&path) {
+                        Ok(_) => pane.status.set_text(&format!(
+                            "{} にエクスポートしました",
+                            path.to_string_lossy()
+                        )),
+                        Err(err) => {
+                            warn!("Cannot export {:?}: {}", path, err);
+                            pane.status
+                                .set_text(&format!("エクスポートできませんでした: {}", err));
+                        }
+                    }
+                }
+            }
+            dialog.close();
+        });
+        dialog.show();
+    }
+}
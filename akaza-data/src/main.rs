@@ -5,6 +5,7 @@ use std::io::Write;
 use clap::{Parser, Subcommand};
 
 use crate::subcmd::check::check;
+use crate::subcmd::convert_model::convert_model;
 use crate::subcmd::dump_bigram_dict::dump_bigram_dict;
 use crate::subcmd::dump_unigram_dict::dump_unigram_dict;
 use crate::subcmd::evaluate::evaluate;
@@ -13,6 +14,7 @@ use crate::subcmd::make_dict::make_system_dict;
 use crate::subcmd::make_stats_system_bigram_lm::make_stats_system_bigram_lm;
 use crate::subcmd::make_stats_system_unigram_lm::make_stats_system_unigram_lm;
 use crate::subcmd::tokenize::tokenize;
+use crate::subcmd::user_data::{export_user_data, import_user_data, merge_user_data};
 use crate::subcmd::vocab::vocab;
 use crate::subcmd::wfreq::wfreq;
 
@@ -61,6 +63,12 @@ enum Commands {
 
     DumpUnigramDict(DumpUnigramDictArgs),
     DumpBigramDict(DumpBigramDictArgs),
+
+    #[clap(arg_required_else_help = true)]
+    ConvertModel(ConvertModelArgs),
+
+    #[clap(arg_required_else_help = true)]
+    UserData(UserDataArgs),
 }
 
 /// コーパスを形態素解析機でトーカナイズする
@@ -187,6 +195,41 @@ struct DumpBigramDictArgs {
     bigram_file: String,
 }
 
+/// marisa-trie 形式のモデルを、pure-rust feature 用のトライ形式に変換する
+#[derive(Debug, clap::Args)]
+struct ConvertModelArgs {
+    src_dir: String,
+    dst_dir: String,
+}
+
+/// ユーザーの学習データを、複数のマシン間で同期するためにエクスポート/インポートする
+#[derive(Debug, clap::Args)]
+struct UserDataArgs {
+    #[clap(subcommand)]
+    command: UserDataCommands,
+}
+
+#[derive(Debug, Subcommand)]
+enum UserDataCommands {
+    /// 学習データをバンドルファイルに書き出す
+    Export { output: String },
+    /// バンドルファイルの内容で学習データを置き換える。
+    /// ibus-akaza を停止してから実行すること。
+    Import {
+        /// 置き換えるのではなく、手元の学習データとマージする
+        #[arg(short, long, default_value_t = false)]
+        merge: bool,
+        input: String,
+    },
+    /// 複数のバンドルファイルをマージする
+    Merge {
+        #[arg(short, long)]
+        output: String,
+        #[arg(required = true)]
+        inputs: Vec<String>,
+    },
+}
+
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
@@ -260,5 +303,11 @@ fn main() -> anyhow::Result<()> {
         Commands::DumpBigramDict(opt) => {
             dump_bigram_dict(opt.unigram_file.as_str(), opt.bigram_file.as_str())
         }
+        Commands::ConvertModel(opt) => convert_model(&opt.src_dir, &opt.dst_dir),
+        Commands::UserData(opt) => match opt.command {
+            UserDataCommands::Export { output } => export_user_data(&output),
+            UserDataCommands::Import { merge, input } => import_user_data(&input, merge),
+            UserDataCommands::Merge { output, inputs } => merge_user_data(&inputs, &output),
+        },
     }
 }
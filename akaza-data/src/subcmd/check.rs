@@ -4,7 +4,10 @@ use std::sync::{Arc, Mutex};
 
 use log::info;
 
-use libakaza::config::{DictConfig, DictEncoding, DictType, DictUsage, EngineConfig};
+use libakaza::config::{
+    DateTimeConfig, DictConfig, DictEncoding, DictType, DictUsage, EngineConfig,
+    DEFAULT_LEARNING_HALF_LIFE_DAYS, DEFAULT_PROVIDERS,
+};
 use libakaza::engine::bigram_word_viterbi_engine::BigramWordViterbiEngineBuilder;
 use libakaza::user_side_data::user_data::UserData;
 
@@ -39,6 +42,10 @@ pub fn check(
         dicts,
         model: model_dir.to_string(),
         dict_cache: false,
+        mmap: true,
+        learning_half_life_days: DEFAULT_LEARNING_HALF_LIFE_DAYS,
+        providers: DEFAULT_PROVIDERS.to_vec(),
+        date_time: DateTimeConfig::default(),
     });
     if user_data {
         info!("Enabled user data");
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use log::info;
+
+use marisa_sys::ffi::Marisa;
+use marisa_sys::louds::{LoudsKeyset, LoudsTrie};
+
+/// marisa-trie 形式のモデルディレクトリを、pure-rust feature で利用する LOUDS トライ形式に変換する。
+///
+/// バイグラムモデルはユニグラムモデルの単語 ID を参照しているので、
+/// ユニグラムモデルの ID の振り直しにあわせて書き換える。
+pub fn convert_model(src_dir: &str, dst_dir: &str) -> Result<()> {
+    let src_dir = Path::new(src_dir);
+    let dst_dir = Path::new(dst_dir);
+    fs::create_dir_all(dst_dir)?;
+
+    // unigram
+    let unigram_keys = read_marisa_keys(&src_dir.join("unigram.model"))?;
+    let mut keyset = LoudsKeyset::default();
+    for (key, _) in &unigram_keys {
+        keyset.push_back(key);
+    }
+    let mut unigram = LoudsTrie::default();
+    unigram.build(&keyset);
+    unigram.save(dst_dir.join("unigram.model").to_str().unwrap())?;
+
+    let mut id_map: HashMap<usize, usize> = HashMap::new();
+    for (key, old_id) in &unigram_keys {
+        let Some(new_id) = unigram.lookup(key) else {
+            bail!("Missing key in converted unigram model: {:?}", key);
+        };
+        id_map.insert(*old_id, new_id);
+    }
+    info!("Converted unigram model: {} keys", unigram.num_keys());
+
+    // bigram
+    let bigram_keys = read_marisa_keys(&src_dir.join("bigram.model"))?;
+    let mut keyset = LoudsKeyset::default();
+    for (key, _) in &bigram_keys {
+        // 3 byte の word_id1 + 3 byte の word_id2 + f16 のスコア。
+        // それ以外はデフォルトコストなどのメタデータ。
+        if key.len() == 8 {
+            let mut new_key: Vec<u8> = Vec::new();
+            for id_bytes in [&key[0..3], &key[3..6]] {
+                let old_id = u32::from_le_bytes([id_bytes[0], id_bytes[1], id_bytes[2], 0]);
+                let Some(new_id) = id_map.get(&(old_id as usize)) else {
+                    bail!("Unknown word id in bigram model: {}", old_id);
+                };
+                new_key.extend(&(*new_id as u32).to_le_bytes()[0..3]);
+            }
+            new_key.extend(&key[6..8]);
+            keyset.push_back(&new_key);
+        } else {
+            keyset.push_back(key);
+        }
+    }
+    let mut bigram = LoudsTrie::default();
+    bigram.build(&keyset);
+    bigram.save(dst_dir.join("bigram.model").to_str().unwrap())?;
+    info!("Converted bigram model: {} keys", bigram.num_keys());
+
+    // 辞書などのその他のファイルはそのままコピーする。
+    for entry in fs::read_dir(src_dir)? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+        if path.is_file() && file_name != "unigram.model" && file_name != "bigram.model" {
+            info!("Copying {}", path.to_string_lossy());
+            fs::copy(&path, dst_dir.join(file_name))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_marisa_keys(path: &Path) -> Result<Vec<(Vec<u8>, usize)>> {
+    info!("Reading {}", path.to_string_lossy());
+    let mut marisa = Marisa::default();
+    marisa.load(path.to_str().unwrap())?;
+    let mut keys: Vec<(Vec<u8>, usize)> = Vec::new();
+    marisa.predictive_search("".as_bytes(), |key, id| {
+        keys.push((key.to_vec(), id));
+        true
+    });
+    Ok(keys)
+}
@@ -5,7 +5,10 @@ use std::time::SystemTime;
 use anyhow::Context;
 use log::info;
 
-use libakaza::config::{DictConfig, DictEncoding, DictType, DictUsage, EngineConfig};
+use libakaza::config::{
+    DateTimeConfig, DictConfig, DictEncoding, DictType, DictUsage, EngineConfig,
+    DEFAULT_LEARNING_HALF_LIFE_DAYS, DEFAULT_PROVIDERS,
+};
 use libakaza::engine::base::HenkanEngine;
 use libakaza::engine::bigram_word_viterbi_engine::BigramWordViterbiEngineBuilder;
 
@@ -74,6 +77,10 @@ pub fn evaluate(
         dicts,
         model: model_dir,
         dict_cache: false,
+        mmap: true,
+        learning_half_life_days: DEFAULT_LEARNING_HALF_LIFE_DAYS,
+        providers: DEFAULT_PROVIDERS.to_vec(),
+        date_time: DateTimeConfig::default(),
     })
     .build()?;
 
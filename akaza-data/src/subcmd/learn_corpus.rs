@@ -1,7 +1,5 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::path::Path;
-use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 use encoding_rs::UTF_8;
@@ -27,8 +25,8 @@ struct LearningService {
     graph_builder:
         GraphBuilder<OnMemorySystemUnigramLM, OnMemorySystemBigramLM, HashmapVecKanaKanjiDict>,
     segmenter: Segmenter,
-    system_unigram_lm: Rc<OnMemorySystemUnigramLM>,
-    system_bigram_lm: Rc<OnMemorySystemBigramLM>,
+    system_unigram_lm: Arc<OnMemorySystemUnigramLM>,
+    system_bigram_lm: Arc<OnMemorySystemBigramLM>,
 }
 
 impl LearningService {
@@ -65,8 +63,8 @@ impl LearningService {
                 }
             }
         }
-        let system_unigram_lm = Rc::new(OnMemorySystemUnigramLM::new(
-            Rc::new(RefCell::new(unigram_map)),
+        let system_unigram_lm = Arc::new(OnMemorySystemUnigramLM::new(
+            Arc::new(Mutex::new(unigram_map)),
             src_system_unigram_lm.get_default_cost(),
             src_system_unigram_lm.get_default_cost_for_short(),
             src_system_unigram_lm.total_words,
@@ -75,8 +73,8 @@ impl LearningService {
 
         info!("bigram source file: {}", src_bigram);
         let src_system_bigram_lm = WordcntBigram::load(src_bigram)?;
-        let system_bigram_lm = Rc::new(OnMemorySystemBigramLM::new(
-            Rc::new(RefCell::new(src_system_bigram_lm.to_cnt_map())),
+        let system_bigram_lm = Arc::new(OnMemorySystemBigramLM::new(
+            Arc::new(Mutex::new(src_system_bigram_lm.to_cnt_map())),
             src_system_bigram_lm.get_default_edge_cost(),
             src_system_bigram_lm.total_words,
             src_system_bigram_lm.unique_words,
@@ -1,4 +1,5 @@
 pub mod check;
+pub mod convert_model;
 pub mod dump_bigram_dict;
 pub mod dump_unigram_dict;
 pub mod evaluate;
@@ -7,5 +8,6 @@ pub mod make_dict;
 pub mod make_stats_system_bigram_lm;
 pub mod make_stats_system_unigram_lm;
 pub mod tokenize;
+pub mod user_data;
 pub mod vocab;
 pub mod wfreq;
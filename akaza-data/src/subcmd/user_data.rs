@@ -0,0 +1,56 @@
+use anyhow::{bail, Result};
+use log::info;
+
+use libakaza::config::Config;
+use libakaza::user_side_data::user_data::UserData;
+use libakaza::user_side_data::user_data_bundle::UserDataBundle;
+
+/// 手元のユーザーデータをバンドルファイルに書き出す。
+pub fn export_user_data(output: &str) -> Result<()> {
+    let user_data = UserData::load_from_default_path()?;
+    let bundle = user_data.export_bundle();
+    info!(
+        "Exporting user data to {}: unigram={}, bigram={}, dict={}",
+        output,
+        bundle.unigram.len(),
+        bundle.bigram.len(),
+        bundle.dict.len()
+    );
+    bundle.save(output)
+}
+
+/// バンドルファイルの内容で手元のユーザーデータを置き換える。
+/// merge が true なら、手元のユーザーデータとマージする。
+pub fn import_user_data(input: &str, merge: bool) -> Result<()> {
+    let mut user_data = UserData::load_from_default_path()?;
+    let mut bundle = UserDataBundle::load(input)?;
+    if merge {
+        let mut local = user_data.export_bundle();
+        local.merge(&bundle, Config::load()?.engine.learning_half_life_days);
+        bundle = local;
+    }
+    info!(
+        "Importing user data from {}: unigram={}, bigram={}, dict={}",
+        input,
+        bundle.unigram.len(),
+        bundle.bigram.len(),
+        bundle.dict.len()
+    );
+    user_data.import_bundle(&bundle);
+    user_data.write_user_files()
+}
+
+/// 複数のバンドルファイルをマージして、一つのバンドルファイルに書き出す。
+pub fn merge_user_data(inputs: &[String], output: &str) -> Result<()> {
+    if inputs.is_empty() {
+        bail!("No input files are specified.");
+    }
+
+    let half_life_days = Config::load()?.engine.learning_half_life_days;
+    let mut bundle = UserDataBundle::default();
+    for input in inputs {
+        info!("Merging {}", input);
+        bundle.merge(&UserDataBundle::load(input)?, half_life_days);
+    }
+    bundle.save(output)
+}
@@ -0,0 +1,558 @@
+//! libakaza の Python binding です。
+//!
+//! 言語モデルの実験などを、akaza-data コマンドを経由せずに Python からスクリプトで
+//! 行えるようにするためのものです。
+//!
+//! ```python
+//! import akaza
+//!
+//! engine = akaza.Engine("/usr/share/akaza/model/default/")
+//! lattice = engine.to_lattice("わたしのなまえはなかのです")
+//! for node in lattice.nodes():
+//!     print(node, lattice.node_cost(node))
+//! print(engine.resolve(lattice))
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use libakaza::config::{
+    Config, DateTimeConfig, DictConfig, DictEncoding, DictType, DictUsage, EngineConfig,
+    DEFAULT_LEARNING_HALF_LIFE_DAYS, DEFAULT_PROVIDERS,
+};
+use libakaza::corpus::{read_corpus_file, FullAnnotationCorpus};
+use libakaza::engine::base::HenkanEngine;
+use libakaza::engine::bigram_word_viterbi_engine::{
+    BigramWordViterbiEngine, BigramWordViterbiEngineBuilder,
+};
+use libakaza::graph::candidate::Candidate;
+use libakaza::graph::lattice_graph::LatticeGraph;
+use libakaza::graph::word_node::WordNode;
+use libakaza::kana_kanji::marisa_kana_kanji_dict::MarisaKanaKanjiDict;
+use libakaza::lm::base::{SystemBigramLM, SystemUnigramLM};
+use libakaza::lm::system_bigram::MarisaSystemBigramLM;
+use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
+use libakaza::user_side_data::user_data::UserData;
+
+/// かな漢字変換エンジン
+#[pyclass(unsendable, name = "Engine")]
+struct PyEngine {
+    engine:
+        BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM, MarisaKanaKanjiDict>,
+}
+
+#[pymethods]
+impl PyEngine {
+    /// model ディレクトリと辞書を指定してエンジンを作成する。
+    /// user_data が True の場合、ユーザーの学習データを利用する。
+    #[new]
+    #[pyo3(signature = (model, eucjp_dicts = vec![], utf8_dicts = vec![], user_data = false))]
+    fn new(
+        model: &str,
+        eucjp_dicts: Vec<String>,
+        utf8_dicts: Vec<String>,
+        user_data: bool,
+    ) -> PyResult<Self> {
+        let mut dicts: Vec<DictConfig> = Vec::new();
+        for (paths, encoding) in [
+            (eucjp_dicts, DictEncoding::EucJp),
+            (utf8_dicts, DictEncoding::Utf8),
+        ] {
+            for path in paths {
+                dicts.push(DictConfig {
+                    dict_type: DictType::SKK,
+                    encoding: encoding.clone(),
+                    path,
+                    usage: DictUsage::Normal,
+                })
+            }
+        }
+
+        let config = EngineConfig {
+            dicts,
+            model: model.to_string(),
+            dict_cache: false,
+            mmap: true,
+            learning_half_life_days: DEFAULT_LEARNING_HALF_LIFE_DAYS,
+            providers: DEFAULT_PROVIDERS.to_vec(),
+            date_time: DateTimeConfig::default(),
+        };
+        Self::build(config, user_data)
+    }
+
+    /// 設定ファイルを元にエンジンを作成する。
+    #[staticmethod]
+    #[pyo3(signature = (path, user_data = false))]
+    fn from_config(path: &str, user_data: bool) -> PyResult<Self> {
+        let config = Config::load_from_file(path)?;
+        Self::build(config.engine, user_data)
+    }
+
+    /// かな漢字変換を行い、文節ごとの候補リストを返す。
+    /// force_ranges には、強制的に区切る文節の範囲を (start, end) のバイト数で指定する。
+    #[pyo3(signature = (yomi, force_ranges = None))]
+    fn convert(
+        &self,
+        yomi: &str,
+        force_ranges: Option<Vec<(usize, usize)>>,
+    ) -> PyResult<Vec<Vec<PyCandidate>>> {
+        let force_ranges =
+            force_ranges.map(|it| it.iter().map(|(s, e)| *s..*e).collect::<Vec<_>>());
+        let clauses = self.engine.convert(yomi, force_ranges.as_deref())?;
+        Ok(to_py_clauses(clauses))
+    }
+
+    /// ラティスグラフを構築する。
+    #[pyo3(signature = (yomi, force_ranges = None))]
+    fn to_lattice(
+        &self,
+        yomi: &str,
+        force_ranges: Option<Vec<(usize, usize)>>,
+    ) -> PyResult<PyLatticeGraph> {
+        let force_ranges =
+            force_ranges.map(|it| it.iter().map(|(s, e)| *s..*e).collect::<Vec<_>>());
+        let lattice = self.engine.to_lattice(yomi, force_ranges.as_deref())?;
+        Ok(PyLatticeGraph { lattice })
+    }
+
+    /// ラティスグラフを解いて、文節ごとの候補リストを返す。
+    fn resolve(&self, lattice: &PyLatticeGraph) -> PyResult<Vec<Vec<PyCandidate>>> {
+        let clauses = self.engine.resolve(&lattice.lattice)?;
+        Ok(to_py_clauses(clauses))
+    }
+
+    /// 候補を学習させる。学習結果はメモリ上にのみ保持される。
+    fn learn(&mut self, candidates: Vec<PyCandidate>) {
+        let candidates = candidates
+            .into_iter()
+            .map(|it| it.candidate)
+            .collect::<Vec<_>>();
+        self.engine.learn(&candidates);
+    }
+}
+
+impl PyEngine {
+    fn build(config: EngineConfig, user_data: bool) -> PyResult<Self> {
+        let mut builder = BigramWordViterbiEngineBuilder::new(config);
+        if user_data {
+            let user_data = UserData::load_from_default_path()?;
+            builder.user_data(Arc::new(Mutex::new(user_data)));
+        }
+        Ok(PyEngine {
+            engine: builder.build()?,
+        })
+    }
+}
+
+fn to_py_clauses(clauses: Vec<Vec<Candidate>>) -> Vec<Vec<PyCandidate>> {
+    clauses
+        .into_iter()
+        .map(|clause| {
+            clause
+                .into_iter()
+                .map(|candidate| PyCandidate { candidate })
+                .collect()
+        })
+        .collect()
+}
+
+/// 変換候補
+#[pyclass(name = "Candidate")]
+#[derive(Clone)]
+struct PyCandidate {
+    candidate: Candidate,
+}
+
+#[pymethods]
+impl PyCandidate {
+    #[new]
+    fn new(yomi: &str, surface: &str, cost: f32) -> Self {
+        PyCandidate {
+            candidate: Candidate::new(yomi, surface, cost),
+        }
+    }
+
+    #[getter]
+    fn surface(&self) -> String {
+        self.candidate.surface.clone()
+    }
+
+    #[getter]
+    fn yomi(&self) -> String {
+        self.candidate.yomi.clone()
+    }
+
+    #[getter]
+    fn cost(&self) -> f32 {
+        self.candidate.cost
+    }
+
+    #[getter]
+    fn compound_word(&self) -> bool {
+        self.candidate.compound_word
+    }
+
+    /// 動的なエントリーも考慮した上での surface を得る。
+    fn surface_with_dynamic(&self) -> String {
+        self.candidate.surface_with_dynamic()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Candidate(surface={:?}, yomi={:?}, cost={})",
+            self.candidate.surface, self.candidate.yomi, self.candidate.cost
+        )
+    }
+}
+
+/// ラティスグラフのノード
+#[pyclass(name = "WordNode")]
+#[derive(Clone)]
+struct PyWordNode {
+    node: WordNode,
+}
+
+#[pymethods]
+impl PyWordNode {
+    #[getter]
+    fn start_pos(&self) -> i32 {
+        self.node.start_pos
+    }
+
+    /// ノードの終了位置(バイト数)
+    #[getter]
+    fn end_pos(&self) -> i32 {
+        self.node.start_pos + self.node.yomi.len() as i32
+    }
+
+    #[getter]
+    fn surface(&self) -> String {
+        self.node.surface.clone()
+    }
+
+    #[getter]
+    fn yomi(&self) -> String {
+        self.node.yomi.clone()
+    }
+
+    /// システム言語モデル上の単語 ID。言語モデルに含まれない場合は None。
+    #[getter]
+    fn word_id(&self) -> Option<i32> {
+        self.node.word_id_and_score.map(|(id, _)| id)
+    }
+
+    /// システム言語モデル上のコスト。言語モデルに含まれない場合は None。
+    #[getter]
+    fn system_cost(&self) -> Option<f32> {
+        self.node.word_id_and_score.map(|(_, score)| score)
+    }
+
+    #[getter]
+    fn auto_generated(&self) -> bool {
+        self.node.auto_generated
+    }
+
+    fn key(&self) -> String {
+        self.node.key()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "WordNode(start_pos={}, surface={:?}, yomi={:?})",
+            self.node.start_pos, self.node.surface, self.node.yomi
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.node.to_string()
+    }
+}
+
+/// 考えられる単語の列全てを含むようなグラフ構造
+#[pyclass(unsendable, name = "LatticeGraph")]
+struct PyLatticeGraph {
+    lattice: LatticeGraph<MarisaSystemUnigramLM, MarisaSystemBigramLM>,
+}
+
+#[pymethods]
+impl PyLatticeGraph {
+    #[getter]
+    fn yomi(&self) -> String {
+        self.lattice.yomi().to_string()
+    }
+
+    /// BOS/EOS を含む全てのノードを、終了位置の順に返す。
+    fn nodes(&self) -> Vec<PyWordNode> {
+        self.lattice
+            .iter()
+            .flat_map(|(_, nodes)| nodes.iter())
+            .map(|node| PyWordNode { node: node.clone() })
+            .collect()
+    }
+
+    /// end_pos(バイト数)で終わるノードを返す。
+    fn node_list(&self, end_pos: i32) -> Vec<PyWordNode> {
+        self.lattice
+            .node_list(end_pos)
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .map(|node| PyWordNode { node: node.clone() })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// node の直前に接続しうるノードを返す。
+    fn prev_nodes(&self, node: &PyWordNode) -> Vec<PyWordNode> {
+        self.lattice
+            .get_prev_nodes(&node.node)
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .map(|node| PyWordNode { node: node.clone() })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// ノードのコスト。ユーザーの学習データも考慮される。
+    fn node_cost(&self, node: &PyWordNode) -> f32 {
+        self.lattice.get_node_cost(&node.node)
+    }
+
+    /// エッジのコスト。ユーザーの学習データも考慮される。
+    fn edge_cost(&self, prev: &PyWordNode, node: &PyWordNode) -> f32 {
+        self.lattice.get_edge_cost(&prev.node, &node.node)
+    }
+
+    fn default_edge_cost(&self) -> f32 {
+        self.lattice.get_default_edge_cost()
+    }
+
+    /// graphviz の dot 形式で出力する。
+    fn dump_position_dot(&self) -> String {
+        self.lattice.dump_position_dot()
+    }
+
+    /// コストつきで graphviz の dot 形式で出力する。
+    fn dump_cost_dot(&self, expected: &str) -> String {
+        self.lattice.dump_cost_dot(expected)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("LatticeGraph(yomi={:?})", self.lattice.yomi())
+    }
+}
+
+/// フルアノテーションコーパス
+#[pyclass(name = "FullAnnotationCorpus")]
+struct PyFullAnnotationCorpus {
+    corpus: FullAnnotationCorpus,
+}
+
+#[pymethods]
+impl PyFullAnnotationCorpus {
+    /// "コーパス/こーぱす の/の 文/ぶん" のような形式の文字列をパースする。
+    #[new]
+    fn new(src: &str) -> PyResult<Self> {
+        Ok(PyFullAnnotationCorpus {
+            corpus: FullAnnotationCorpus::new(src)?,
+        })
+    }
+
+    #[getter]
+    fn nodes(&self) -> Vec<PyWordNode> {
+        self.corpus
+            .nodes
+            .iter()
+            .map(|node| PyWordNode { node: node.clone() })
+            .collect()
+    }
+
+    /// コーパスの「よみ」を連結したものを返す。
+    fn yomi(&self) -> String {
+        self.corpus.yomi()
+    }
+
+    /// コーパスの「表層」を連結したものを返す。
+    fn surface(&self) -> String {
+        self.corpus.surface()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("FullAnnotationCorpus(surface={:?})", self.corpus.surface())
+    }
+}
+
+/// コーパスファイルを読み込む。
+#[pyfunction(name = "read_corpus_file")]
+fn py_read_corpus_file(path: &str) -> PyResult<Vec<PyFullAnnotationCorpus>> {
+    Ok(read_corpus_file(path.as_ref())?
+        .into_iter()
+        .map(|corpus| PyFullAnnotationCorpus { corpus })
+        .collect())
+}
+
+/// システムのユニグラム言語モデル
+#[pyclass(unsendable, name = "SystemUnigramLM")]
+struct PySystemUnigramLM {
+    lm: MarisaSystemUnigramLM,
+}
+
+#[pymethods]
+impl PySystemUnigramLM {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        Ok(PySystemUnigramLM {
+            lm: MarisaSystemUnigramLM::load(path)?,
+        })
+    }
+
+    /// "漢字/かんじ" 形式のキーで検索し、(単語ID, コスト) を返す。
+    fn find(&self, word: &str) -> Option<(i32, f32)> {
+        self.lm.find(word)
+    }
+
+    fn default_cost(&self) -> f32 {
+        self.lm.get_default_cost()
+    }
+
+    fn default_cost_for_short(&self) -> f32 {
+        self.lm.get_default_cost_for_short()
+    }
+
+    fn num_keys(&self) -> usize {
+        self.lm.num_keys()
+    }
+
+    /// キーから (単語ID, コスト) への dict を返す。
+    fn to_dict(&self) -> std::collections::HashMap<String, (i32, f32)> {
+        self.lm.as_hash_map()
+    }
+}
+
+/// システムのバイグラム言語モデル
+#[pyclass(unsendable, name = "SystemBigramLM")]
+struct PySystemBigramLM {
+    lm: MarisaSystemBigramLM,
+}
+
+#[pymethods]
+impl PySystemBigramLM {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        Ok(PySystemBigramLM {
+            lm: MarisaSystemBigramLM::load(path)?,
+        })
+    }
+
+    /// ユニグラム言語モデルの単語 ID の組でエッジコストを検索する。
+    fn get_edge_cost(&self, word_id1: i32, word_id2: i32) -> PyResult<Option<f32>> {
+        // 単語 ID は 3 byte で格納されている。
+        if !(0..1 << 24).contains(&word_id1) || !(0..1 << 24).contains(&word_id2) {
+            return Err(PyValueError::new_err(format!(
+                "Invalid word id: {}, {}",
+                word_id1, word_id2
+            )));
+        }
+        Ok(self.lm.get_edge_cost(word_id1, word_id2))
+    }
+
+    fn default_edge_cost(&self) -> f32 {
+        self.lm.get_default_edge_cost()
+    }
+
+    fn num_keys(&self) -> usize {
+        self.lm.num_keys()
+    }
+
+    /// (単語ID1, 単語ID2) からコストへの dict を返す。
+    fn to_dict(&self) -> std::collections::HashMap<(i32, i32), f32> {
+        self.lm.as_hash_map()
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "akaza")]
+fn akaza_py(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyEngine>()?;
+    m.add_class::<PyCandidate>()?;
+    m.add_class::<PyWordNode>()?;
+    m.add_class::<PyLatticeGraph>()?;
+    m.add_class::<PyFullAnnotationCorpus>()?;
+    m.add_class::<PySystemUnigramLM>()?;
+    m.add_class::<PySystemBigramLM>()?;
+    m.add_function(wrap_pyfunction!(py_read_corpus_file, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use libakaza::lm::system_bigram::MarisaSystemBigramLMBuilder;
+    use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
+
+    use super::*;
+
+    /// 「わたし」「の」だけを含む小さなモデルを作る。
+    fn build_model() -> anyhow::Result<TempDir> {
+        let dir = TempDir::new()?;
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+
+        let mut unigram = MarisaSystemUnigramLMBuilder::default();
+        unigram.add("私/わたし", 1.0);
+        unigram.add("渡し/わたし", 5.0);
+        unigram.add("の/の", 1.0);
+        unigram
+            .set_default_cost(20.0)
+            .set_default_cost_for_short(19.0)
+            .save(&path("unigram.model"))?;
+
+        let mut bigram = MarisaSystemBigramLMBuilder::default();
+        bigram
+            .set_default_edge_cost(20.0)
+            .save(&path("bigram.model"))?;
+
+        fs::write(path("SKK-JISYO.akaza"), "わたし /私/渡し/\nの /の/\n")?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn test_convert() -> anyhow::Result<()> {
+        let model = build_model()?;
+        let engine = PyEngine::new(&model.path().to_string_lossy(), vec![], vec![], false)?;
+
+        let clauses = engine.convert("わたしの", None)?;
+        let surfaces = clauses
+            .iter()
+            .map(|clause| clause[0].surface())
+            .collect::<Vec<_>>();
+        assert_eq!(surfaces, vec!["私", "の"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_lattice_and_resolve() -> anyhow::Result<()> {
+        let model = build_model()?;
+        let engine = PyEngine::new(&model.path().to_string_lossy(), vec![], vec![], false)?;
+
+        let lattice = engine.to_lattice("わたしの", Some(vec![(0, 9)]))?;
+        assert_eq!(lattice.yomi(), "わたしの");
+        let nodes = lattice.node_list(9);
+        let find = |surface: &str| nodes.iter().find(|node| node.surface() == surface).unwrap();
+        assert_eq!(find("私").start_pos(), 0);
+        assert_eq!(find("私").end_pos(), 9);
+        assert!(lattice.node_cost(find("私")) < lattice.node_cost(find("渡し")));
+
+        let clauses = engine.resolve(&lattice)?;
+        assert_eq!(clauses.len(), 2);
+        assert_eq!(clauses[0][0].surface(), "私");
+        Ok(())
+    }
+}
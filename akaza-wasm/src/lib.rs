@@ -0,0 +1,75 @@
+//! ブラウザ上で akaza の変換を試すための WebAssembly バインディング。
+//!
+//! wasm32 ではファイルシステムや XDG ディレクトリが使えないので、モデルと辞書は
+//! JavaScript 側で fetch したバイト列/文字列として渡す。
+
+use libakaza::config::EngineConfig;
+use libakaza::engine::base::HenkanEngine;
+use libakaza::engine::bigram_word_viterbi_engine::{
+    BigramWordViterbiEngine, BigramWordViterbiEngineBuilder,
+};
+use libakaza::kana_kanji::marisa_kana_kanji_dict::MarisaKanaKanjiDict;
+use libakaza::lm::system_bigram::MarisaSystemBigramLM;
+use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+#[derive(Serialize)]
+struct Candidate {
+    surface: String,
+    yomi: String,
+    cost: f32,
+}
+
+#[wasm_bindgen]
+pub struct Akaza {
+    engine:
+        BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM, MarisaKanaKanjiDict>,
+}
+
+#[wasm_bindgen]
+impl Akaza {
+    /// unigram/bigram は `akaza-data convert-model` で変換したモデルファイルの中身。
+    /// system_dict は UTF-8 の SKK 辞書の中身。
+    #[wasm_bindgen(constructor)]
+    pub fn new(unigram: Vec<u8>, bigram: Vec<u8>, system_dict: &str) -> Result<Akaza, JsError> {
+        let engine = BigramWordViterbiEngineBuilder::new(EngineConfig::default())
+            .build_from_bytes(unigram, bigram, system_dict)
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(Akaza { engine })
+    }
+
+    /// 変換結果を、文節ごとの候補の配列(`{surface, yomi, cost}[][]`)として返す。
+    pub fn convert(&self, yomi: &str) -> Result<JsValue, JsError> {
+        let clauses: Vec<Vec<Candidate>> = self
+            .engine
+            .convert(yomi, None)
+            .map_err(|err| JsError::new(&err.to_string()))?
+            .iter()
+            .map(|clause| {
+                clause
+                    .iter()
+                    .map(|c| Candidate {
+                        surface: c.surface.clone(),
+                        yomi: c.yomi.clone(),
+                        cost: c.cost,
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(serde_wasm_bindgen::to_value(&clauses)?)
+    }
+
+    /// 各文節の第一候補をつなげた文字列を返す。
+    pub fn convert_to_string(&self, yomi: &str) -> Result<String, JsError> {
+        let clauses = self
+            .engine
+            .convert(yomi, None)
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(clauses
+            .iter()
+            .map(|clause| clause[0].surface.clone())
+            .collect::<Vec<_>>()
+            .join(""))
+    }
+}
@@ -24,8 +24,7 @@ pub(crate) fn ibus_akaza_commands_map() -> HashMap<&'static str, IbusAkazaComman
     });
     // 無変換状態では、ひらがなに変換してコミットします
     register("commit_preedit", |context, engine| {
-        let (_, surface) = context.make_preedit_word();
-        context.commit_string(engine, surface.as_str());
+        context.commit_preedit(engine);
     });
     register("escape", |context, engine| context.escape(engine));
     register("page_up", |context, engine| {
@@ -90,6 +89,21 @@ pub(crate) fn ibus_akaza_commands_map() -> HashMap<&'static str, IbusAkazaComman
     register("convert_to_half_romaji", |context, engine| {
         context.convert_to_half_romaji(engine).unwrap();
     });
+    register("forget_candidate", |context, engine| {
+        context.forget_candidate(engine).unwrap();
+    });
+    register("start_word_registration", |context, engine| {
+        context.start_word_registration(engine);
+    });
+    register("start_code_input", |context, engine| {
+        context.start_code_input(engine);
+    });
+    register("start_abbrev", |context, engine| {
+        context.start_abbrev(engine);
+    });
+    register("toggle_alnum_run", |context, engine| {
+        context.toggle_alnum_run(engine);
+    });
 
     {
         // TODO コピペがすごい。マクロかうまいなにかでまとめて登録できるようにしたい。
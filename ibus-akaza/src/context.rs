@@ -1,5 +1,6 @@
 use alloc::ffi::CString;
 use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
 
 use anyhow::Result;
 use kelp::{h2z, hira2kata, z2h, ConvOption};
@@ -25,29 +26,34 @@ use ibus_sys::engine::ibus_engine_update_lookup_table;
 use ibus_sys::engine::ibus_engine_update_preedit_text;
 use ibus_sys::engine::IBusEngine;
 use ibus_sys::engine::{ibus_engine_hide_auxiliary_text, ibus_engine_hide_lookup_table};
+use ibus_sys::engine::{
+    IBusInputHints_IBUS_INPUT_HINT_PRIVATE, IBusInputPurpose_IBUS_INPUT_PURPOSE_PASSWORD,
+    IBusInputPurpose_IBUS_INPUT_PURPOSE_PIN,
+};
 use ibus_sys::glib::gchar;
 use ibus_sys::glib::{gboolean, guint};
+use ibus_sys::ibus_key::{
+    IBUS_KEY_BackSpace, IBUS_KEY_Escape, IBUS_KEY_Return, IBUS_KEY_Shift_L, IBUS_KEY_Shift_R,
+};
 use ibus_sys::lookup_table::IBusLookupTable;
 use ibus_sys::property::IBusPropState_PROP_STATE_CHECKED;
 use ibus_sys::text::{ibus_text_new_from_string, ibus_text_set_attributes, StringExt};
-use libakaza::config::Config;
+use libakaza::code_input::lookup_code;
+use libakaza::config::{Config, ConfigIssue};
 use libakaza::consonant::ConsonantSuffixExtractor;
 use libakaza::engine::base::HenkanEngine;
-use libakaza::engine::bigram_word_viterbi_engine::BigramWordViterbiEngine;
 use libakaza::graph::candidate::Candidate;
-use libakaza::kana_kanji::marisa_kana_kanji_dict::MarisaKanaKanjiDict;
 use libakaza::keymap::KeyState;
-use libakaza::lm::system_bigram::MarisaSystemBigramLM;
-use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
 use libakaza::romkan::RomKanConverter;
 
 use crate::commands::{ibus_akaza_commands_map, IbusAkazaCommand};
-use crate::current_state::CurrentState;
+use crate::current_state::{CurrentState, RawInput, WordRegistration};
 use crate::input_mode::{
     get_input_mode_from_prop_name, InputMode, INPUT_MODE_HALFWIDTH_KATAKANA, INPUT_MODE_HIRAGANA,
     INPUT_MODE_KATAKANA,
 };
 use crate::keymap::KeyMap;
+use crate::reloader::{AkazaEngine, ReloadedComponents};
 use crate::ui::prop_controller::PropController;
 
 #[repr(C)]
@@ -56,12 +62,23 @@ pub struct AkazaContext {
     keymap: KeyMap,
     romkan: RomKanConverter,
     command_map: HashMap<&'static str, IbusAkazaCommand>,
-    engine:
-        BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM, MarisaKanaKanjiDict>,
+    engine: AkazaEngine,
     consonant_suffix_extractor: ConsonantSuffixExtractor,
+    /// 設定ファイルの変更を検知して、作り直されたコンポーネントを受け取る。
+    reload_receiver: Receiver<ReloadedComponents>,
+    /// まだユーザーに知らせていない、設定ファイルの問題点。
+    config_issues: Vec<ConfigIssue>,
+    /// 設定ファイルの問題点を auxiliary text に表示している。次のキー入力で消す。
+    config_issues_shown: bool,
 
     // ==== 現在の入力状態を保持 ====
     current_state: CurrentState,
+    /// ユーザーがメニューから「学習しない」を選んでいる。
+    incognito: bool,
+    /// パスワード入力欄など、入力内容を学習すべきでない入力欄にフォーカスがある。
+    private_input: bool,
+    /// Shift キーだけが押されている。離したときに、英数字の入力を切り替える。
+    shift_alone: bool,
 
     // ==== UI 関連 ====
     lookup_table: IBusLookupTable,
@@ -70,18 +87,19 @@ pub struct AkazaContext {
 
 impl AkazaContext {
     pub(crate) fn new(
-        akaza: BigramWordViterbiEngine<
-            MarisaSystemUnigramLM,
-            MarisaSystemBigramLM,
-            MarisaKanaKanjiDict,
-        >,
+        akaza: AkazaEngine,
         config: Config,
+        config_issues: Vec<ConfigIssue>,
+        reload_receiver: Receiver<ReloadedComponents>,
     ) -> Result<Self> {
         let input_mode = INPUT_MODE_HIRAGANA;
         let romkan = RomKanConverter::new(config.romkan.as_str())?;
 
         Ok(AkazaContext {
             current_state: CurrentState::new(input_mode),
+            incognito: false,
+            private_input: false,
+            shift_alone: false,
             //         self.lookup_table = IBus.LookupTable.new(page_size=10, cursor_pos=0, cursor_visible=True, round=True)
             lookup_table: IBusLookupTable::new(10, 0, 1, 1),
             romkan,
@@ -90,9 +108,57 @@ impl AkazaContext {
             keymap: KeyMap::new(config.keymap)?,
             prop_controller: PropController::new(input_mode)?,
             consonant_suffix_extractor: ConsonantSuffixExtractor::default(),
+            reload_receiver,
+            config_issues,
+            config_issues_shown: false,
         })
     }
 
+    /// バックグラウンドで作り直されたコンポーネントがあれば差し替える。
+    /// 入力途中に差し替えると状態がおかしくなるので、preedit が空のときだけ呼ぶこと。
+    fn apply_reloaded_components(&mut self) {
+        while let Ok(reloaded) = self.reload_receiver.try_recv() {
+            if let Some(keymap) = reloaded.keymap {
+                info!("Swapping keymap");
+                self.keymap = keymap;
+            }
+            if let Some(romkan) = reloaded.romkan {
+                info!("Swapping romkan: {}", romkan.mapping_name);
+                self.romkan = romkan;
+            }
+            if let Some(engine) = reloaded.engine {
+                info!("Swapping engine");
+                self.engine = engine;
+            }
+            if !reloaded.config_issues.is_empty() {
+                self.config_issues = reloaded.config_issues;
+            }
+        }
+    }
+
+    /// 設定ファイルに問題があれば、auxiliary text で知らせる。
+    /// 一度知らせたら、設定ファイルが読み直されるまでは表示しない。
+    fn show_config_issues(&mut self, engine: *mut IBusEngine) {
+        if self.config_issues.is_empty() {
+            return;
+        }
+        let text = format!(
+            "Akaza: {}",
+            self.config_issues
+                .iter()
+                .map(|it| it.to_string())
+                .collect::<Vec<_>>()
+                .join(" / ")
+        );
+        self.config_issues.clear();
+        self.config_issues_shown = true;
+        unsafe {
+            let auxiliary_text = text.to_ibus_text();
+            ibus_text_set_attributes(auxiliary_text, ibus_attr_list_new());
+            ibus_engine_update_auxiliary_text(engine, auxiliary_text, to_gboolean(true));
+        }
+    }
+
     /// Set props
     pub(crate) fn do_property_activate(
         &mut self,
@@ -106,6 +172,8 @@ impl AkazaContext {
                 Ok(_) => {}
                 Err(e) => info!("Err: {}", e),
             }
+        } else if prop_name == "Incognito" {
+            self.set_incognito(engine, prop_state == IBusPropState_PROP_STATE_CHECKED);
         } else if prop_state == IBusPropState_PROP_STATE_CHECKED
             && prop_name.starts_with("InputMode.")
         {
@@ -113,6 +181,35 @@ impl AkazaContext {
         }
     }
 
+    /// 学習しないモードを切り替える。
+    pub(crate) fn set_incognito(&mut self, engine: *mut IBusEngine, incognito: bool) {
+        info!("Changing incognito mode to: {}", incognito);
+        self.incognito = incognito;
+        self.prop_controller.set_incognito(incognito, engine);
+    }
+
+    /// 入力欄の用途が通知されたときに呼ばれる。
+    /// パスワードや PIN の入力欄、プライベートな入力欄では学習しない。
+    pub(crate) fn do_set_content_type(
+        &mut self,
+        _engine: *mut IBusEngine,
+        purpose: guint,
+        hints: guint,
+    ) {
+        self.private_input = purpose == IBusInputPurpose_IBUS_INPUT_PURPOSE_PASSWORD
+            || purpose == IBusInputPurpose_IBUS_INPUT_PURPOSE_PIN
+            || hints & IBusInputHints_IBUS_INPUT_HINT_PRIVATE != 0;
+        debug!(
+            "do_set_content_type: purpose={}, hints={}, private_input={}",
+            purpose, hints, self.private_input
+        );
+    }
+
+    /// 確定した内容を学習してよいか。
+    fn learning_enabled(&self) -> bool {
+        !self.incognito && !self.private_input
+    }
+
     pub fn input_mode_activate(
         &mut self,
         engine: *mut IBusEngine,
@@ -183,10 +280,49 @@ impl AkazaContext {
             modifiers
         );
 
+        // Shift キーだけを押して離したら、キーマップで Shift キーに割り当てられたコマンドを実行する。
+        // 標準のキーマップでは、英数字の入力とかなの入力を切り替える。
+        if keyval == IBUS_KEY_Shift_L || keyval == IBUS_KEY_Shift_R {
+            if modifiers & IBusModifierType_IBUS_RELEASE_MASK == 0 {
+                self.shift_alone = true;
+            } else if self.shift_alone {
+                self.shift_alone = false;
+                if let Some(callback) = self.keymap.get(&self.get_key_state(), keyval, 0).cloned() {
+                    return self.run_callback_by_name(engine, callback.as_str());
+                }
+            }
+            return false;
+        }
+
         // ignore key release event
         if modifiers & IBusModifierType_IBUS_RELEASE_MASK != 0 {
             return false;
         }
+        self.shift_alone = false;
+
+        if self.config_issues_shown {
+            // 入力を始めたら、設定ファイルの問題点の表示は消す。
+            self.config_issues_shown = false;
+            unsafe { ibus_engine_hide_auxiliary_text(engine) };
+        }
+
+        if self.current_state.raw_input != RawInput::Normal && !self.current_state.in_conversion() {
+            if let Some(handled) = self.process_key_event_in_raw_input(engine, keyval, modifiers) {
+                return handled;
+            }
+        }
+
+        if self.current_state.preedit.is_empty() {
+            if self.current_state.word_registration.is_some() {
+                if let Some(handled) =
+                    self.process_key_event_in_word_registration(engine, keyval, modifiers)
+                {
+                    return handled;
+                }
+            } else {
+                self.apply_reloaded_components();
+            }
+        }
         let key_state = self.get_key_state();
 
         trace!("KeyState={:?}", key_state);
@@ -208,7 +344,14 @@ impl AkazaContext {
             )
             .cloned()
         {
-            return self.run_callback_by_name(engine, callback.as_str());
+            // abbrev はかなを入力するときのためのものなので、英数モードではキーをそのまま入力させる。
+            // かな入力の "/" → "め" のように、ローマ字かな変換テーブルでキーに文字が割り当てられていれば、
+            // その文字を入力させる。
+            let mapped = char::from_u32(keyval)
+                .is_some_and(|c| c.is_ascii_graphic() && self.romkan.has_mapping(&c.to_string()));
+            if callback != "start_abbrev" || (self.current_state.input_mode.is_kana() && !mapped) {
+                return self.run_callback_by_name(engine, callback.as_str());
+            }
         }
 
         match self.current_state.input_mode.prop_name {
@@ -232,6 +375,11 @@ impl AkazaContext {
 
                     // Append the character to preedit string.
                     let ch = char::from_u32(keyval).unwrap();
+                    // Shift を押しながら英字を入力したら、そこから英数字のまま入力する。
+                    if ch.is_ascii_uppercase() && modifiers & IBusModifierType_IBUS_SHIFT_MASK != 0
+                    {
+                        self.current_state.start_alnum_run();
+                    }
                     self.current_state.append_preedit(ch);
 
                     // And update the display status.
@@ -239,7 +387,20 @@ impl AkazaContext {
                     return true;
                 }
             }
-            "InputMode.Alphanumeric" => return false,
+            "InputMode.Alphanumeric" => {
+                if self.current_state.word_registration.is_some()
+                    && ('!' as u32) <= keyval
+                    && keyval <= ('~' as u32)
+                    && (modifiers
+                        & (IBusModifierType_IBUS_CONTROL_MASK | IBusModifierType_IBUS_MOD1_MASK))
+                        == 0
+                {
+                    // 単語登録中は、英数字もそのまま表記に追加する。
+                    self.commit_text(engine, char::from_u32(keyval).unwrap().to_string().as_str());
+                    return true;
+                }
+                return false;
+            }
             "InputMode.FullWidthAlnum" => {
                 if ('!' as u32) <= keyval
                     && keyval <= ('~' as u32)
@@ -253,7 +414,7 @@ impl AkazaContext {
                         ..Default::default()
                     };
                     let text = h2z(char::from_u32(keyval).unwrap().to_string().as_str(), option);
-                    unsafe { ibus_engine_commit_text(engine, text.to_ibus_text()) };
+                    self.commit_text(engine, text.as_str());
                     return true;
                 }
             }
@@ -275,6 +436,13 @@ impl AkazaContext {
                 self.current_state.clear_clauses();
                 ibus_engine_hide_auxiliary_text(engine);
                 ibus_engine_hide_lookup_table(engine);
+            } else if self.current_state.raw_input != RawInput::Normal
+                || self.current_state.ends_with_alnum()
+            {
+                // 文字コードや英数字はローマ字ではないので、単に一文字削る。
+                let mut preedit = self.current_state.preedit.clone();
+                preedit.pop();
+                self.current_state.set_preedit(preedit);
             } else {
                 // サイゴの一文字をけずるが、子音が先行しているばあいは、子音もついでにとる。
                 self.current_state
@@ -283,9 +451,11 @@ impl AkazaContext {
             // 変換していないときのレンダリングをする。
             self.update_preedit_text_in_precomposition(engine);
         }
+        self.update_raw_input_text(engine);
     }
 
     pub(crate) fn update_preedit_text_in_precomposition(&mut self, engine: *mut IBusEngine) {
+        self.update_alnum_run_text(engine);
         unsafe {
             if self.current_state.preedit.is_empty() {
                 ibus_engine_hide_preedit_text(engine);
@@ -371,13 +541,18 @@ impl AkazaContext {
 
     pub fn commit_string(&mut self, engine: *mut IBusEngine, text: &str) {
         unsafe {
-            if self.current_state.in_conversion() {
+            if self.current_state.in_conversion()
+                && self.learning_enabled()
+                && self.current_state.word_registration.is_none()
+                && self.current_state.raw_input != RawInput::CodeInput
+            {
                 // 変換モードのときのみ学習を実施する
+                // 学習しないモードやパスワード入力欄などでは学習しない。
                 self.engine
                     .learn(self.current_state.get_first_candidates().as_slice());
             }
 
-            ibus_engine_commit_text(engine, text.to_ibus_text());
+            self.commit_text(engine, text);
 
             self.current_state.clear();
 
@@ -387,12 +562,37 @@ impl AkazaContext {
             ibus_engine_hide_auxiliary_text(engine);
             ibus_engine_hide_preedit_text(engine);
         }
+        self.update_word_registration_text(engine);
+    }
+
+    /// 確定した文字列をアプリケーションに送る。
+    /// 単語登録中は、アプリケーションに送らずに登録する表記に追加する。
+    fn commit_text(&mut self, engine: *mut IBusEngine, text: &str) {
+        if let Some(registration) = &mut self.current_state.word_registration {
+            registration.surface += text;
+            self.update_word_registration_text(engine);
+        } else {
+            unsafe { ibus_engine_commit_text(engine, text.to_ibus_text()) };
+        }
     }
 
     pub fn commit_candidate(&mut self, engine: *mut IBusEngine) {
         self.commit_string(engine, self.current_state.build_string().as_str());
     }
 
+    /// 変換せずに確定する。ひらがなにして確定するが、文字コードの入力中はその文字にする。
+    pub fn commit_preedit(&mut self, engine: *mut IBusEngine) {
+        let surface = if self.current_state.raw_input == RawInput::CodeInput {
+            lookup_code(&self.current_state.preedit)
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| self.current_state.preedit.clone())
+        } else {
+            self.make_preedit_word().1
+        };
+        self.commit_string(engine, surface.as_str());
+    }
+
     pub(crate) fn update_candidates(&mut self, engine: *mut IBusEngine) {
         self._update_candidates(engine).unwrap();
         self.current_state.clear_state();
@@ -405,8 +605,21 @@ impl AkazaContext {
             let yomi = self.current_state.preedit.clone();
 
             // 先頭が大文字なケースと、URL っぽい文字列のときは変換処理を実施しない。
-            let clauses = if (!yomi.is_empty()
-                && yomi.chars().next().unwrap().is_ascii_uppercase()
+            let clauses = if self.current_state.raw_input == RawInput::CodeInput {
+                let candidates = lookup_code(&yomi)
+                    .iter()
+                    .map(|surface| Candidate::new(&yomi, surface, 0_f32))
+                    .collect::<Vec<_>>();
+                if candidates.is_empty() {
+                    vec![vec![Candidate::new(&yomi, &yomi, 0_f32)]]
+                } else {
+                    vec![candidates]
+                }
+            } else if self.current_state.raw_input == RawInput::Abbrev {
+                // 英字の読みは、ローマ字かな変換せずに、全体を一つの文節として辞書を引く。
+                self.engine
+                    .convert(&yomi, Some(std::slice::from_ref(&(0..yomi.len()))))?
+            } else if (self.current_state.is_uppercase_word()
                 && self.current_state.force_selected_clause.is_empty())
                 || yomi.starts_with("https://")
                 || yomi.starts_with("http://")
@@ -417,10 +630,16 @@ impl AkazaContext {
                     0_f32,
                 )])]
             } else {
-                self.engine.convert(
-                    self.romkan.to_hiragana(&yomi).as_str(),
-                    Some(&self.current_state.force_selected_clause),
-                )?
+                let (hiragana, alnum_ranges) = self
+                    .current_state
+                    .to_yomi(yomi.len(), |it| self.romkan.to_hiragana(it));
+                // 英数字の部分は、それぞれ一つの文節にする。
+                let force_ranges = if self.current_state.force_selected_clause.is_empty() {
+                    alnum_ranges
+                } else {
+                    self.current_state.force_selected_clause.clone()
+                };
+                self.engine.convert(&hiragana, Some(&force_ranges))?
             };
 
             self.current_state.set_clauses(clauses);
@@ -528,12 +747,27 @@ impl AkazaContext {
     /// (yomi, surface)
     pub fn make_preedit_word(&self) -> (String, String) {
         let preedit = self.current_state.preedit.clone();
+        // 文字コードや英字の読みの入力中は、入力されたものをそのまま表示する。
+        if self.current_state.raw_input != RawInput::Normal {
+            return (preedit.clone(), preedit);
+        }
         // 先頭文字が大文字な場合は、そのまま返す。
         // "IME" などと入力された場合は、それをそのまま返すようにする。
-        if !preedit.is_empty() && preedit.chars().next().unwrap().is_ascii_uppercase() {
+        if self.current_state.is_uppercase_word() {
             return (preedit.clone(), preedit);
         }
 
+        // 英数字として入力した部分までは、ローマ字かな変換せずにそのまま読みにする。
+        let alnum_end = self
+            .current_state
+            .alnum_ranges
+            .last()
+            .map_or(0, |it| it.end);
+        let (head, _) = self
+            .current_state
+            .to_yomi(alnum_end, |it| self.romkan.to_hiragana(it));
+        let preedit = preedit[alnum_end..].to_string();
+
         // hogen と入力された場合、"ほげn" と表示する。
         // hogena となったら "ほげな"
         // hogenn となったら "ほげん" と表示する必要があるため。
@@ -544,7 +778,7 @@ impl AkazaContext {
             (preedit, "".to_string())
         };
 
-        let yomi = self.romkan.to_hiragana(preedit.as_str());
+        let yomi = head + &self.romkan.to_hiragana(preedit.as_str());
         let surface = yomi.clone();
         if self.current_state.input_mode == INPUT_MODE_KATAKANA {
             (
@@ -662,6 +896,10 @@ impl AkazaContext {
     pub fn do_focus_in(&mut self, engine: *mut IBusEngine) {
         trace!("do_focus_in");
         self.prop_controller.do_focus_in(engine);
+        if self.current_state.preedit.is_empty() {
+            self.apply_reloaded_components();
+        }
+        self.show_config_issues(engine);
     }
 
     /// convert selected word/characters to full-width hiragana (standard hiragana): ホワイト → ほわいと
@@ -743,6 +981,228 @@ impl AkazaContext {
         Ok(())
     }
 
+    /// 単語登録を開始する。
+    /// 現在入力中の読みを、登録する単語の読みとして使う。
+    /// 表記は、通常どおりに入力・変換して確定したものが追加されていく。
+    pub fn start_word_registration(&mut self, engine: *mut IBusEngine) {
+        if self.current_state.word_registration.is_some() {
+            return;
+        }
+        let (yomi, _) = self
+            .current_state
+            .to_yomi(self.current_state.preedit.len(), |it| {
+                self.romkan.to_hiragana(it)
+            });
+        if yomi.is_empty() {
+            warn!("Cannot start word registration without yomi");
+            return;
+        }
+        info!("Start word registration: {}", yomi);
+
+        self.current_state.clear();
+        self.lookup_table.clear();
+        self._update_lookup_table(engine);
+        unsafe { ibus_engine_hide_preedit_text(engine) };
+
+        self.current_state.word_registration = Some(WordRegistration {
+            yomi,
+            surface: String::new(),
+        });
+        self.update_word_registration_text(engine);
+    }
+
+    /// 単語登録中に、未入力状態で押されたキーを処理する。
+    /// Return で登録、Escape で中止、BackSpace で表記を一文字削除する。
+    /// それ以外のキーは None を返して、通常の入力処理にまかせる。
+    fn process_key_event_in_word_registration(
+        &mut self,
+        engine: *mut IBusEngine,
+        keyval: guint,
+        modifiers: guint,
+    ) -> Option<bool> {
+        if modifiers & (IBusModifierType_IBUS_CONTROL_MASK | IBusModifierType_IBUS_MOD1_MASK) != 0 {
+            return None;
+        }
+
+        match keyval {
+            IBUS_KEY_Return => {
+                let registration = self.current_state.word_registration.take()?;
+                if registration.surface.is_empty() {
+                    info!("Word registration was cancelled: surface is empty");
+                } else {
+                    info!(
+                        "Register word: {} {}",
+                        registration.yomi, registration.surface
+                    );
+                    self.engine
+                        .user_data
+                        .lock()
+                        .unwrap()
+                        .register_word(&registration.yomi, &registration.surface);
+                }
+                unsafe { ibus_engine_hide_auxiliary_text(engine) };
+                Some(true)
+            }
+            IBUS_KEY_Escape => {
+                info!("Word registration was cancelled");
+                self.current_state.word_registration = None;
+                unsafe { ibus_engine_hide_auxiliary_text(engine) };
+                Some(true)
+            }
+            IBUS_KEY_BackSpace => {
+                if let Some(registration) = &mut self.current_state.word_registration {
+                    registration.surface.pop();
+                }
+                self.update_word_registration_text(engine);
+                Some(true)
+            }
+            _ => None,
+        }
+    }
+
+    /// 単語登録中であれば、登録しようとしている単語を auxiliary text に表示する。
+    fn update_word_registration_text(&mut self, engine: *mut IBusEngine) {
+        let Some(registration) = &self.current_state.word_registration else {
+            return;
+        };
+        let text = format!("単語登録 [{}] {}", registration.yomi, registration.surface);
+        unsafe {
+            let auxiliary_text = text.to_ibus_text();
+            ibus_text_set_attributes(auxiliary_text, ibus_attr_list_new());
+            ibus_engine_update_auxiliary_text(engine, auxiliary_text, to_gboolean(true));
+        }
+    }
+
+    /// 文字コードによる入力を開始する。
+    /// 入力中の内容は確定してから、"u+2603" や "16-01" のような文字コードを受け付ける。
+    pub fn start_code_input(&mut self, engine: *mut IBusEngine) {
+        if self.current_state.raw_input == RawInput::CodeInput {
+            return;
+        }
+        if self.current_state.in_conversion() {
+            self.commit_candidate(engine);
+        } else if !self.current_state.preedit.is_empty() {
+            self.commit_preedit(engine);
+        }
+        info!("Start code input");
+
+        self.current_state.raw_input = RawInput::CodeInput;
+        self.update_raw_input_text(engine);
+    }
+
+    /// 英数字の入力と、かなの入力を切り替える。
+    /// かなを入力するモードで、読みを入力していて、変換していないときだけ切り替える。
+    pub(crate) fn toggle_alnum_run(&mut self, engine: *mut IBusEngine) {
+        if !self.current_state.input_mode.is_kana()
+            || self.current_state.preedit.is_empty()
+            || self.current_state.in_conversion()
+            || self.current_state.raw_input != RawInput::Normal
+        {
+            return;
+        }
+        info!("Toggle alphanumeric input");
+        self.current_state.toggle_alnum_run();
+        self.update_preedit_text_in_precomposition(engine);
+    }
+
+    /// 英数字を入力中であれば、そのことを auxiliary text に表示する。
+    /// 単語登録中や、文字コードなどの入力中は、それぞれの表示を優先する。
+    fn update_alnum_run_text(&mut self, engine: *mut IBusEngine) {
+        if self.current_state.word_registration.is_some()
+            || self.current_state.raw_input != RawInput::Normal
+            || self.current_state.in_conversion()
+        {
+            return;
+        }
+        unsafe {
+            if self.current_state.in_alnum_run() {
+                let auxiliary_text = "英数".to_ibus_text();
+                ibus_text_set_attributes(auxiliary_text, ibus_attr_list_new());
+                ibus_engine_update_auxiliary_text(engine, auxiliary_text, to_gboolean(true));
+            } else {
+                ibus_engine_hide_auxiliary_text(engine);
+            }
+        }
+    }
+
+    /// SKK の abbrev モードのように、英字の読みの入力を開始する。
+    /// "cpu" と入力して変換すると、辞書の英字の読みから "CPU" や "シーピーユー" を候補にする。
+    pub fn start_abbrev(&mut self, engine: *mut IBusEngine) {
+        if self.current_state.raw_input != RawInput::Normal {
+            return;
+        }
+        info!("Start abbrev");
+
+        self.current_state.raw_input = RawInput::Abbrev;
+        self.update_raw_input_text(engine);
+    }
+
+    /// 文字コードや英字の読みの入力中に、変換前に押されたキーを処理する。
+    /// 英数字や記号はローマ字かな変換せずに preedit に追加し、未入力状態の Escape で入力を中止する。
+    /// それ以外のキーは None を返して、通常の入力処理にまかせる。
+    fn process_key_event_in_raw_input(
+        &mut self,
+        engine: *mut IBusEngine,
+        keyval: guint,
+        modifiers: guint,
+    ) -> Option<bool> {
+        if modifiers & (IBusModifierType_IBUS_CONTROL_MASK | IBusModifierType_IBUS_MOD1_MASK) != 0 {
+            return None;
+        }
+
+        if keyval == IBUS_KEY_Escape && self.current_state.preedit.is_empty() {
+            info!("Raw input was cancelled");
+            self.current_state.raw_input = RawInput::Normal;
+            unsafe { ibus_engine_hide_auxiliary_text(engine) };
+            return Some(true);
+        }
+
+        if ('!' as u32) <= keyval && keyval <= ('~' as u32) {
+            self.current_state
+                .append_preedit(char::from_u32(keyval).unwrap());
+            self.update_preedit_text_in_precomposition(engine);
+            self.update_raw_input_text(engine);
+            return Some(true);
+        }
+        None
+    }
+
+    /// 文字コードの入力中であれば、入力中のコードに対応する文字を auxiliary text に表示する。
+    /// 英字の読みの入力中であれば、そのことを表示する。
+    fn update_raw_input_text(&mut self, engine: *mut IBusEngine) {
+        let text = match self.current_state.raw_input {
+            RawInput::CodeInput => format!(
+                "コード入力 [{}] {}",
+                self.current_state.preedit,
+                lookup_code(&self.current_state.preedit).join(" ")
+            ),
+            RawInput::Abbrev => format!("abbrev [{}]", self.current_state.preedit),
+            RawInput::Normal => return,
+        };
+        unsafe {
+            let auxiliary_text = text.to_ibus_text();
+            ibus_text_set_attributes(auxiliary_text, ibus_attr_list_new());
+            ibus_engine_update_auxiliary_text(engine, auxiliary_text, to_gboolean(true));
+        }
+    }
+
+    /// 選択中の候補の学習結果を忘れる。
+    /// 間違えて確定してしまった候補が、学習によって上位に出続けるのを防ぐため。
+    pub fn forget_candidate(&mut self, engine: *mut IBusEngine) -> Result<()> {
+        let Some(candidate) = self.current_state.get_selected_candidate() else {
+            return Ok(());
+        };
+        info!("Forget candidate: {}/{}", candidate.surface, candidate.yomi);
+        self.engine
+            .user_data
+            .lock()
+            .unwrap()
+            .forget(&candidate.yomi, &candidate.surface);
+
+        // 学習結果を反映させるため、変換しなおす。
+        self._update_candidates(engine)
+    }
+
     pub fn escape(&mut self, engine: *mut IBusEngine) {
         trace!("escape: {}", self.current_state.preedit);
         self.current_state.clear();
@@ -6,6 +6,26 @@ use libakaza::graph::candidate::Candidate;
 
 use crate::input_mode::InputMode;
 
+/// 単語登録中の状態
+#[derive(Debug, PartialEq)]
+pub struct WordRegistration {
+    /// 登録する単語の読み
+    pub yomi: String,
+    /// 入力中の表記
+    pub surface: String,
+}
+
+/// preedit をローマ字かな変換せずに、入力したまま扱う入力の種類。
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum RawInput {
+    /// 通常の入力。ローマ字かな変換する。
+    Normal,
+    /// 文字コードの入力。"u+2603" や "16-01" のような文字コードとして扱う。
+    CodeInput,
+    /// SKK の abbrev モードのような、英字の読みの入力。"cpu" のような英字のまま辞書を引く。
+    Abbrev,
+}
+
 #[derive(Debug)]
 pub struct CurrentState {
     pub(crate) input_mode: InputMode,
@@ -17,6 +37,16 @@ pub struct CurrentState {
     node_selected: HashMap<usize, usize>,
     /// シフト+右 or シフト+左で強制指定された範囲
     pub(crate) force_selected_clause: Vec<Range<usize>>,
+    /// 単語登録中であれば、その状態。
+    /// 単語登録中は、確定した文字列をアプリケーションに送らずに、登録する表記に追加していく。
+    pub(crate) word_registration: Option<WordRegistration>,
+    /// 文字コードや英字の読みを入力中かどうか。確定や中止で Normal に戻る。
+    pub(crate) raw_input: RawInput,
+    /// preedit の中で、ローマ字かな変換せずに英数字のまま読みにする部分の範囲(バイト数)。
+    /// "iPhoneを" のように、かなの途中で英数字を入力するためのもの。
+    pub(crate) alnum_ranges: Vec<Range<usize>>,
+    /// 英数字を入力中かどうか。入力中は、入力した文字を alnum_ranges の最後の範囲に追加していく。
+    alnum_run: bool,
 }
 
 impl CurrentState {
@@ -28,6 +58,10 @@ impl CurrentState {
             current_clause: 0,
             node_selected: HashMap::new(),
             force_selected_clause: Vec::new(),
+            word_registration: None,
+            raw_input: RawInput::Normal,
+            alnum_ranges: Vec::new(),
+            alnum_run: false,
         }
     }
 
@@ -44,6 +78,9 @@ impl CurrentState {
     pub(crate) fn clear(&mut self) {
         self.preedit.clear();
         self.clauses.clear();
+        self.alnum_ranges.clear();
+        self.alnum_run = false;
+        self.raw_input = RawInput::Normal;
 
         self.clear_state();
     }
@@ -56,15 +93,92 @@ impl CurrentState {
     }
 
     pub(crate) fn append_preedit(&mut self, ch: char) {
+        if self.alnum_run {
+            let end = self.preedit.len();
+            match self.alnum_ranges.last_mut() {
+                Some(range) if range.end == end => range.end += ch.len_utf8(),
+                _ => self.alnum_ranges.push(end..end + ch.len_utf8()),
+            }
+        }
         self.preedit.push(ch);
     }
 
     /// バックスペースで一文字削除した場合などに呼ばれる。
     pub(crate) fn set_preedit(&mut self, preedit: String) {
+        let alnum_ranges = std::mem::take(&mut self.alnum_ranges);
+        let alnum_run = self.alnum_run;
+        let raw_input = self.raw_input;
         self.clear();
+        self.raw_input = raw_input;
+        // 削られた分だけ、英数字の範囲も縮める。
+        self.alnum_ranges = alnum_ranges
+            .into_iter()
+            .map(|it| it.start..it.end.min(preedit.len()))
+            .filter(|it| !it.is_empty())
+            .collect();
+        self.alnum_run = alnum_run && !preedit.is_empty();
         self.preedit = preedit;
     }
 
+    /// 英数字の入力を始める。以降に入力した文字は、ローマ字かな変換せずにそのまま読みにする。
+    pub(crate) fn start_alnum_run(&mut self) {
+        self.alnum_run = true;
+    }
+
+    /// 英数字の入力と、かなの入力を切り替える。
+    pub(crate) fn toggle_alnum_run(&mut self) {
+        self.alnum_run = !self.alnum_run;
+    }
+
+    /// 英数字を入力中かどうか。
+    pub(crate) fn in_alnum_run(&self) -> bool {
+        self.alnum_run
+    }
+
+    /// preedit の最後の文字が、英数字として入力したものかどうか。
+    pub(crate) fn ends_with_alnum(&self) -> bool {
+        self.alnum_ranges
+            .last()
+            .is_some_and(|it| it.end == self.preedit.len())
+    }
+
+    /// 先頭が大文字で、かなを含まない入力かどうか。
+    /// "IME" のような入力は、変換せずにそのまま確定できるようにする。
+    pub(crate) fn is_uppercase_word(&self) -> bool {
+        self.preedit
+            .chars()
+            .next()
+            .is_some_and(|it| it.is_ascii_uppercase())
+            && (self.alnum_ranges.is_empty()
+                || (self.alnum_ranges.len() == 1
+                    && self.alnum_ranges[0] == (0..self.preedit.len())))
+    }
+
+    /// preedit の先頭から `end` までを読みにする。
+    /// 英数字の部分はそのままにして、それ以外の部分を `to_hiragana` でかなにする。
+    /// 読みと、読みの中での英数字の部分の範囲を返す。
+    pub(crate) fn to_yomi(
+        &self,
+        end: usize,
+        to_hiragana: impl Fn(&str) -> String,
+    ) -> (String, Vec<Range<usize>>) {
+        let mut yomi = String::new();
+        let mut ranges = Vec::new();
+        let mut pos = 0;
+        for range in &self.alnum_ranges {
+            if range.start >= end {
+                break;
+            }
+            yomi += &to_hiragana(&self.preedit[pos..range.start]);
+            let alnum = &self.preedit[range.start..range.end.min(end)];
+            ranges.push(yomi.len()..yomi.len() + alnum.len());
+            yomi += alnum;
+            pos = range.end.min(end);
+        }
+        yomi += &to_hiragana(&self.preedit[pos..end]);
+        (yomi, ranges)
+    }
+
     pub fn set_clauses(&mut self, clause: Vec<Vec<Candidate>>) {
         self.clauses = clause;
         self.node_selected.clear();
@@ -86,6 +200,13 @@ impl CurrentState {
         targets
     }
 
+    /// 現在選択されている文節で、選択されている候補
+    pub fn get_selected_candidate(&self) -> Option<&Candidate> {
+        let candidates = self.clauses.get(self.current_clause)?;
+        let idx = self.node_selected.get(&self.current_clause).unwrap_or(&0);
+        candidates.get(*idx)
+    }
+
     /// 一個右の文節を選択する
     pub fn select_right_clause(&mut self) {
         if self.current_clause == self.clauses.len() - 1 {
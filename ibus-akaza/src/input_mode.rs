@@ -28,6 +28,14 @@ impl InputMode {
             label,
         }
     }
+
+    /// ローマ字かな変換をする入力モードかどうか。
+    pub fn is_kana(&self) -> bool {
+        matches!(
+            self.prop_name,
+            "InputMode.Hiragana" | "InputMode.Katakana" | "InputMode.HalfWidthKatakana"
+        )
+    }
 }
 
 pub const INPUT_MODE_ALNUM: InputMode =
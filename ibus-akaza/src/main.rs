@@ -19,6 +19,7 @@ use libakaza::engine::bigram_word_viterbi_engine::BigramWordViterbiEngineBuilder
 use libakaza::user_side_data::user_data::UserData;
 
 use crate::context::AkazaContext;
+use crate::reloader::spawn_config_watcher;
 use crate::wrapper_bindings::{ibus_akaza_init, ibus_akaza_set_callback};
 
 mod commands;
@@ -26,6 +27,7 @@ mod context;
 mod current_state;
 mod input_mode;
 mod keymap;
+mod reloader;
 mod ui;
 mod wrapper_bindings;
 
@@ -72,6 +74,16 @@ unsafe extern "C" fn property_activate(
     );
 }
 
+unsafe extern "C" fn set_content_type(
+    context: *mut c_void,
+    engine: *mut IBusEngine,
+    purpose: guint,
+    hints: guint,
+) {
+    let context_ref = &mut *(context as *mut AkazaContext);
+    context_ref.do_set_content_type(engine, purpose, hints);
+}
+
 fn load_user_data() -> Arc<Mutex<UserData>> {
     match UserData::load_from_default_path() {
         Ok(user_data) => Arc::new(Mutex::new(user_data)),
@@ -82,6 +94,33 @@ fn load_user_data() -> Arc<Mutex<UserData>> {
     }
 }
 
+fn save_user_data(user_data: &Arc<Mutex<UserData>>) {
+    if let Ok(mut data) = user_data.lock() {
+        if let Err(e) = data.write_user_files() {
+            warn!("Cannot save user stats file: {}", e);
+        }
+    } else {
+        warn!("Cannot get mutex for saving user data")
+    };
+}
+
+/// ユーザー辞書を定期的にバックグラウンドで保存するスレッド。
+/// 変更がなければ何もしない。
+fn spawn_autosave_thread(user_data: Arc<Mutex<UserData>>) -> Result<()> {
+    thread::Builder::new()
+        .name("user-data-save-thread".to_string())
+        .spawn(move || {
+            let interval = time::Duration::from_secs(3);
+
+            // スレッド内で雑に例外投げるとスレッドとまっちゃうので丁寧めに処理する。
+            loop {
+                save_user_data(&user_data);
+                thread::sleep(interval);
+            }
+        })?;
+    Ok(())
+}
+
 #[derive(Debug, clap::Parser)]
 #[command(author, version, about, long_about = None)]
 struct IBusAkazaArgs {
@@ -123,11 +162,13 @@ fn main() -> Result<()> {
     unsafe {
         let sys_time = SystemTime::now();
         let user_data = load_user_data();
-        let config = Config::load()?;
-        let akaza = BigramWordViterbiEngineBuilder::new(Config::load()?.engine)
+        let (config, config_issues) = Config::load_with_issues()?;
+        let akaza = BigramWordViterbiEngineBuilder::new(config.engine.clone())
             .user_data(user_data.clone())
             .build()?;
-        let mut ac = AkazaContext::new(akaza, config);
+        // 設定ファイルや辞書が更新されたら、バックグラウンドで読み直す。
+        let reload_receiver = spawn_config_watcher(config.clone(), user_data.clone())?;
+        let mut ac = AkazaContext::new(akaza, config, config_issues, reload_receiver)?;
         let new_sys_time = SystemTime::now();
         let difference = new_sys_time.duration_since(sys_time)?;
         info!(
@@ -135,24 +176,7 @@ fn main() -> Result<()> {
             difference.as_millis()
         );
 
-        // ユーザー辞書をバックグラウンドで保存するスレッド。
-        thread::Builder::new()
-            .name("user-data-save-thread".to_string())
-            .spawn(move || {
-                let interval = time::Duration::from_secs(3);
-
-                // スレッド内で雑に例外投げるとスレッドとまっちゃうので丁寧めに処理する。
-                loop {
-                    if let Ok(mut data) = user_data.lock() {
-                        if let Err(e) = data.write_user_files() {
-                            warn!("Cannot save user stats file: {}", e);
-                        }
-                    } else {
-                        warn!("Cannot get mutex for saving user data")
-                    };
-                    thread::sleep(interval);
-                }
-            })?;
+        spawn_autosave_thread(user_data.clone())?;
 
         ibus_akaza_set_callback(
             &mut ac as *mut _ as *mut c_void,
@@ -160,6 +184,7 @@ fn main() -> Result<()> {
             candidate_clicked,
             focus_in,
             property_activate,
+            set_content_type,
         );
 
         ibus_akaza_init(arg.ibus);
@@ -170,6 +195,8 @@ fn main() -> Result<()> {
         ibus_main();
 
         warn!("Should not reach here.");
+        // 念の為、最後に保存しておく。
+        save_user_data(&user_data);
     }
     Ok(())
 }
@@ -0,0 +1,207 @@
+use std::fs;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use log::{info, warn};
+
+use libakaza::config::{Config, ConfigIssue};
+use libakaza::engine::bigram_word_viterbi_engine::{
+    BigramWordViterbiEngine, BigramWordViterbiEngineBuilder,
+};
+use libakaza::kana_kanji::marisa_kana_kanji_dict::MarisaKanaKanjiDict;
+use libakaza::keymap::Keymap;
+use libakaza::lm::system_bigram::MarisaSystemBigramLM;
+use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
+use libakaza::romkan::{RomKanConfig, RomKanConverter};
+use libakaza::user_side_data::user_data::UserData;
+
+use crate::keymap::KeyMap;
+
+pub type AkazaEngine =
+    BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM, MarisaKanaKanjiDict>;
+
+/// バックグラウンドで作り直したコンポーネント。
+/// 変更がなかったものは None になる。
+pub struct ReloadedComponents {
+    pub keymap: Option<KeyMap>,
+    pub romkan: Option<RomKanConverter>,
+    pub engine: Option<AkazaEngine>,
+    /// 読み直した設定ファイルの問題点
+    pub config_issues: Vec<ConfigIssue>,
+}
+
+/// 監視対象のファイルの mtime
+/// キーマップとローマ字かな変換テーブルは、extends している親のファイルも含む。
+#[derive(PartialEq)]
+struct WatchedFiles {
+    config: Option<SystemTime>,
+    keymap: Vec<Option<SystemTime>>,
+    romkan: Vec<Option<SystemTime>>,
+    dicts: Vec<Option<SystemTime>>,
+    user_dict: Option<SystemTime>,
+}
+
+impl WatchedFiles {
+    fn new(config_path: &str, config: &Config, user_dict_path: Option<&str>) -> WatchedFiles {
+        WatchedFiles {
+            config: get_mtime(config_path),
+            keymap: Keymap::extends_chain(&config.keymap)
+                .iter()
+                .map(|it| get_mtime(it))
+                .collect(),
+            romkan: RomKanConfig::extends_chain(&config.romkan)
+                .iter()
+                .map(|it| get_mtime(it))
+                .collect(),
+            dicts: config
+                .engine
+                .dicts
+                .iter()
+                .map(|it| get_mtime(&it.path))
+                .collect(),
+            user_dict: user_dict_path.and_then(get_mtime),
+        }
+    }
+}
+
+fn get_mtime(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|it| it.modified()).ok()
+}
+
+/// config.yml と、そこで指定されているキーマップ、ローマ字かな変換テーブル(extends している親も含む)、
+/// 辞書ファイルを監視する。
+/// 変更があったら、影響をうけるコンポーネントをバックグラウンドで作り直して、返り値の Receiver に送る。
+/// 受け取った側は、キー入力の合間に差し替えること。
+/// ユーザー辞書が akaza-conf などで更新された場合は、UserData に読み直させる。
+pub fn spawn_config_watcher(
+    config: Config,
+    user_data: Arc<Mutex<UserData>>,
+) -> Result<Receiver<ReloadedComponents>> {
+    let config_path = Config::file_name()?.to_string_lossy().to_string();
+    let user_dict_path = user_data
+        .lock()
+        .unwrap()
+        .dict_path()
+        .map(|it| it.to_string());
+    let (sender, receiver) = channel();
+
+    thread::Builder::new()
+        .name("config-watcher-thread".to_string())
+        .spawn(move || {
+            let interval = Duration::from_secs(2);
+            let mut config = config;
+            let mut watched = WatchedFiles::new(&config_path, &config, user_dict_path.as_deref());
+
+            loop {
+                thread::sleep(interval);
+
+                let current = WatchedFiles::new(&config_path, &config, user_dict_path.as_deref());
+                if current == watched {
+                    continue;
+                }
+
+                if !reload(
+                    &config_path,
+                    &mut config,
+                    &watched,
+                    &current,
+                    &user_data,
+                    &sender,
+                ) {
+                    info!("Receiver was dropped. Stop watching the configuration files.");
+                    break;
+                }
+                watched = WatchedFiles::new(&config_path, &config, user_dict_path.as_deref());
+            }
+        })?;
+
+    Ok(receiver)
+}
+
+/// 変更のあったコンポーネントを作り直して送る。
+/// Receiver がすでに破棄されていたら false を返す。
+fn reload(
+    config_path: &str,
+    config: &mut Config,
+    prev: &WatchedFiles,
+    current: &WatchedFiles,
+    user_data: &Arc<Mutex<UserData>>,
+    sender: &Sender<ReloadedComponents>,
+) -> bool {
+    let mut config_issues = Vec::new();
+    let new_config = if current.config != prev.config {
+        info!("{} was modified. Reloading.", config_path);
+        match Config::load_from_file_with_issues(config_path) {
+            Ok((new_config, issues)) => {
+                for issue in &issues {
+                    warn!("{}: {}", config_path, issue);
+                }
+                config_issues = issues;
+                new_config
+            }
+            Err(err) => {
+                // 読めない設定は使わずに、今の設定のままにしておく。
+                warn!("Cannot load {}: {}", config_path, err);
+                config_issues.push(ConfigIssue::ParseError(format!("{:#}", err)));
+                config.clone()
+            }
+        }
+    } else {
+        config.clone()
+    };
+
+    let keymap = if new_config.keymap != config.keymap || current.keymap != prev.keymap {
+        info!("Reloading keymap: {}", new_config.keymap);
+        KeyMap::new(new_config.keymap.clone())
+            .map_err(|err| warn!("Cannot load keymap: {}", err))
+            .ok()
+    } else {
+        None
+    };
+
+    let romkan = if new_config.romkan != config.romkan || current.romkan != prev.romkan {
+        info!("Reloading romkan: {}", new_config.romkan);
+        RomKanConverter::new(new_config.romkan.as_str())
+            .map_err(|err| warn!("Cannot load romkan table: {}", err))
+            .ok()
+    } else {
+        None
+    };
+
+    let engine = if new_config.engine != config.engine || current.dicts != prev.dicts {
+        info!("Rebuilding the engine: {:?}", new_config.engine);
+        BigramWordViterbiEngineBuilder::new(new_config.engine.clone())
+            .user_data(user_data.clone())
+            .build()
+            .map_err(|err| warn!("Cannot build the engine: {}", err))
+            .ok()
+    } else {
+        None
+    };
+
+    *config = new_config;
+
+    if current.user_dict != prev.user_dict {
+        // ibus-akaza 自身が保存した場合は、内容が変わっていないので何もしない。
+        // まだ保存していない学習結果は、読み直した内容にマージされる。
+        info!("User dictionary was modified. Reloading.");
+        if let Err(err) = user_data.lock().unwrap().reload_user_dict() {
+            warn!("Cannot reload user dictionary: {}", err);
+        }
+    }
+
+    if keymap.is_none() && romkan.is_none() && engine.is_none() && config_issues.is_empty() {
+        return true;
+    }
+    sender
+        .send(ReloadedComponents {
+            keymap,
+            romkan,
+            engine,
+            config_issues,
+        })
+        .is_ok()
+}
@@ -10,7 +10,7 @@ use ibus_sys::property::{
     ibus_property_new, ibus_property_set_label, ibus_property_set_state,
     ibus_property_set_sub_props, ibus_property_set_symbol, IBusPropState_PROP_STATE_CHECKED,
     IBusPropState_PROP_STATE_UNCHECKED, IBusPropType_PROP_TYPE_MENU, IBusPropType_PROP_TYPE_RADIO,
-    IBusProperty,
+    IBusPropType_PROP_TYPE_TOGGLE, IBusProperty,
 };
 use ibus_sys::text::{IBusText, StringExt};
 
@@ -22,16 +22,20 @@ pub struct PropController {
     input_mode_prop: *mut IBusProperty,
     /// メニューの input mode ごとのメニュープロパティたち。
     prop_dict: HashMap<String, *mut IBusProperty>,
+    /// 学習しないモード(シークレットモード)の切り替え
+    incognito_prop: *mut IBusProperty,
 }
 
 impl PropController {
     pub fn new(initial_input_mode: InputMode) -> Result<Self> {
-        let (input_mode_prop, prop_list, prop_dict) = Self::init_props(initial_input_mode);
+        let (input_mode_prop, prop_list, prop_dict, incognito_prop) =
+            Self::init_props(initial_input_mode);
 
         Ok(PropController {
             prop_list,
             input_mode_prop,
             prop_dict,
+            incognito_prop,
         })
     }
 
@@ -51,6 +55,7 @@ impl PropController {
         *mut IBusProperty,
         *mut IBusPropList,
         HashMap<String, *mut IBusProperty>,
+        *mut IBusProperty,
     ) {
         unsafe {
             let prop_list =
@@ -107,7 +112,21 @@ impl PropController {
             ) as gpointer) as *mut IBusProperty;
             ibus_prop_list_append(prop_list, preference_prop);
 
-            (input_mode_prop, prop_list, prop_map)
+            // 学習しないモード
+            let incognito_prop = g_object_ref_sink(ibus_property_new(
+                "Incognito\0".as_ptr() as *const gchar,
+                IBusPropType_PROP_TYPE_TOGGLE,
+                "学習しない(シークレットモード)".to_ibus_text(),
+                "\0".as_ptr() as *const gchar,
+                "Do not learn from the input".to_ibus_text(),
+                to_gboolean(true),
+                to_gboolean(true),
+                IBusPropState_PROP_STATE_UNCHECKED,
+                std::ptr::null_mut() as *mut IBusPropList,
+            ) as gpointer) as *mut IBusProperty;
+            ibus_prop_list_append(prop_list, incognito_prop);
+
+            (input_mode_prop, prop_list, prop_map, incognito_prop)
         }
     }
 
@@ -132,4 +151,19 @@ impl PropController {
             ibus_engine_update_property(engine, *property);
         }
     }
+
+    /// 学習しないモードの切り替え時に実行される処理
+    pub fn set_incognito(&self, incognito: bool, engine: *mut IBusEngine) {
+        unsafe {
+            ibus_property_set_state(
+                self.incognito_prop,
+                if incognito {
+                    IBusPropState_PROP_STATE_CHECKED
+                } else {
+                    IBusPropState_PROP_STATE_UNCHECKED
+                },
+            );
+            ibus_engine_update_property(engine, self.incognito_prop);
+        }
+    }
 }
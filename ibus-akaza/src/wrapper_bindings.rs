@@ -31,6 +31,13 @@ pub(crate) type ibus_akaza_callback_property_activate = unsafe extern "C" fn(
     prop_state: guint,
 );
 
+pub(crate) type ibus_akaza_callback_set_content_type = unsafe extern "C" fn(
+    context: *mut c_void,
+    engine: *mut IBusEngine,
+    purpose: guint,
+    hints: guint,
+);
+
 pub(crate) type ibus_akaza_callback_focus_in =
     unsafe extern "C" fn(context: *mut c_void, engine: *mut IBusEngine);
 
@@ -44,5 +51,6 @@ extern "C" {
         candidate_cb: ibus_akaza_callback_candidate_clicked,
         focus_in_cb: ibus_akaza_callback_focus_in,
         property_activate: ibus_akaza_callback_property_activate,
+        set_content_type: ibus_akaza_callback_set_content_type,
     );
 }
@@ -11,6 +11,7 @@ static ibus_akaza_callback_key_event global_key_event_cb = NULL;
 static ibus_akaza_callback_candidate_clicked global_candidate_clicked_cb = NULL;
 static ibus_akaza_callback_focus_in global_focus_in_cb = NULL;
 static ibus_akaza_callback_property_activate global_property_activate_cb = NULL;
+static ibus_akaza_callback_set_content_type global_set_content_type_cb = NULL;
 
 #define IBUS_TYPE_AKAZA_ENGINE        \
         (ibus_akaza_engine_get_type ())
@@ -37,6 +38,11 @@ static void ibus_akaza_engine_property_activate(
     const gchar *prop_name,
     guint prop_state
 );
+static void ibus_akaza_engine_set_content_type(
+    IBusEngine *engine,
+    guint purpose,
+    guint hints
+);
 
 G_DEFINE_TYPE(IBusAkazaEngine, ibus_akaza_engine, IBUS_TYPE_ENGINE)
 
@@ -71,6 +77,14 @@ static void ibus_akaza_engine_property_activate(
    global_property_activate_cb(global_context, engine, prop_name, prop_state);
 }
 
+static void ibus_akaza_engine_set_content_type(
+    IBusEngine *engine,
+    guint purpose,
+    guint hints
+) {
+   global_set_content_type_cb(global_context, engine, purpose, hints);
+}
+
 static gboolean ibus_akaza_engine_process_key_event(IBusEngine *engine,
                                                       guint keyval,
                                                       guint keycode,
@@ -93,6 +107,7 @@ static void ibus_akaza_engine_class_init(IBusAkazaEngineClass *klass) {
   engine_class->candidate_clicked = ibus_akaza_engine_candidate_clicked;
   engine_class->focus_in = ibus_akaza_engine_focus_in;
   engine_class->property_activate = ibus_akaza_engine_property_activate;
+  engine_class->set_content_type = ibus_akaza_engine_set_content_type;
 }
 
 
@@ -101,13 +116,15 @@ void ibus_akaza_set_callback(
     ibus_akaza_callback_key_event* key_event_cb,
     ibus_akaza_callback_candidate_clicked* candidate_cb,
     ibus_akaza_callback_focus_in* focus_in_cb,
-    ibus_akaza_callback_property_activate* property_activate_cb
+    ibus_akaza_callback_property_activate* property_activate_cb,
+    ibus_akaza_callback_set_content_type* set_content_type_cb
 ) {
     global_context = context;
     global_key_event_cb = key_event_cb;
     global_candidate_clicked_cb = candidate_cb;
     global_focus_in_cb = focus_in_cb;
     global_property_activate_cb = property_activate_cb;
+    global_set_content_type_cb = set_content_type_cb;
 }
 
 void ibus_akaza_init(bool ibus) {
@@ -38,3 +38,33 @@ extern "C" {
 }
 
 pub type IBusEngine = [u64; 11usize];
+
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_FREE_FORM: IBusInputPurpose = 0;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_ALPHA: IBusInputPurpose = 1;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_DIGITS: IBusInputPurpose = 2;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_NUMBER: IBusInputPurpose = 3;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_PHONE: IBusInputPurpose = 4;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_URL: IBusInputPurpose = 5;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_EMAIL: IBusInputPurpose = 6;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_NAME: IBusInputPurpose = 7;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_PASSWORD: IBusInputPurpose = 8;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_PIN: IBusInputPurpose = 9;
+pub const IBusInputPurpose_IBUS_INPUT_PURPOSE_TERMINAL: IBusInputPurpose = 10;
+#[doc = " IBusInputPurpose:\n\n Describes primary purpose of the input context.\n This information is provided by the client via ibus_input_context_set_content_type()."]
+pub type IBusInputPurpose = ::std::os::raw::c_uint;
+
+pub const IBusInputHints_IBUS_INPUT_HINT_NONE: IBusInputHints = 0;
+pub const IBusInputHints_IBUS_INPUT_HINT_SPELLCHECK: IBusInputHints = 1;
+pub const IBusInputHints_IBUS_INPUT_HINT_NO_SPELLCHECK: IBusInputHints = 2;
+pub const IBusInputHints_IBUS_INPUT_HINT_WORD_COMPLETION: IBusInputHints = 4;
+pub const IBusInputHints_IBUS_INPUT_HINT_LOWERCASE: IBusInputHints = 8;
+pub const IBusInputHints_IBUS_INPUT_HINT_UPPERCASE_CHARS: IBusInputHints = 16;
+pub const IBusInputHints_IBUS_INPUT_HINT_UPPERCASE_WORDS: IBusInputHints = 32;
+pub const IBusInputHints_IBUS_INPUT_HINT_UPPERCASE_SENTENCES: IBusInputHints = 64;
+pub const IBusInputHints_IBUS_INPUT_HINT_INHIBIT_OSK: IBusInputHints = 128;
+pub const IBusInputHints_IBUS_INPUT_HINT_VERTICAL_WRITING: IBusInputHints = 256;
+pub const IBusInputHints_IBUS_INPUT_HINT_EMOJI: IBusInputHints = 512;
+pub const IBusInputHints_IBUS_INPUT_HINT_NO_EMOJI: IBusInputHints = 1024;
+pub const IBusInputHints_IBUS_INPUT_HINT_PRIVATE: IBusInputHints = 2048;
+#[doc = " IBusInputHints:\n\n Describes hints that might be taken into account by engines.\n Note that engines may ignore the hints."]
+pub type IBusInputHints = ::std::os::raw::c_uint;
@@ -0,0 +1,170 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use anyhow::Result;
+use log::warn;
+
+/// 一つ前の世代のファイルのパス
+pub(crate) fn backup_path(path: &str) -> String {
+    path.to_string() + ".bak"
+}
+
+/// path にファイルを書き込む。
+///
+/// 一時ファイルに書いて fsync してから rename するので、書き込み中にクラッシュしても
+/// path が中途半端な状態になることはない。
+/// 既存のファイルは `{path}.bak` として一世代分残しておく。
+pub(crate) fn write_file_with_backup<F>(path: &str, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<&File>) -> Result<()>,
+{
+    write_file(path, true, write)
+}
+
+/// path にファイルを書き込む。
+///
+/// [`write_file_with_backup`] と同じく一時ファイルを rename するが、バックアップは残さない。
+/// ユーザーが指定した場所に書き出すファイルのように、`.bak` を作られると困るもの向け。
+pub(crate) fn write_file_atomically<F>(path: &str, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<&File>) -> Result<()>,
+{
+    write_file(path, false, write)
+}
+
+fn write_file<F>(path: &str, backup: bool, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<&File>) -> Result<()>,
+{
+    let tmp_path = path.to_string() + ".tmp";
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // ユーザーの入力履歴を含むので、他のユーザーからは読めないようにする。
+    #[cfg(unix)]
+    options.mode(0o600);
+    let file = options.open(&tmp_path)?;
+    {
+        let mut writer = BufWriter::new(&file);
+        write(&mut writer)?;
+        writer.flush()?;
+    }
+    file.sync_all()?;
+
+    if backup && Path::new(path).exists() {
+        fs::rename(path, backup_path(path))?;
+    }
+    fs::rename(&tmp_path, path)?;
+
+    // rename 自体をディスクに反映させるために、ディレクトリも fsync する。
+    #[cfg(unix)]
+    if let Some(dir) = Path::new(path).parent() {
+        if let Err(err) = File::open(dir).and_then(|it| it.sync_all()) {
+            warn!("Cannot sync directory {:?}: {}", dir, err);
+        }
+    }
+
+    Ok(())
+}
+
+/// path のファイルを読み込む。
+/// 読み込みに失敗した場合は、`{path}.bak` から読み込む。両方ダメなら、path を読んだときのエラーを返す。
+pub(crate) fn read_file_with_backup<T, F>(path: &str, read: F) -> Result<T>
+where
+    F: Fn(&str) -> Result<T>,
+{
+    match read(path) {
+        Ok(got) => Ok(got),
+        Err(err) => {
+            let backup = backup_path(path);
+            if !Path::new(&backup).exists() {
+                return Err(err);
+            }
+            warn!("Cannot read {}: {}. Try to read {}", path, err, backup);
+            read(&backup).map_err(|_| err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn read_string(path: &str) -> Result<String> {
+        let mut buf = String::new();
+        File::open(path)?.read_to_string(&mut buf)?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_write_file_with_backup() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test.txt").to_string_lossy().to_string();
+
+        write_file_with_backup(&path, |w| Ok(w.write_all(b"first")?))?;
+        assert_eq!(read_string(&path)?, "first");
+        assert!(!Path::new(&backup_path(&path)).exists());
+
+        write_file_with_backup(&path, |w| Ok(w.write_all(b"second")?))?;
+        assert_eq!(read_string(&path)?, "second");
+        assert_eq!(read_string(&backup_path(&path))?, "first");
+        assert!(!Path::new(&(path.clone() + ".tmp")).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_file_atomically() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test.txt").to_string_lossy().to_string();
+
+        write_file_atomically(&path, |w| Ok(w.write_all(b"first")?))?;
+        write_file_atomically(&path, |w| Ok(w.write_all(b"second")?))?;
+        assert_eq!(read_string(&path)?, "second");
+        assert!(!Path::new(&backup_path(&path)).exists());
+        assert!(!Path::new(&(path.clone() + ".tmp")).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_failure() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test.txt").to_string_lossy().to_string();
+
+        write_file_with_backup(&path, |w| Ok(w.write_all(b"first")?))?;
+        // 書き込みに失敗しても、既存のファイルは壊れない。
+        let result = write_file_with_backup(&path, |_| anyhow::bail!("failed"));
+        assert!(result.is_err());
+        assert_eq!(read_string(&path)?, "first");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_file_with_backup() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("test.txt").to_string_lossy().to_string();
+        let parse = |path: &str| -> Result<u32> { Ok(read_string(path)?.parse::<u32>()?) };
+
+        // どちらもない
+        assert!(read_file_with_backup(&path, parse).is_err());
+
+        write_file_with_backup(&path, |w| Ok(w.write_all(b"1")?))?;
+        write_file_with_backup(&path, |w| Ok(w.write_all(b"2")?))?;
+        assert_eq!(read_file_with_backup(&path, parse)?, 2);
+
+        // 壊れていたらバックアップから読む
+        fs::write(&path, "broken")?;
+        assert_eq!(read_file_with_backup(&path, parse)?, 1);
+
+        // 本体が消えていてもバックアップから読む
+        fs::remove_file(&path)?;
+        assert_eq!(read_file_with_backup(&path, parse)?, 1);
+        Ok(())
+    }
+}
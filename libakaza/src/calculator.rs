@@ -0,0 +1,284 @@
+//! 計算機能。
+//!
+//! 「12*3+4=」のように、末尾が = の式を変換すると、計算結果を候補にする。
+//! 整数、小数、かっこ、全角の数字や記号に対応している。
+
+use crate::graph::candidate_provider::CandidateProvider;
+use crate::graph::word_node::WordNode;
+
+/// 計算中の値。整数どうしの計算は、桁あふれしないかぎり整数のまま計算する。
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Value {
+    Int(i128),
+    Float(f64),
+}
+
+impl Value {
+    fn as_f64(&self) -> f64 {
+        match self {
+            Value::Int(i) => *i as f64,
+            Value::Float(f) => *f,
+        }
+    }
+
+    fn binary(
+        self,
+        other: Value,
+        int_op: fn(i128, i128) -> Option<i128>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Value {
+        if let (Value::Int(a), Value::Int(b)) = (self, other) {
+            if let Some(result) = int_op(a, b) {
+                return Value::Int(result);
+            }
+        }
+        Value::Float(float_op(self.as_f64(), other.as_f64()))
+    }
+
+    fn div(self, other: Value) -> Option<Value> {
+        if other.as_f64() == 0_f64 {
+            return None;
+        }
+        if let (Value::Int(a), Value::Int(b)) = (self, other) {
+            // i128::MIN / -1 は桁あふれするので、小数で計算する。
+            if a.checked_rem(b) == Some(0) {
+                if let Some(result) = a.checked_div(b) {
+                    return Some(Value::Int(result));
+                }
+            }
+        }
+        Some(Value::Float(self.as_f64() / other.as_f64()))
+    }
+
+    fn format(&self) -> Option<String> {
+        match self {
+            Value::Int(i) => Some(i.to_string()),
+            Value::Float(f) => {
+                if !f.is_finite() {
+                    return None;
+                }
+                // 0.1+0.2 のような計算の誤差が見えないように、小数点以下は 10 桁までにする。
+                let s = format!("{:.10}", f);
+                let s = s.trim_end_matches('0').trim_end_matches('.');
+                Some(if s == "-0" {
+                    "0".to_string()
+                } else {
+                    s.to_string()
+                })
+            }
+        }
+    }
+}
+
+/// 全角の数字や記号、ローマ字かな変換された記号("ー", "。")を、ASCII の式にする。
+fn normalize(src: &str) -> String {
+    src.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap(),
+            '．' | '。' => '.',
+            '＋' => '+',
+            'ー' | '－' | '−' => '-',
+            '＊' | '×' => '*',
+            '／' | '÷' => '/',
+            '（' => '(',
+            '）' => ')',
+            '＝' => '=',
+            _ => c,
+        })
+        .collect()
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    /// 二項演算子の数。演算子のない "123=" は式として扱わない。
+    operators: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str) -> Self {
+        Parser {
+            chars: src.chars().peekable(),
+            operators: 0,
+        }
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Option<Value> {
+        let mut value = self.term()?;
+        while let Some(&op) = self.chars.peek() {
+            if op != '+' && op != '-' {
+                break;
+            }
+            self.chars.next();
+            self.operators += 1;
+            let rhs = self.term()?;
+            value = if op == '+' {
+                value.binary(rhs, i128::checked_add, |a, b| a + b)
+            } else {
+                value.binary(rhs, i128::checked_sub, |a, b| a - b)
+            };
+        }
+        Some(value)
+    }
+
+    /// term := factor (('*' | '/') factor)*
+    fn term(&mut self) -> Option<Value> {
+        let mut value = self.factor()?;
+        while let Some(&op) = self.chars.peek() {
+            if op != '*' && op != '/' {
+                break;
+            }
+            self.chars.next();
+            self.operators += 1;
+            let rhs = self.factor()?;
+            value = if op == '*' {
+                value.binary(rhs, i128::checked_mul, |a, b| a * b)
+            } else {
+                value.div(rhs)?
+            };
+        }
+        Some(value)
+    }
+
+    /// factor := ('+' | '-') factor | '(' expr ')' | number
+    fn factor(&mut self) -> Option<Value> {
+        match self.chars.peek()? {
+            '+' => {
+                self.chars.next();
+                self.factor()
+            }
+            '-' => {
+                self.chars.next();
+                Some(Value::Int(0).binary(self.factor()?, i128::checked_sub, |a, b| a - b))
+            }
+            '(' => {
+                self.chars.next();
+                let value = self.expr()?;
+                if self.chars.next()? != ')' {
+                    return None;
+                }
+                Some(value)
+            }
+            _ => self.number(),
+        }
+    }
+
+    /// number := digit+ ('.' digit+)?
+    fn number(&mut self) -> Option<Value> {
+        let mut buf = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_digit() && c != '.' {
+                break;
+            }
+            buf.push(c);
+            self.chars.next();
+        }
+        if buf.is_empty() || buf.starts_with('.') || buf.ends_with('.') {
+            return None;
+        }
+        if buf.contains('.') {
+            Some(Value::Float(buf.parse().ok()?))
+        } else {
+            Some(Value::Int(buf.parse().ok()?))
+        }
+    }
+}
+
+/// 末尾が = の式を計算して、(式, 計算結果) を返す。式は ASCII にしたもの。
+/// 式として読めない場合や、0 で割った場合は None を返す。
+pub fn calculate(src: &str) -> Option<(String, String)> {
+    let expr = normalize(src);
+    let body = expr.strip_suffix('=')?;
+
+    let mut parser = Parser::new(body);
+    let value = parser.expr()?;
+    if parser.chars.next().is_some() || parser.operators == 0 {
+        return None;
+    }
+    Some((expr.clone(), value.format()?))
+}
+
+/// 読みの残り全体が計算できる式であれば、その長さを返す。
+/// 式の途中で分節が切れないように、Segmenter で使う。
+pub(crate) fn expression_len(yomi: &str) -> Option<usize> {
+    calculate(yomi).map(|_| yomi.len())
+}
+
+/// 計算結果の候補を作る CandidateProvider。
+pub struct CalculatorProvider;
+
+impl CandidateProvider for CalculatorProvider {
+    fn provide(&self, _yomi: &str, segmented_yomi: &str, start_pos: i32) -> Vec<WordNode> {
+        let Some((expr, result)) = calculate(segmented_yomi) else {
+            return Vec::new();
+        };
+        [result.clone(), expr + &result]
+            .iter()
+            .map(|surface| WordNode::new(start_pos, surface, segmented_yomi, None, true))
+            .collect()
+    }
+
+    /// 末尾が = の式("12*3+4=")は、計算結果を候補にするために全体を一つの単語として扱う。
+    fn segment_lens(&self, yomi: &str) -> Vec<usize> {
+        expression_len(yomi).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calc(src: &str) -> Option<String> {
+        calculate(src).map(|(_, result)| result)
+    }
+
+    #[test]
+    fn test_calculate() {
+        assert_eq!(
+            calculate("12*3+4="),
+            Some(("12*3+4=".to_string(), "40".to_string()))
+        );
+        assert_eq!(calc("(1+2)*3="), Some("9".to_string()));
+        assert_eq!(calc("1+2*3="), Some("7".to_string()));
+        assert_eq!(calc("10/4="), Some("2.5".to_string()));
+        assert_eq!(calc("10/5="), Some("2".to_string()));
+        assert_eq!(calc("1/3="), Some("0.3333333333".to_string()));
+        assert_eq!(calc("0.1+0.2="), Some("0.3".to_string()));
+        assert_eq!(calc("-3*-2="), Some("6".to_string()));
+        // f64 では桁が足りない大きさでも、整数なら正確に計算する
+        assert_eq!(
+            calc("99999999999*99999999999="),
+            Some("9999999999800000000001".to_string())
+        );
+        // 整数で割ると桁あふれするものは、小数で計算する
+        assert_eq!(
+            calc("-85070591730234615865843651857942052864*2/-1="),
+            Some("170141183460469231731687303715884105728".to_string())
+        );
+    }
+
+    #[test]
+    fn test_full_width() {
+        // ローマ字かな変換で "-" は "ー" に、"." は "。" になっている。
+        assert_eq!(
+            calculate("１２ー３。５＝"),
+            Some(("12-3.5=".to_string(), "8.5".to_string()))
+        );
+        assert_eq!(calc("（１＋２）×３＝"), Some("9".to_string()));
+    }
+
+    #[test]
+    fn test_not_expression() {
+        // = がない
+        assert_eq!(calc("1+2"), None);
+        // 演算子がない
+        assert_eq!(calc("123="), None);
+        assert_eq!(calc("1+="), None);
+        assert_eq!(calc("(1+2="), None);
+        assert_eq!(calc("1+2)="), None);
+        assert_eq!(calc("1/0="), None);
+        assert_eq!(calc("あ+い="), None);
+        assert_eq!(calc("1.2.3+1="), None);
+    }
+}
@@ -0,0 +1,93 @@
+//! 文字コードによる入力。
+//!
+//! "u+2603" のような Unicode のコードポイントや、"16-01" のような JIS X 0208 の区点コードから
+//! 文字を得る。区点コードは、EUC-JP の辞書を読むのにも使っている encoding_rs の表で引く。
+
+use encoding_rs::EUC_JP;
+
+/// 文字コードに対応する文字を返す。
+///
+/// - `u+2603`, `U+1F600`: Unicode のコードポイント
+/// - `16-01`: JIS X 0208 の区点コード
+/// - `1601`: 区点コードと、Unicode のコードポイントの両方として解釈する
+///
+/// 対応する文字がなければ空を返す。
+pub fn lookup_code(code: &str) -> Vec<String> {
+    let code = code.trim().to_ascii_lowercase();
+
+    if let Some(hex) = code.strip_prefix("u+") {
+        return from_code_point(hex).into_iter().collect();
+    }
+
+    if let Some((ku, ten)) = code.split_once('-') {
+        return from_kuten(ku, ten).into_iter().collect();
+    }
+
+    let mut result = Vec::new();
+    if code.len() == 4 && code.bytes().all(|b| b.is_ascii_digit()) {
+        result.extend(from_kuten(&code[0..2], &code[2..4]));
+    }
+    result.extend(from_code_point(&code));
+    result
+}
+
+fn from_code_point(hex: &str) -> Option<String> {
+    if hex.is_empty() || hex.len() > 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let ch = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+    // 制御文字を入力したいことはないはず。
+    if ch.is_control() {
+        return None;
+    }
+    Some(ch.to_string())
+}
+
+fn from_kuten(ku: &str, ten: &str) -> Option<String> {
+    if [ku, ten]
+        .iter()
+        .any(|it| it.is_empty() || it.len() > 2 || !it.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return None;
+    }
+    let ku: u8 = ku.parse().ok()?;
+    let ten: u8 = ten.parse().ok()?;
+    if !(1..=94).contains(&ku) || !(1..=94).contains(&ten) {
+        return None;
+    }
+
+    // EUC-JP では、区点に 0xA0 を足したものが JIS X 0208 の文字のバイト列になる。
+    let bytes = [ku + 0xA0, ten + 0xA0];
+    EUC_JP
+        .decode_without_bom_handling_and_without_replacement(&bytes)
+        .map(|it| it.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_point() {
+        assert_eq!(lookup_code("u+2603"), vec!["☃"]);
+        assert_eq!(lookup_code("U+1F600"), vec!["😀"]);
+        assert_eq!(lookup_code("u+30a2"), vec!["ア"]);
+        // サロゲートや制御文字は入力できない
+        assert!(lookup_code("u+d800").is_empty());
+        assert!(lookup_code("u+0007").is_empty());
+        assert!(lookup_code("u+zzzz").is_empty());
+    }
+
+    #[test]
+    fn test_kuten() {
+        assert_eq!(lookup_code("16-01"), vec!["亜"]);
+        assert_eq!(lookup_code("4-2"), vec!["あ"]);
+        assert_eq!(lookup_code("01-02"), vec!["、"]);
+        // 割り当てられていない区点
+        assert!(lookup_code("95-01").is_empty());
+        assert!(lookup_code("16-00").is_empty());
+        // 区点コードとしても、コードポイントとしても読める
+        assert_eq!(lookup_code("1601"), vec!["亜", "\u{1601}"]);
+        assert_eq!(lookup_code("30a2"), vec!["ア"]);
+    }
+}
@@ -7,21 +7,32 @@ dicts:
  */
 use std::fmt::Display;
 use std::fmt::Formatter;
-use std::fs::File;
-use std::io::{BufReader, Write};
-use std::path::PathBuf;
+use std::fs;
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
 
 use DictEncoding::Utf8;
 
+use crate::atomic_file::write_file_with_backup;
 use crate::config::DictUsage::{Normal, SingleTerm};
-use crate::resource::detect_resource_path;
+use crate::config_migration::migrate;
+use crate::resource::{config_file_path, detect_resource_path};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Default)]
+/// 設定ファイルのフォーマットのバージョン。
+/// 互換性のない変更をしたときは、上げたうえで config_migration に移行処理を書くこと。
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone)]
 pub struct Config {
+    /// 設定ファイルのフォーマットのバージョン。
+    /// 古い設定ファイルにはないので、0 として扱う。
+    #[serde(default)]
+    pub version: u32,
+
     /// ローマ字かな変換テーブルの指定
     /// "default", "kana", etc.
     #[serde(default = "default_romkan")]
@@ -43,51 +54,183 @@ fn default_keymap() -> String {
     detect_resource_path("keymap", "default").unwrap()
 }
 
+/// 設定ファイルの問題点。ユーザーに知らせるためのもの。
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConfigIssue {
+    /// 設定ファイルとして読めない。
+    ParseError(String),
+    /// このバージョンの akaza が知らない、新しい形式の設定ファイル。
+    NewerVersion(u32),
+    /// 知らないキー。typo に気づけるようにするため。"engine.dicts.0.foo" のような形式。
+    UnknownKey(String),
+    /// 辞書ファイルが見つからない。
+    MissingDictFile(String),
+    /// 辞書のエンコーディングの指定がおかしい。
+    BadEncoding { path: String, encoding: String },
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigIssue::ParseError(err) => write!(f, "設定ファイルを読めません: {}", err),
+            ConfigIssue::NewerVersion(version) => write!(
+                f,
+                "設定ファイルのバージョン({})に対応していません(対応: {})",
+                version, CONFIG_VERSION
+            ),
+            ConfigIssue::UnknownKey(key) => write!(f, "不明な設定項目: {}", key),
+            ConfigIssue::MissingDictFile(path) => {
+                write!(f, "辞書ファイルが見つかりません: {}", path)
+            }
+            ConfigIssue::BadEncoding { path, encoding } => write!(
+                f,
+                "辞書のエンコーディングが正しくありません: {} ({})",
+                path, encoding
+            ),
+        }
+    }
+}
+
 impl Config {
     pub fn load_from_file(path: &str) -> Result<Self> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let config: Config = serde_yaml::from_reader(reader)?;
+        let (config, issues) = Self::load_from_file_with_issues(path)?;
+        for issue in issues {
+            warn!("{}: {}", path, issue);
+        }
         Ok(config)
     }
 
+    /// 設定ファイルを読み込む。
+    /// 古い形式であれば現在の形式に移行し、見つかった問題点もあわせて返す。
+    /// 読み込めなかった場合はエラーになる。
+    pub fn load_from_file_with_issues(path: &str) -> Result<(Self, Vec<ConfigIssue>)> {
+        let file = fs::File::open(path).with_context(|| path.to_string())?;
+        let mut value: serde_yaml::Value = serde_yaml::from_reader(BufReader::new(file))?;
+
+        let mut issues = Vec::new();
+        let version = value.get("version").and_then(|it| it.as_u64()).unwrap_or(0) as u32;
+        if version > CONFIG_VERSION {
+            issues.push(ConfigIssue::NewerVersion(version));
+        }
+        migrate(&mut value, &mut issues);
+
+        let config: Config = serde_ignored::deserialize(value, |path| {
+            issues.push(ConfigIssue::UnknownKey(path.to_string()))
+        })?;
+        issues.extend(config.validate());
+        Ok((config, issues))
+    }
+
+    /// 設定の内容を検証する。
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        for dict in &self.engine.dicts {
+            if dict.usage == DictUsage::Disabled {
+                continue;
+            }
+            match read_prefix(&dict.path) {
+                Ok(bytes) => {
+                    // EUC-JP の辞書を UTF-8 として指定していると、変換候補が化けてしまう。
+                    // 先頭の途中で切れた文字はエラーとしない。
+                    if dict.encoding == Utf8
+                        && matches!(std::str::from_utf8(&bytes), Err(err) if err.error_len().is_some())
+                    {
+                        issues.push(ConfigIssue::BadEncoding {
+                            path: dict.path.clone(),
+                            encoding: dict.encoding.to_string(),
+                        });
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::NotFound => {
+                    issues.push(ConfigIssue::MissingDictFile(dict.path.clone()));
+                }
+                Err(err) => {
+                    warn!("Cannot read {}: {}", dict.path, err);
+                }
+            }
+        }
+        issues
+    }
+
     pub fn file_name() -> Result<PathBuf> {
-        let basedir = xdg::BaseDirectories::with_prefix("akaza")?;
-        Ok(basedir.get_config_file("config.yml"))
+        config_file_path("config.yml")
     }
 
+    /// 設定ファイルに保存する。
+    /// 書きかけのファイルを ibus-akaza が読んでしまわないように、一時ファイルに書いてから置き換える。
     pub fn save(&self) -> Result<()> {
         let file_name = Self::file_name()?;
-        let yml = serde_yaml::to_string(self)?;
-        info!("Write to file: {}", file_name.to_str().unwrap());
-        let mut fp = File::create(file_name)?;
-        fp.write_all(yml.as_bytes())?;
-        Ok(())
+        self.save_to_file(&file_name.to_string_lossy())
+    }
+
+    pub fn save_to_file(&self, path: &str) -> Result<()> {
+        let config = Config {
+            version: CONFIG_VERSION,
+            ..self.clone()
+        };
+        let yml = serde_yaml::to_string(&config)?;
+        info!("Write to file: {}", path);
+        write_file_with_backup(path, |w| Ok(w.write_all(yml.as_bytes())?))
     }
 
     pub fn load() -> Result<Self> {
+        let (config, issues) = Self::load_with_issues()?;
+        for issue in issues {
+            warn!("{}", issue);
+        }
+        Ok(config)
+    }
+
+    /// 設定ファイルを読み込む。
+    /// ファイルがなければデフォルトの設定を使う。読み込めなかった場合も、デフォルトの設定を使うが、
+    /// その理由を問題点として返すので、ユーザーに知らせること。
+    pub fn load_with_issues() -> Result<(Self, Vec<ConfigIssue>)> {
         let configfile = Self::file_name()?;
-        let config = match Config::load_from_file(configfile.to_str().unwrap()) {
-            Ok(config) => config,
+        if !Path::new(&configfile).exists() {
+            info!(
+                "Configuration file does not exist: {}",
+                configfile.to_string_lossy()
+            );
+            return Ok((Config::default(), Vec::new()));
+        }
+
+        match Config::load_from_file_with_issues(configfile.to_str().unwrap()) {
+            Ok((config, issues)) => {
+                info!(
+                    "Loaded config file: {}, {:?}",
+                    configfile.to_string_lossy(),
+                    config
+                );
+                Ok((config, issues))
+            }
             Err(err) => {
                 warn!(
                     "Cannot load configuration file: {} {}",
                     configfile.to_string_lossy(),
                     err
                 );
-                return Ok(Config::default());
+                Ok((
+                    Config::default(),
+                    vec![ConfigIssue::ParseError(format!("{:#}", err))],
+                ))
             }
-        };
-        info!(
-            "Loaded config file: {}, {:?}",
-            configfile.to_string_lossy(),
-            config
-        );
-        Ok(config)
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone)]
+/// エンコーディングの検証のために読む、辞書の先頭のバイト数。
+/// 大きな辞書を起動のたびに全部読まなくていいように、先頭だけを見る。
+const ENCODING_CHECK_BYTES: u64 = 64 * 1024;
+
+fn read_prefix(path: &str) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    fs::File::open(path)?
+        .take(ENCODING_CHECK_BYTES)
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct EngineConfig {
     pub dicts: Vec<DictConfig>,
 
@@ -99,16 +242,129 @@ pub struct EngineConfig {
     /// "default", etc.
     #[serde(default = "default_model")]
     pub model: String,
+
+    /// モデルや辞書のキャッシュをメモリマップして読み込むかどうか。
+    /// 起動が速くなり、複数のプロセスでメモリを共有できる。
+    #[serde(default = "default_mmap")]
+    pub mmap: bool,
+
+    /// 学習結果の半減期(日)。
+    /// 最近選んだ単語が優先されるように、ユーザーの統計を時間経過で減衰させる。0 以下なら減衰させない。
+    #[serde(default = "default_learning_half_life_days")]
+    pub learning_half_life_days: f32,
+
+    /// 辞書の候補に加えて使う CandidateProvider。候補はこの順に並ぶ。
+    #[serde(default = "default_providers")]
+    pub providers: Vec<CandidateProviderType>,
+
+    /// 「きょう」「いま」などから作る日付・時刻の候補の書式
+    #[serde(default)]
+    pub date_time: DateTimeConfig,
+}
+
+/// 設定ファイルで省略したときと同じ値にする。
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            dicts: Vec::new(),
+            dict_cache: default_dict_cache(),
+            model: default_model(),
+            mmap: default_mmap(),
+            learning_half_life_days: default_learning_half_life_days(),
+            providers: default_providers(),
+            date_time: DateTimeConfig::default(),
+        }
+    }
 }
 
+pub const DEFAULT_LEARNING_HALF_LIFE_DAYS: f32 = 90_f32;
+
 fn default_model() -> String {
-    detect_resource_path("model", "default").unwrap()
+    // wasm のように XDG のディレクトリがない環境でもデフォルトの設定は作れるように、
+    // 見つからなければ空にしておく。その場合、エンジンを作るときにモデルが読めずにエラーになる。
+    detect_resource_path("model", "default").unwrap_or_else(|err| {
+        warn!("Cannot find the default model: {}", err);
+        String::new()
+    })
 }
 
 fn default_dict_cache() -> bool {
     true
 }
 
+fn default_mmap() -> bool {
+    true
+}
+
+fn default_learning_half_life_days() -> f32 {
+    DEFAULT_LEARNING_HALF_LIFE_DAYS
+}
+
+/// 辞書以外から候補を作る CandidateProvider の種類
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub enum CandidateProviderType {
+    /// 「123」「にじゅうさん」などから、漢数字や全角数字の候補を作る。
+    Number,
+    /// 「きょう」「いま」などから、日付や時刻の候補を作る。
+    DateTime,
+    /// 「12*3+4=」のような末尾が = の式から、計算結果の候補を作る。
+    Calculator,
+}
+
+pub const DEFAULT_PROVIDERS: [CandidateProviderType; 3] = [
+    CandidateProviderType::Number,
+    CandidateProviderType::DateTime,
+    CandidateProviderType::Calculator,
+];
+
+fn default_providers() -> Vec<CandidateProviderType> {
+    DEFAULT_PROVIDERS.to_vec()
+}
+
+/// 日付・時刻の候補の書式。候補はこの順に並ぶ。
+/// strftime 形式の書式に加えて、{wareki}(令和8 など)と {youbi}(日 など)が使える。
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct DateTimeConfig {
+    /// 「きょう」「あした」などの書式
+    #[serde(default = "default_date_formats")]
+    pub date_formats: Vec<String>,
+
+    /// 「いま」の書式
+    #[serde(default = "default_time_formats")]
+    pub time_formats: Vec<String>,
+
+    /// 「ことし」「らいねん」などの書式
+    #[serde(default = "default_year_formats")]
+    pub year_formats: Vec<String>,
+}
+
+impl Default for DateTimeConfig {
+    fn default() -> Self {
+        DateTimeConfig {
+            date_formats: default_date_formats(),
+            time_formats: default_time_formats(),
+            year_formats: default_year_formats(),
+        }
+    }
+}
+
+fn default_date_formats() -> Vec<String> {
+    vec![
+        "%Y-%m-%d".to_string(),
+        "%Y年%-m月%-d日".to_string(),
+        "{wareki}年%-m月%-d日".to_string(),
+        "%-m/%-d({youbi})".to_string(),
+    ]
+}
+
+fn default_time_formats() -> Vec<String> {
+    vec!["%H:%M".to_string(), "%-H時%-M分".to_string()]
+}
+
+fn default_year_formats() -> Vec<String> {
+    vec!["%Y年".to_string(), "{wareki}年".to_string()]
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone)]
 pub struct DictConfig {
     pub path: String,
@@ -194,6 +450,8 @@ impl DictType {
 pub enum DictUsage {
     Normal,
     SingleTerm,
+    /// 絵文字・記号の辞書。変換には使わずに、文節の候補の最後に追加する。
+    Emoji,
     Disabled,
 }
 
@@ -208,6 +466,7 @@ impl DictUsage {
         match s {
             "Normal" => Ok(Normal),
             "SingleTerm" => Ok(SingleTerm),
+            "Emoji" => Ok(DictUsage::Emoji),
             "Disabled" => Ok(DictUsage::Disabled),
             _ => bail!("Unknown name: {:?}", s),
         }
@@ -217,6 +476,7 @@ impl DictUsage {
         match self {
             Normal => "Normal",
             SingleTerm => "SingleTerm",
+            DictUsage::Emoji => "Emoji",
             DictUsage::Disabled => "Disabled",
         }
     }
@@ -225,7 +485,140 @@ impl DictUsage {
         match self {
             Normal => "通常辞書",
             SingleTerm => "単項",
+            DictUsage::Emoji => "絵文字・記号",
             DictUsage::Disabled => "無効",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dict_path = tmpdir.path().join("SKK-JISYO.test");
+        fs::write(&dict_path, "あかざ /藜/\n")?;
+        let path = tmpdir.path().join("config.yml");
+        let path = path.to_str().unwrap();
+
+        let config = Config {
+            version: 0,
+            romkan: "/usr/share/akaza/romkan/default.yml".to_string(),
+            keymap: "/usr/share/akaza/keymap/default.yml".to_string(),
+            engine: EngineConfig {
+                dicts: vec![
+                    DictConfig {
+                        path: dict_path.to_string_lossy().to_string(),
+                        encoding: Utf8,
+                        dict_type: DictType::SKK,
+                        usage: Normal,
+                    },
+                    DictConfig {
+                        path: "/nonexistent/SKK-JISYO.L".to_string(),
+                        encoding: DictEncoding::EucJp,
+                        dict_type: DictType::SKK,
+                        usage: Normal,
+                    },
+                ],
+                dict_cache: true,
+                model: "/usr/share/akaza/model/default".to_string(),
+                mmap: true,
+                learning_half_life_days: DEFAULT_LEARNING_HALF_LIFE_DAYS,
+                providers: DEFAULT_PROVIDERS.to_vec(),
+                date_time: DateTimeConfig::default(),
+            },
+        };
+        config.save_to_file(path)?;
+
+        let (got, issues) = Config::load_from_file_with_issues(path)?;
+        // 保存時には最新のバージョンになる
+        assert_eq!(
+            got,
+            Config {
+                version: CONFIG_VERSION,
+                ..config
+            }
+        );
+        assert_eq!(
+            issues,
+            vec![ConfigIssue::MissingDictFile(
+                "/nonexistent/SKK-JISYO.L".to_string()
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_config_default() -> Result<()> {
+        // 省略できる項目を全部省略したときと同じになる。
+        let config: EngineConfig = serde_yaml::from_str("dicts: []")?;
+        assert_eq!(config, EngineConfig::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_encoding() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dict_path = tmpdir.path().join("SKK-JISYO.euc");
+        // "あ" の EUC-JP 表現
+        fs::write(&dict_path, b"\xa4\xa2 /\xa4\xa2/\n")?;
+        let dict_path = dict_path.to_string_lossy().to_string();
+
+        let config = Config {
+            engine: EngineConfig {
+                dicts: vec![DictConfig {
+                    path: dict_path.clone(),
+                    encoding: Utf8,
+                    dict_type: DictType::SKK,
+                    usage: Normal,
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            vec![ConfigIssue::BadEncoding {
+                path: dict_path,
+                encoding: "UTF-8".to_string(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_keys() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let path = tmpdir.path().join("config.yml");
+        fs::write(
+            &path,
+            r#"
+version: 1
+keymap: /usr/share/akaza/keymap/default.yml
+romkn: default
+engine:
+  mmap: true
+  dict: []
+  dicts:
+    - path: /nonexistent/SKK-JISYO.okinawa
+      encoding: EucJp
+      dict_type: SKK
+      usage: Disabled
+      enabled: true
+"#,
+        )?;
+
+        let (_, issues) = Config::load_from_file_with_issues(&path.to_string_lossy())?;
+        assert_eq!(
+            issues,
+            vec![
+                ConfigIssue::UnknownKey("romkn".to_string()),
+                ConfigIssue::UnknownKey("engine.dict".to_string()),
+                ConfigIssue::UnknownKey("engine.dicts.0.enabled".to_string()),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+use log::info;
+use serde_yaml::{Mapping, Value};
+
+use crate::config::{ConfigIssue, EngineConfig};
+use crate::resource::detect_resource_path;
+
+/// 古い形式の設定を、現在の形式に書き換える。
+/// 手で編集された設定ファイルの表記ゆれもあわせて直す。書き換えられなかったものは issues に追加する。
+pub(crate) fn migrate(value: &mut Value, issues: &mut Vec<ConfigIssue>) {
+    if value.is_null() {
+        *value = Value::Mapping(Mapping::new());
+    }
+    let Some(config) = value.as_mapping_mut() else {
+        return;
+    };
+
+    let version = config
+        .get("version")
+        .and_then(|it| it.as_u64())
+        .unwrap_or(0);
+    if version < 1 {
+        info!("Migrating the configuration from version {}", version);
+        migrate_v0_to_v1(config);
+    }
+
+    normalize_dicts(config, issues);
+}
+
+/// version 0(version フィールドがない)の設定を version 1 にする。
+///
+/// - dicts などのエンジンの設定がトップレベルに書かれていたら engine の下に移す。
+/// - keymap, romkan, model に名前("default" など)が書かれていたらパスにする。
+fn migrate_v0_to_v1(config: &mut Mapping) {
+    let mut engine = match config.remove("engine") {
+        Some(Value::Mapping(engine)) => engine,
+        _ => Mapping::new(),
+    };
+    for key in engine_keys() {
+        if let Some(value) = config.remove(&key) {
+            if !engine.contains_key(&key) {
+                engine.insert(key, value);
+            }
+        }
+    }
+
+    for (key, base) in [("keymap", "keymap"), ("romkan", "romkan")] {
+        if let Some(Value::String(name)) = config.get_mut(key) {
+            if !name.contains('/') {
+                if let Ok(path) = detect_resource_path(base, &format!("{}.yml", name)) {
+                    *name = path;
+                }
+            }
+        }
+    }
+    if let Some(Value::String(name)) = engine.get_mut("model") {
+        if !name.contains('/') {
+            if let Ok(path) = detect_resource_path("model", name) {
+                *name = path;
+            }
+        }
+    }
+
+    if !engine.contains_key("dicts") {
+        engine.insert(Value::from("dicts"), Value::Sequence(Vec::new()));
+    }
+
+    config.insert(Value::from("engine"), Value::Mapping(engine));
+    config.insert(Value::from("version"), Value::from(1));
+}
+
+/// EngineConfig のキー。フィールドを足したときに、ここを直し忘れないように、シリアライズした結果から得る。
+fn engine_keys() -> Vec<Value> {
+    match serde_yaml::to_value(EngineConfig::default()) {
+        Ok(Value::Mapping(engine)) => engine.into_iter().map(|(key, _)| key).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 辞書のエンコーディングや種類の表記ゆれ("euc-jp", "skk" など)をそろえて、省略されていれば補う。
+fn normalize_dicts(config: &mut Mapping, issues: &mut Vec<ConfigIssue>) {
+    let Some(dicts) = config
+        .get_mut("engine")
+        .and_then(|it| it.get_mut("dicts"))
+        .and_then(|it| it.as_sequence_mut())
+    else {
+        return;
+    };
+    for dict in dicts {
+        if let Value::Mapping(dict) = dict {
+            normalize_dict(dict, issues);
+        }
+    }
+}
+
+fn normalize_dict(dict: &mut Mapping, issues: &mut Vec<ConfigIssue>) {
+    let path = dict
+        .get("path")
+        .and_then(|it| it.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let encoding = match dict.get("encoding").and_then(|it| it.as_str()) {
+        None => "Utf8",
+        Some(encoding) => match encoding
+            .to_ascii_lowercase()
+            .replace(['-', '_'], "")
+            .as_str()
+        {
+            "eucjp" => "EucJp",
+            "utf8" => "Utf8",
+            _ => {
+                issues.push(ConfigIssue::BadEncoding {
+                    path: path.clone(),
+                    encoding: encoding.to_string(),
+                });
+                "Utf8"
+            }
+        },
+    };
+    dict.insert(Value::from("encoding"), Value::from(encoding));
+
+    match dict.get("dict_type").and_then(|it| it.as_str()) {
+        Some(dict_type) if !dict_type.eq_ignore_ascii_case("skk") => {}
+        _ => {
+            dict.insert(Value::from("dict_type"), Value::from("SKK"));
+        }
+    }
+
+    if !dict.contains_key("usage") {
+        dict.insert(Value::from("usage"), Value::from("Normal"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_v0() {
+        let mut value: Value = serde_yaml::from_str(
+            r#"
+romkan: /usr/share/akaza/romkan/default.yml
+dicts:
+  - path: /usr/share/skk/SKK-JISYO.okinawa
+    encoding: euc-jp
+    dict_type: skk
+  - path: /usr/share/skk/SKK-JISYO.jinmei
+    encoding: shift_jis
+"#,
+        )
+        .unwrap();
+        let mut issues = Vec::new();
+        migrate(&mut value, &mut issues);
+
+        let expected: Value = serde_yaml::from_str(
+            r#"
+romkan: /usr/share/akaza/romkan/default.yml
+engine:
+  dicts:
+    - path: /usr/share/skk/SKK-JISYO.okinawa
+      encoding: EucJp
+      dict_type: SKK
+      usage: Normal
+    - path: /usr/share/skk/SKK-JISYO.jinmei
+      encoding: Utf8
+      dict_type: SKK
+      usage: Normal
+version: 1
+"#,
+        )
+        .unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(
+            issues,
+            vec![ConfigIssue::BadEncoding {
+                path: "/usr/share/skk/SKK-JISYO.jinmei".to_string(),
+                encoding: "shift_jis".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_migrate_current_version() {
+        let src = r#"
+version: 1
+engine:
+  dicts:
+    - path: /usr/share/skk/SKK-JISYO.okinawa
+      encoding: EucJp
+      dict_type: SKK
+      usage: Normal
+"#;
+        let mut value: Value = serde_yaml::from_str(src).unwrap();
+        let mut issues = Vec::new();
+        migrate(&mut value, &mut issues);
+        // 最新の形式なら何もしない
+        assert_eq!(value, serde_yaml::from_str::<Value>(src).unwrap());
+        assert!(issues.is_empty());
+    }
+}
@@ -8,9 +8,36 @@ const ALPHA: f32 = 0.00001;
 /// - `total_words`: コーパス中の単語の総出現回数, `C`
 /// - `unique_words`: 語彙数, `V`
 pub fn calc_cost(count: u32, total_words: u32, unique_words: u32) -> f32 {
+    calc_cost_f32(count as f32, total_words as f32, unique_words)
+}
+
+/// 出現回数が整数でない場合の [`calc_cost`]。
+/// 時間経過で減衰させた出現回数からコストを計算するのに使う。
+pub fn calc_cost_f32(count: f32, total_words: f32, unique_words: u32) -> f32 {
     -f32::log10(
-        ((count as f32) + ALPHA) // Alpha を足す。
+        (count + ALPHA) // Alpha を足す。
             / // -------
-            ((total_words as f32) + ALPHA + (unique_words as f32)),
+            (total_words + ALPHA + (unique_words as f32)),
     )
 }
+
+/// 半減期 `half_life` 秒で、出現回数を減衰させる。
+///
+/// - `count`: 出現回数
+/// - `elapsed`: 最後に使われてからの経過秒数
+pub fn decay_count(count: f32, elapsed: u64, half_life: f32) -> f32 {
+    count * 0.5_f32.powf(elapsed as f32 / half_life)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decay_count() {
+        assert_eq!(decay_count(4_f32, 0, 10_f32), 4_f32);
+        assert_eq!(decay_count(4_f32, 10, 10_f32), 2_f32);
+        assert_eq!(decay_count(4_f32, 20, 10_f32), 1_f32);
+        assert_eq!(calc_cost(3, 10, 5), calc_cost_f32(3_f32, 10_f32, 5));
+    }
+}
@@ -0,0 +1,230 @@
+//! 日付・時刻の変換候補。
+//!
+//! 「きょう」「あした」「いま」「ことし」のような読みから、2026-10-18 / 2026年10月18日 /
+//! 令和8年10月18日 / 10/18(日) / 14:05 のような候補を、変換した時点の日時で生成する。
+
+use std::fmt::Write;
+
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime};
+use log::warn;
+
+use crate::config::DateTimeConfig;
+use crate::graph::candidate_provider::CandidateProvider;
+use crate::graph::word_node::WordNode;
+
+/// 日付になる読みと、今日からの日数
+const DATE_YOMIS: [(&str, i64); 6] = [
+    ("おととい", -2),
+    ("きのう", -1),
+    ("きょう", 0),
+    ("あした", 1),
+    ("あす", 1),
+    ("あさって", 2),
+];
+
+/// 年になる読みと、今年からの年数
+const YEAR_YOMIS: [(&str, i32); 3] = [("きょねん", -1), ("ことし", 0), ("らいねん", 1)];
+
+/// 時刻になる読み
+const TIME_YOMIS: [&str; 1] = ["いま"];
+
+/// 元号と、その開始日
+const ERAS: [(&str, i32, u32, u32); 4] = [
+    ("令和", 2019, 5, 1),
+    ("平成", 1989, 1, 8),
+    ("昭和", 1926, 12, 25),
+    ("大正", 1912, 7, 30),
+];
+
+const YOUBI: [&str; 7] = ["日", "月", "火", "水", "木", "金", "土"];
+
+/// 読みに対応する日付・時刻の候補を、設定された書式の順に返す。
+/// 日付・時刻の読みでなければ、空を返す。
+pub fn date_time_candidates(
+    yomi: &str,
+    now: NaiveDateTime,
+    config: &DateTimeConfig,
+) -> Vec<String> {
+    if let Some((_, days)) = DATE_YOMIS.iter().find(|(it, _)| *it == yomi) {
+        let date = now + Duration::days(*days);
+        return format_all(date, &config.date_formats);
+    }
+    if let Some((_, years)) = YEAR_YOMIS.iter().find(|(it, _)| *it == yomi) {
+        // 和暦は年の途中で変わることがあるので、今日と同じ月日で求める。
+        // 2/29 の翌年のように同じ日付がなければ、前の日にする。
+        let year = now.year() + years;
+        let Some(date) = now
+            .date()
+            .with_year(year)
+            .or_else(|| NaiveDate::from_ymd_opt(year, now.month(), now.day() - 1))
+        else {
+            return Vec::new();
+        };
+        return format_all(date.and_time(now.time()), &config.year_formats);
+    }
+    if TIME_YOMIS.contains(&yomi) {
+        return format_all(now, &config.time_formats);
+    }
+    Vec::new()
+}
+
+fn format_all(date_time: NaiveDateTime, formats: &[String]) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for fmt in formats {
+        if let Some(surface) = format_date_time(date_time, fmt) {
+            if !surface.is_empty() && !result.contains(&surface) {
+                result.push(surface);
+            }
+        }
+    }
+    result
+}
+
+/// strftime 形式の書式に加えて、{wareki}(令和8 など)と {youbi}(日 など)を展開する。
+/// 書式が間違っていたら None を返す。
+fn format_date_time(date_time: NaiveDateTime, fmt: &str) -> Option<String> {
+    let fmt = fmt.replace("{wareki}", &wareki(date_time.date())).replace(
+        "{youbi}",
+        YOUBI[date_time.weekday().num_days_from_sunday() as usize],
+    );
+    let mut result = String::new();
+    if write!(result, "{}", date_time.format(&fmt)).is_err() {
+        warn!("Invalid date time format: {}", fmt);
+        return None;
+    }
+    Some(result)
+}
+
+/// 和暦の年を "令和8" のような形式で返す。元年は "令和元" になる。
+fn wareki(date: NaiveDate) -> String {
+    for (name, year, month, day) in ERAS {
+        if date >= NaiveDate::from_ymd_opt(year, month, day).unwrap() {
+            return match date.year() - year + 1 {
+                1 => format!("{}元", name),
+                n => format!("{}{}", name, n),
+            };
+        }
+    }
+    date.year().to_string()
+}
+
+/// 日付・時刻の候補を作る CandidateProvider。
+pub struct DateTimeProvider {
+    config: DateTimeConfig,
+}
+
+impl DateTimeProvider {
+    pub fn new(config: DateTimeConfig) -> DateTimeProvider {
+        DateTimeProvider { config }
+    }
+}
+
+impl CandidateProvider for DateTimeProvider {
+    fn provide(&self, _yomi: &str, segmented_yomi: &str, start_pos: i32) -> Vec<WordNode> {
+        date_time_candidates(segmented_yomi, Local::now().naive_local(), &self.config)
+            .iter()
+            .map(|surface| WordNode::new(start_pos, surface, segmented_yomi, None, true))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 18)
+            .unwrap()
+            .and_hms_opt(14, 5, 9)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_date_time_candidates() {
+        let config = DateTimeConfig::default();
+        assert_eq!(
+            date_time_candidates("きょう", now(), &config),
+            vec![
+                "2026-10-18",
+                "2026年10月18日",
+                "令和8年10月18日",
+                "10/18(日)",
+            ]
+        );
+        assert_eq!(
+            date_time_candidates("あした", now(), &config),
+            vec![
+                "2026-10-19",
+                "2026年10月19日",
+                "令和8年10月19日",
+                "10/19(月)",
+            ]
+        );
+        assert_eq!(
+            date_time_candidates("いま", now(), &config),
+            vec!["14:05", "14時5分"]
+        );
+        assert_eq!(
+            date_time_candidates("ことし", now(), &config),
+            vec!["2026年", "令和8年"]
+        );
+        assert!(date_time_candidates("すし", now(), &config).is_empty());
+    }
+
+    #[test]
+    fn test_year_wareki() {
+        let config = DateTimeConfig::default();
+        let at = |y, m, d| {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
+        // 改元の前後で、年の初めではなく今日の日付の元号にする。
+        assert_eq!(
+            date_time_candidates("ことし", at(2019, 4, 30), &config),
+            vec!["2019年", "平成31年"]
+        );
+        assert_eq!(
+            date_time_candidates("ことし", at(2019, 6, 1), &config),
+            vec!["2019年", "令和元年"]
+        );
+        assert_eq!(
+            date_time_candidates("らいねん", at(2024, 2, 29), &config),
+            vec!["2025年", "令和7年"]
+        );
+    }
+
+    #[test]
+    fn test_custom_format() {
+        let config = DateTimeConfig {
+            date_formats: vec![
+                "%Y/%m/%d".to_string(),
+                // 間違った書式は無視する
+                "%Q".to_string(),
+                "%Y/%m/%d".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            date_time_candidates("きのう", now(), &config),
+            vec!["2026/10/17"]
+        );
+    }
+
+    #[test]
+    fn test_wareki() {
+        assert_eq!(
+            wareki(NaiveDate::from_ymd_opt(2019, 4, 30).unwrap()),
+            "平成31"
+        );
+        assert_eq!(
+            wareki(NaiveDate::from_ymd_opt(2019, 5, 1).unwrap()),
+            "令和元"
+        );
+        assert_eq!(
+            wareki(NaiveDate::from_ymd_opt(2026, 1, 1).unwrap()),
+            "令和8"
+        );
+    }
+}
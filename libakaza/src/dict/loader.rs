@@ -3,7 +3,6 @@ use std::fs::File;
 use std::path::Path;
 use std::time::SystemTime;
 
-use anyhow::Context;
 use anyhow::Result;
 use encoding_rs::{EUC_JP, UTF_8};
 use log::{error, info};
@@ -12,6 +11,7 @@ use crate::config::{DictConfig, DictEncoding, DictType};
 use crate::dict::merge_dict::merge_dict;
 use crate::dict::skk::read::read_skkdict;
 use crate::kana_kanji::marisa_kana_kanji_dict::MarisaKanaKanjiDict;
+use crate::resource::cache_file_path;
 
 fn try_get_mtime(path: &str) -> Result<u128> {
     let file = File::open(path)?;
@@ -23,9 +23,11 @@ fn try_get_mtime(path: &str) -> Result<u128> {
 
 /// - `dict_configs`: 辞書の読み込み設定
 /// - `cache_name`: キャッシュファイル名。 `~/.cache/akaza/kana_kanji_cache.marisa` とかにでる。
+/// - `mmap`: キャッシュファイルをメモリマップして読み込むかどうか
 pub fn load_dicts_with_cache(
     dict_configs: &Vec<DictConfig>,
     cache_name: &str,
+    mmap: bool,
 ) -> Result<MarisaKanaKanjiDict> {
     // さて、ここで、全部の依存先ファイルの mtime の max とキャッシュファイルの mtime の max を比較する
     // 更新が必要だったら、更新する。
@@ -42,13 +44,7 @@ pub fn load_dicts_with_cache(
         .unwrap_or(0_u128);
 
     // cache file のパスを得る
-    let base_dirs = xdg::BaseDirectories::with_prefix("akaza")
-        .with_context(|| "xdg directory with 'akaza' prefix")?;
-    base_dirs.create_cache_directory("")?;
-    let cache_path = base_dirs
-        .get_cache_file(cache_name)
-        .to_string_lossy()
-        .to_string();
+    let cache_path = cache_file_path(cache_name)?.to_string_lossy().to_string();
     let cache_mtime = try_get_mtime(&cache_path).unwrap_or(0_u128);
 
     // 現在の Config をシリアライズする。
@@ -56,7 +52,12 @@ pub fn load_dicts_with_cache(
     info!("SERIALIZED: {:?}", config_serialized);
 
     if cache_mtime >= max_dict_mtime {
-        match MarisaKanaKanjiDict::load(cache_path.as_str()) {
+        let dict = if mmap {
+            MarisaKanaKanjiDict::mmap(cache_path.as_str())
+        } else {
+            MarisaKanaKanjiDict::load(cache_path.as_str())
+        };
+        match dict {
             Ok(dict) => {
                 let dict_serialized = dict.cache_serialized();
                 if dict_serialized == config_serialized {
@@ -170,6 +171,7 @@ mod tests {
                 usage: DictUsage::Normal,
             }],
             "test",
+            true,
         )?;
         assert_eq!(loaded.yomis(), vec!["たこ"]);
 
@@ -196,6 +198,7 @@ mod tests {
                 usage: DictUsage::Normal,
             }],
             "test",
+            true,
         )?;
         assert_eq!(
             loaded
@@ -214,6 +217,7 @@ mod tests {
                 usage: DictUsage::Normal,
             }],
             "test",
+            true,
         )?;
         assert_eq!(
             loaded
@@ -272,6 +276,7 @@ mod tests {
                 usage: DictUsage::Normal,
             }],
             "test",
+            true,
         )?;
         assert_eq!(loaded.yomis(), vec!["たこ"]);
 
@@ -292,6 +297,7 @@ mod tests {
                 },
             ],
             "test",
+            true,
         )?;
         assert_eq!(
             loaded
@@ -11,19 +11,25 @@ pub fn write_skk_dict(
     dicts: Vec<HashMap<String, Vec<String>>>,
 ) -> anyhow::Result<()> {
     info!("Writing {}", ofname);
+    let mut wfp = File::create(ofname)?;
+    write_skk_dict_to(&mut wfp, dicts)
+}
+
+/// SKK 辞書の形式で `wfp` に書き出す。
+pub fn write_skk_dict_to<W: Write>(
+    wfp: &mut W,
+    dicts: Vec<HashMap<String, Vec<String>>>,
+) -> anyhow::Result<()> {
     let merged_dict = merge_dict(dicts);
-    {
-        let mut wfp = File::create(ofname)?;
-        wfp.write_all(";; okuri-ari entries.\n".as_bytes())?;
-        wfp.write_all(";; okuri-nasi entries.\n".as_bytes())?;
-        let mut keys = merged_dict.keys().collect::<Vec<_>>();
-        keys.sort();
-        for yomi in keys {
-            let kanjis = merged_dict.get(yomi).unwrap();
-            assert!(!yomi.is_empty(), "yomi must not be empty: {:?}", kanjis);
-            let kanjis = kanjis.join("/");
-            wfp.write_fmt(format_args!("{} /{}/\n", yomi, kanjis))?;
-        }
+    wfp.write_all(";; okuri-ari entries.\n".as_bytes())?;
+    wfp.write_all(";; okuri-nasi entries.\n".as_bytes())?;
+    let mut keys = merged_dict.keys().collect::<Vec<_>>();
+    keys.sort();
+    for yomi in keys {
+        let kanjis = merged_dict.get(yomi).unwrap();
+        assert!(!yomi.is_empty(), "yomi must not be empty: {:?}", kanjis);
+        let kanjis = kanjis.join("/");
+        wfp.write_fmt(format_args!("{} /{}/\n", yomi, kanjis))?;
     }
     Ok(())
 }
@@ -1,13 +1,19 @@
 use std::ops::Range;
-use std::rc::Rc;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
 
-use crate::config::{DictConfig, DictEncoding, DictType, DictUsage, EngineConfig};
+use crate::calculator::CalculatorProvider;
+use crate::config::{
+    CandidateProviderType, DictConfig, DictEncoding, DictType, DictUsage, EngineConfig,
+};
+use crate::date_time::DateTimeProvider;
 use crate::dict::loader::{load_dicts, load_dicts_with_cache};
+use crate::dict::skk::read::parse_skkdict;
 use crate::engine::base::HenkanEngine;
 use crate::graph::candidate::Candidate;
+use crate::graph::candidate_provider::CandidateProvider;
 use crate::graph::graph_builder::GraphBuilder;
 use crate::graph::graph_resolver::GraphResolver;
 use crate::graph::lattice_graph::LatticeGraph;
@@ -18,12 +24,19 @@ use crate::kana_trie::cedarwood_kana_trie::CedarwoodKanaTrie;
 use crate::lm::base::{SystemBigramLM, SystemUnigramLM};
 use crate::lm::system_bigram::MarisaSystemBigramLM;
 use crate::lm::system_unigram_lm::MarisaSystemUnigramLM;
+use crate::number::NumberProvider;
+use crate::resource::detect_resource_path;
 use crate::user_side_data::user_data::UserData;
 
+/// 同梱している絵文字・記号の辞書のファイル名
+const EMOJI_DICT_NAME: &str = "SKK-JISYO.emoji";
+
 /// バイグラムのビタビベースかな漢字変換エンジンです。
 /// 単語バイグラムを採用しています。
 pub struct BigramWordViterbiEngine<U: SystemUnigramLM, B: SystemBigramLM, KD: KanaKanjiDict> {
     graph_builder: GraphBuilder<U, B, KD>,
+    /// 絵文字・記号の辞書。Viterbi の経路には入れずに、文節の候補の最後に追加する。
+    emoji_dict: KD,
     pub segmenter: Segmenter,
     pub graph_resolver: GraphResolver,
     pub user_data: Arc<Mutex<UserData>>,
@@ -42,7 +55,29 @@ impl<U: SystemUnigramLM, B: SystemBigramLM, KD: KanaKanjiDict> HenkanEngine
         force_ranges: Option<&[Range<usize>]>,
     ) -> Result<Vec<Vec<Candidate>>> {
         let lattice = self.to_lattice(yomi, force_ranges)?;
-        self.resolve(&lattice)
+        let mut clauses = self.resolve(&lattice)?;
+        append_emoji_candidates(&mut clauses, &self.emoji_dict);
+        Ok(clauses)
+    }
+}
+
+/// 絵文字・記号の候補を、それぞれの文節の候補の最後に追加する。
+/// 漢字の候補と競合しないように、コストは文節の中で最大のものにあわせる。
+fn append_emoji_candidates<KD: KanaKanjiDict>(clauses: &mut [Vec<Candidate>], emoji_dict: &KD) {
+    for clause in clauses {
+        let Some(last) = clause.last() else {
+            continue;
+        };
+        let Some(surfaces) = emoji_dict.get(&last.yomi) else {
+            continue;
+        };
+        let (yomi, cost) = (last.yomi.clone(), last.cost);
+        for surface in surfaces {
+            if clause.iter().any(|it| it.surface == surface) {
+                continue;
+            }
+            clause.push(Candidate::new(&yomi, &surface, cost));
+        }
     }
 }
 
@@ -87,17 +122,20 @@ impl BigramWordViterbiEngineBuilder {
     > {
         let model_name = self.config.model.clone();
 
-        let system_unigram_lm =
-            MarisaSystemUnigramLM::load(Self::try_load(&model_name, "unigram.model")?.as_str())?;
-        let system_bigram_lm =
-            MarisaSystemBigramLM::load(Self::try_load(&model_name, "bigram.model")?.as_str())?;
-        let system_dict = Self::try_load(&model_name, "SKK-JISYO.akaza")?;
-
-        let user_data = if let Some(d) = &self.user_data {
-            d.clone()
+        let unigram_path = Self::try_load(&model_name, "unigram.model")?;
+        let bigram_path = Self::try_load(&model_name, "bigram.model")?;
+        let (system_unigram_lm, system_bigram_lm) = if self.config.mmap {
+            (
+                MarisaSystemUnigramLM::mmap(unigram_path.as_str())?,
+                MarisaSystemBigramLM::mmap(bigram_path.as_str())?,
+            )
         } else {
-            Arc::new(Mutex::new(UserData::default()))
+            (
+                MarisaSystemUnigramLM::load(unigram_path.as_str())?,
+                MarisaSystemBigramLM::load(bigram_path.as_str())?,
+            )
         };
+        let system_dict = Self::try_load(&model_name, "SKK-JISYO.akaza")?;
 
         let dict = {
             let mut dicts = self
@@ -115,7 +153,7 @@ impl BigramWordViterbiEngineBuilder {
             });
 
             if self.config.dict_cache {
-                load_dicts_with_cache(&dicts, "kana_kanji_cache.marisa")?
+                load_dicts_with_cache(&dicts, "kana_kanji_cache.marisa", self.config.mmap)?
             } else {
                 let dict = load_dicts(&dicts)?;
                 MarisaKanaKanjiDict::build(dict)?
@@ -131,13 +169,100 @@ impl BigramWordViterbiEngineBuilder {
                 .cloned()
                 .collect::<Vec<_>>();
             if self.config.dict_cache {
-                load_dicts_with_cache(&dicts, "single_term_cache.marisa")?
+                load_dicts_with_cache(&dicts, "single_term_cache.marisa", self.config.mmap)?
             } else {
                 let dict = load_dicts(&dicts)?;
                 MarisaKanaKanjiDict::build(dict)?
             }
         };
 
+        let emoji = {
+            let mut dicts = self
+                .config
+                .dicts
+                .iter()
+                .filter(|it| it.usage == DictUsage::Emoji)
+                .cloned()
+                .collect::<Vec<_>>();
+            // 同梱の絵文字辞書。設定に書かれていれば、そちらの usage に従う。
+            if let Ok(path) = detect_resource_path("dict", EMOJI_DICT_NAME) {
+                if Path::new(&path).exists() && !self.config.dicts.iter().any(|it| it.path == path)
+                {
+                    dicts.push(DictConfig {
+                        path,
+                        dict_type: DictType::SKK,
+                        encoding: DictEncoding::Utf8,
+                        usage: DictUsage::Emoji,
+                    });
+                }
+            }
+            // 小さい辞書なので、キャッシュはしない。
+            MarisaKanaKanjiDict::build(load_dicts(&dicts)?)?
+        };
+
+        self.assemble(
+            system_unigram_lm,
+            system_bigram_lm,
+            dict,
+            single_term,
+            emoji,
+        )
+    }
+
+    /// ファイルシステムを経由せずに、メモリ上のモデルデータからエンジンを作成する。
+    /// wasm32 のように、ファイルシステムや XDG ディレクトリが利用できない環境向け。
+    /// config で指定された model や dicts は利用しない。
+    ///
+    /// - `unigram`, `bigram`: unigram.model, bigram.model の内容
+    /// - `system_dict`: SKK-JISYO.akaza の内容
+    pub fn build_from_bytes(
+        &self,
+        unigram: Vec<u8>,
+        bigram: Vec<u8>,
+        system_dict: &str,
+    ) -> Result<
+        BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM, MarisaKanaKanjiDict>,
+    > {
+        let system_unigram_lm = MarisaSystemUnigramLM::load_from_bytes(unigram)?;
+        let system_bigram_lm = MarisaSystemBigramLM::load_from_bytes(bigram)?;
+        let dict = MarisaKanaKanjiDict::build(parse_skkdict(system_dict)?)?;
+        let single_term = MarisaKanaKanjiDict::default();
+        let emoji = MarisaKanaKanjiDict::default();
+
+        self.assemble(
+            system_unigram_lm,
+            system_bigram_lm,
+            dict,
+            single_term,
+            emoji,
+        )
+    }
+
+    fn assemble(
+        &self,
+        system_unigram_lm: MarisaSystemUnigramLM,
+        system_bigram_lm: MarisaSystemBigramLM,
+        dict: MarisaKanaKanjiDict,
+        single_term: MarisaKanaKanjiDict,
+        emoji: MarisaKanaKanjiDict,
+    ) -> Result<
+        BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM, MarisaKanaKanjiDict>,
+    > {
+        let user_data = if let Some(d) = &self.user_data {
+            d.clone()
+        } else {
+            Arc::new(Mutex::new(UserData::default()))
+        };
+        {
+            let mut user_data = user_data.lock().unwrap();
+            user_data.set_learning_half_life_days(self.config.learning_half_life_days);
+            user_data.set_learn_number_forms(
+                self.config
+                    .providers
+                    .contains(&CandidateProviderType::Number),
+            );
+        }
+
         // 辞書を元に、トライを作成していく。
         let mut kana_trie = CedarwoodKanaTrie::default();
         for yomi in dict.yomis() {
@@ -148,13 +273,14 @@ impl BigramWordViterbiEngineBuilder {
             assert!(!yomi.is_empty());
             kana_trie.update(yomi.as_str());
         }
+        // 絵文字の候補は変換結果の文節に足すだけなので、絵文字辞書の読みは区切り方に影響させない。
 
-        let segmenter = Segmenter::new(vec![
+        let mut segmenter = Segmenter::new(vec![
             Arc::new(Mutex::new(kana_trie)),
             user_data.lock().unwrap().kana_trie.clone(),
         ]);
 
-        let graph_builder: GraphBuilder<
+        let mut graph_builder: GraphBuilder<
             MarisaSystemUnigramLM,
             MarisaSystemBigramLM,
             MarisaKanaKanjiDict,
@@ -162,21 +288,129 @@ impl BigramWordViterbiEngineBuilder {
             dict,
             single_term,
             user_data.clone(),
-            Rc::new(system_unigram_lm),
-            Rc::new(system_bigram_lm),
+            Arc::new(system_unigram_lm),
+            Arc::new(system_bigram_lm),
         );
+        for provider in &self.config.providers {
+            let provider = self.build_provider(provider, &user_data);
+            segmenter.add_provider(provider.clone());
+            graph_builder.add_provider(provider);
+        }
 
         let graph_resolver = GraphResolver::default();
 
         Ok(BigramWordViterbiEngine {
             graph_builder,
+            emoji_dict: emoji,
             segmenter,
             graph_resolver,
             user_data,
         })
     }
 
+    fn build_provider(
+        &self,
+        provider: &CandidateProviderType,
+        user_data: &Arc<Mutex<UserData>>,
+    ) -> Arc<dyn CandidateProvider> {
+        match provider {
+            CandidateProviderType::Number => Arc::new(NumberProvider::new(user_data.clone())),
+            CandidateProviderType::DateTime => {
+                Arc::new(DateTimeProvider::new(self.config.date_time.clone()))
+            }
+            CandidateProviderType::Calculator => Arc::new(CalculatorProvider),
+        }
+    }
+
     fn try_load(model_dir: &str, name: &str) -> Result<String> {
         Ok(model_dir.to_string() + "/" + name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::kana_kanji::hashmap_vec::HashmapVecKanaKanjiDict;
+    use crate::lm::system_bigram::MarisaSystemBigramLMBuilder;
+    use crate::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_append_emoji_candidates() {
+        let emoji_dict = HashmapVecKanaKanjiDict::new(HashMap::from([(
+            "ねこ".to_string(),
+            vec!["🐱".to_string(), "猫".to_string()],
+        )]));
+        let mut clauses = vec![
+            vec![
+                Candidate::new("ねこ", "猫", 10.0),
+                Candidate::new("ねこ", "ネコ", 12.0),
+            ],
+            vec![Candidate::new("が", "が", 5.0)],
+        ];
+        append_emoji_candidates(&mut clauses, &emoji_dict);
+        assert_eq!(
+            clauses,
+            vec![
+                vec![
+                    Candidate::new("ねこ", "猫", 10.0),
+                    Candidate::new("ねこ", "ネコ", 12.0),
+                    Candidate::new("ねこ", "🐱", 12.0),
+                ],
+                vec![Candidate::new("が", "が", 5.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_emoji_dict() -> Result<()> {
+        let dict = load_dicts(&vec![DictConfig {
+            path: detect_resource_path("dict", EMOJI_DICT_NAME)?,
+            dict_type: DictType::SKK,
+            encoding: DictEncoding::Utf8,
+            usage: DictUsage::Emoji,
+        }])?;
+        assert_eq!(dict.get("ねこ").unwrap()[0], "🐱");
+        assert_eq!(dict.get("やじるし").unwrap()[0], "→");
+        Ok(())
+    }
+
+    #[test]
+    fn test_emoji_dict_does_not_change_segmentation() -> Result<()> {
+        let segment = |emoji: HashMap<String, Vec<String>>| -> Result<_> {
+            let engine = BigramWordViterbiEngineBuilder::new(EngineConfig {
+                providers: Vec::new(),
+                ..Default::default()
+            })
+            .assemble(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+                MarisaKanaKanjiDict::build(HashMap::from([
+                    ("わたし".to_string(), vec!["私".to_string()]),
+                    ("の".to_string(), vec!["の".to_string()]),
+                    ("ねこ".to_string(), vec!["猫".to_string()]),
+                ]))?,
+                MarisaKanaKanjiDict::default(),
+                MarisaKanaKanjiDict::build(emoji)?,
+            )?;
+            Ok(engine.segmenter.build("わたしのねこ", None))
+        };
+
+        // 「わたしの」が一つの単語として区切られたりしない。
+        assert_eq!(
+            segment(HashMap::from([(
+                "わたしの".to_string(),
+                vec!["🙋".to_string()]
+            )]))?,
+            segment(HashMap::new())?
+        );
+        Ok(())
+    }
+}
@@ -10,6 +10,8 @@ pub struct Candidate {
     pub cost: f32,
     /// 複合語か? 複合語だったら、true になるので、その場合は学習時にユーザー辞書に登録する必要がある。
     pub compound_word: bool,
+    /// 日付や計算結果のように、変換するたびに作られる候補か? その場合は学習しない。
+    pub auto_generated: bool,
 }
 
 impl Eq for Candidate {}
@@ -39,6 +41,7 @@ impl Candidate {
             surface: surface.to_string(),
             cost,
             compound_word: false,
+            auto_generated: false,
         }
     }
 
@@ -0,0 +1,168 @@
+use std::sync::{Arc, Mutex};
+
+use kelp::{hira2kata, ConvOption};
+
+use crate::graph::word_node::WordNode;
+use crate::kana_kanji::base::KanaKanjiDict;
+use crate::lm::base::SystemUnigramLM;
+use crate::user_side_data::user_data::UserData;
+
+/// 読みから候補を作るもの。
+/// GraphBuilder に登録すると、分節された読みごとに呼ばれる。
+pub trait CandidateProvider: Send + Sync {
+    /// - `yomi`: 変換対象の読み全体
+    /// - `segmented_yomi`: 分節された読み
+    /// - `start_pos`: segmented_yomi の yomi 中での開始位置(バイト数)
+    ///
+    /// 言語モデルにある単語なら、WordNode の word_id_and_score にそのコストを入れて返す。
+    fn provide(&self, yomi: &str, segmented_yomi: &str, start_pos: i32) -> Vec<WordNode>;
+
+    /// yomi の先頭から、一つの単語として扱ってほしい部分の長さ(バイト数)を返す。
+    /// 辞書にない数字の並びのように、かなトライでは分節できない読みの候補を作るときに実装する。
+    /// Segmenter に登録すると、分節するときに呼ばれる。
+    fn segment_lens(&self, _yomi: &str) -> Vec<usize> {
+        Vec::new()
+    }
+}
+
+/// 表層のリストを、言語モデルのコストつきの WordNode にする。
+fn to_word_nodes<U: SystemUnigramLM>(
+    surfaces: &[String],
+    segmented_yomi: &str,
+    start_pos: i32,
+    system_unigram_lm: &U,
+) -> Vec<WordNode> {
+    surfaces
+        .iter()
+        .map(|surface| {
+            WordNode::new(
+                start_pos,
+                surface,
+                segmented_yomi,
+                system_unigram_lm.find((surface.to_string() + "/" + segmented_yomi).as_str()),
+                false,
+            )
+        })
+        .collect()
+}
+
+/// システム辞書にある候補。
+pub struct SystemDictProvider<KD: KanaKanjiDict, U: SystemUnigramLM> {
+    dict: KD,
+    system_unigram_lm: Arc<U>,
+}
+
+impl<KD: KanaKanjiDict, U: SystemUnigramLM> SystemDictProvider<KD, U> {
+    pub fn new(dict: KD, system_unigram_lm: Arc<U>) -> Self {
+        SystemDictProvider {
+            dict,
+            system_unigram_lm,
+        }
+    }
+}
+
+impl<KD, U> CandidateProvider for SystemDictProvider<KD, U>
+where
+    KD: KanaKanjiDict + Send + Sync,
+    U: SystemUnigramLM + Send + Sync,
+{
+    fn provide(&self, _yomi: &str, segmented_yomi: &str, start_pos: i32) -> Vec<WordNode> {
+        let Some(kanjis) = self.dict.get(segmented_yomi) else {
+            return Vec::new();
+        };
+        to_word_nodes(
+            &kanjis,
+            segmented_yomi,
+            start_pos,
+            self.system_unigram_lm.as_ref(),
+        )
+    }
+}
+
+/// 変換範囲が全体になっているときだけ使う、single term 辞書にある候補。
+pub struct SingleTermProvider<KD: KanaKanjiDict, U: SystemUnigramLM> {
+    dict: KD,
+    system_unigram_lm: Arc<U>,
+}
+
+impl<KD: KanaKanjiDict, U: SystemUnigramLM> SingleTermProvider<KD, U> {
+    pub fn new(dict: KD, system_unigram_lm: Arc<U>) -> Self {
+        SingleTermProvider {
+            dict,
+            system_unigram_lm,
+        }
+    }
+}
+
+impl<KD, U> CandidateProvider for SingleTermProvider<KD, U>
+where
+    KD: KanaKanjiDict + Send + Sync,
+    U: SystemUnigramLM + Send + Sync,
+{
+    fn provide(&self, yomi: &str, segmented_yomi: &str, start_pos: i32) -> Vec<WordNode> {
+        if segmented_yomi != yomi {
+            return Vec::new();
+        }
+        let Some(surfaces) = self.dict.get(yomi) else {
+            return Vec::new();
+        };
+        to_word_nodes(
+            &surfaces,
+            segmented_yomi,
+            start_pos,
+            self.system_unigram_lm.as_ref(),
+        )
+    }
+}
+
+/// ユーザー辞書(ユーザーが確定した複合語など)にある候補。
+pub struct UserDictProvider<U: SystemUnigramLM> {
+    user_data: Arc<Mutex<UserData>>,
+    system_unigram_lm: Arc<U>,
+}
+
+impl<U: SystemUnigramLM> UserDictProvider<U> {
+    pub fn new(user_data: Arc<Mutex<UserData>>, system_unigram_lm: Arc<U>) -> Self {
+        UserDictProvider {
+            user_data,
+            system_unigram_lm,
+        }
+    }
+}
+
+impl<U: SystemUnigramLM + Send + Sync> CandidateProvider for UserDictProvider<U> {
+    fn provide(&self, _yomi: &str, segmented_yomi: &str, start_pos: i32) -> Vec<WordNode> {
+        let Some(surfaces) = self
+            .user_data
+            .lock()
+            .unwrap()
+            .dict
+            .get(segmented_yomi)
+            .cloned()
+        else {
+            return Vec::new();
+        };
+        to_word_nodes(
+            &surfaces,
+            segmented_yomi,
+            start_pos,
+            self.system_unigram_lm.as_ref(),
+        )
+    }
+}
+
+/// ひらがなそのものと、カタカナ表現の候補。
+/// 辞書にない読みでも、必ず何かしらの候補が出るようにするためのもの。
+pub struct KanaProvider;
+
+impl CandidateProvider for KanaProvider {
+    fn provide(&self, _yomi: &str, segmented_yomi: &str, start_pos: i32) -> Vec<WordNode> {
+        [
+            segmented_yomi.to_string(),
+            hira2kata(segmented_yomi, ConvOption::default()),
+        ]
+        .iter()
+        .map(|surface| WordNode::new(start_pos, surface, segmented_yomi, None, false))
+        .collect()
+    }
+}
@@ -1,11 +1,13 @@
 use std::collections::btree_map::BTreeMap;
 use std::collections::HashSet;
-use std::rc::Rc;
+use std::marker::PhantomData;
 use std::sync::{Arc, Mutex};
 
-use kelp::{hira2kata, ConvOption};
 use log::trace;
 
+use crate::graph::candidate_provider::{
+    CandidateProvider, KanaProvider, SingleTermProvider, SystemDictProvider, UserDictProvider,
+};
 use crate::graph::lattice_graph::LatticeGraph;
 use crate::graph::segmenter::SegmentationResult;
 use crate::graph::word_node::WordNode;
@@ -14,30 +16,60 @@ use crate::lm::base::{SystemBigramLM, SystemUnigramLM};
 use crate::user_side_data::user_data::UserData;
 
 pub struct GraphBuilder<U: SystemUnigramLM, B: SystemBigramLM, KD: KanaKanjiDict> {
-    system_kana_kanji_dict: KD,
-    system_single_term_dict: KD,
     user_data: Arc<Mutex<UserData>>,
-    system_unigram_lm: Rc<U>,
-    system_bigram_lm: Rc<B>,
+    system_unigram_lm: Arc<U>,
+    system_bigram_lm: Arc<B>,
+    /// 候補を作るもの。登録した順に候補を並べる。
+    providers: Vec<Arc<dyn CandidateProvider>>,
+    /// 辞書は providers が持っている。
+    _dict: PhantomData<KD>,
 }
 
 impl<U: SystemUnigramLM, B: SystemBigramLM, KD: KanaKanjiDict> GraphBuilder<U, B, KD> {
+    /// システム辞書、ユーザー辞書、ひらがな・カタカナ、single term 辞書の候補を作る GraphBuilder を作成する。
+    /// それ以外の候補が必要であれば、add_provider で追加する。
     pub fn new(
         system_kana_kanji_dict: KD,
         system_single_term_dict: KD,
         user_data: Arc<Mutex<UserData>>,
-        system_unigram_lm: Rc<U>,
-        system_bigram_lm: Rc<B>,
-    ) -> GraphBuilder<U, B, KD> {
+        system_unigram_lm: Arc<U>,
+        system_bigram_lm: Arc<B>,
+    ) -> GraphBuilder<U, B, KD>
+    where
+        U: Send + Sync + 'static,
+        KD: Send + Sync + 'static,
+    {
+        let providers: Vec<Arc<dyn CandidateProvider>> = vec![
+            Arc::new(SystemDictProvider::new(
+                system_kana_kanji_dict,
+                system_unigram_lm.clone(),
+            )),
+            Arc::new(UserDictProvider::new(
+                user_data.clone(),
+                system_unigram_lm.clone(),
+            )),
+            Arc::new(KanaProvider),
+            Arc::new(SingleTermProvider::new(
+                system_single_term_dict,
+                system_unigram_lm.clone(),
+            )),
+        ];
         GraphBuilder {
-            system_kana_kanji_dict,
-            system_single_term_dict,
             user_data,
             system_unigram_lm,
             system_bigram_lm,
+            providers,
+            _dict: PhantomData,
         }
     }
 
+    /// 候補を作る CandidateProvider を追加する。
+    /// 追加した provider の候補は、辞書などの候補の後に並ぶ。
+    /// 辞書にない読みの候補を作る provider であれば、Segmenter にも追加すること。
+    pub fn add_provider(&mut self, provider: Arc<dyn CandidateProvider>) {
+        self.providers.push(provider);
+    }
+
     pub fn construct(&self, yomi: &str, words_ends_at: &SegmentationResult) -> LatticeGraph<U, B> {
         // このグラフのインデクスは単語の終了位置。
         let mut graph: BTreeMap<i32, Vec<WordNode>> = BTreeMap::new();
@@ -50,77 +82,17 @@ impl<U: SystemUnigramLM, B: SystemBigramLM, KD: KanaKanjiDict> GraphBuilder<U, B
         for (end_pos, segmented_yomis) in words_ends_at.iter() {
             for segmented_yomi in segmented_yomis {
                 let vec = graph.entry(*end_pos as i32).or_default();
+                let start_pos = (end_pos - segmented_yomi.len()) as i32;
 
+                // 同じ表層の候補が複数の provider から来たら、先に登録された provider のものを使う。
                 let mut seen: HashSet<String> = HashSet::new();
-
-                // TODO このへんコピペすぎるので整理必要。
-                // システム辞書にある候補を元に候補をリストアップする
-                if let Some(kanjis) = self.system_kana_kanji_dict.get(segmented_yomi) {
-                    for kanji in kanjis {
-                        let node = WordNode::new(
-                            (end_pos - segmented_yomi.len()) as i32,
-                            &kanji,
-                            segmented_yomi,
-                            self.system_unigram_lm
-                                .find((kanji.to_string() + "/" + segmented_yomi).as_str()),
-                            false,
-                        );
-                        trace!("WordIDScore: {:?}", node.word_id_and_score);
-                        vec.push(node);
-                        seen.insert(kanji.to_string());
-                    }
-                }
-                if let Some(surfaces) = self.user_data.lock().unwrap().dict.get(segmented_yomi) {
-                    for surface in surfaces {
-                        if seen.contains(surface) {
+                for provider in &self.providers {
+                    for node in provider.provide(yomi, segmented_yomi, start_pos) {
+                        if !seen.insert(node.surface.clone()) {
                             continue;
                         }
-                        let node = WordNode::new(
-                            (end_pos - segmented_yomi.len()) as i32,
-                            surface,
-                            segmented_yomi,
-                            self.system_unigram_lm
-                                .find((surface.to_string() + "/" + segmented_yomi).as_str()),
-                            false,
-                        );
                         trace!("WordIDScore: {:?}", node.word_id_and_score);
                         vec.push(node);
-                        seen.insert(surface.to_string());
-                    }
-                }
-                // ひらがな候補をリストアップする
-                for surface in [
-                    segmented_yomi,
-                    hira2kata(segmented_yomi, ConvOption::default()).as_str(),
-                ] {
-                    if seen.contains(surface) {
-                        continue;
-                    }
-                    // ひらがなそのものと、カタカナ表現もエントリーとして登録しておく。
-                    let node = WordNode::new(
-                        (end_pos - segmented_yomi.len()) as i32,
-                        surface,
-                        segmented_yomi,
-                        None,
-                        true,
-                    );
-                    vec.push(node);
-                }
-
-                // 変換範囲が全体になっていれば single term 辞書を利用する。
-                if segmented_yomi == yomi {
-                    if let Some(surfaces) = self.system_single_term_dict.get(yomi) {
-                        for surface in surfaces {
-                            let node = WordNode::new(
-                                (end_pos - segmented_yomi.len()) as i32,
-                                &surface,
-                                segmented_yomi,
-                                self.system_unigram_lm
-                                    .find((surface.to_string() + "/" + segmented_yomi).as_str()),
-                                false,
-                            );
-                            vec.push(node);
-                        }
                     }
                 }
             }
@@ -139,9 +111,11 @@ impl<U: SystemUnigramLM, B: SystemBigramLM, KD: KanaKanjiDict> GraphBuilder<U, B
 mod tests {
     use std::collections::HashMap;
 
+    use crate::graph::candidate::Candidate;
     use crate::kana_kanji::hashmap_vec::HashmapVecKanaKanjiDict;
     use crate::lm::system_bigram::MarisaSystemBigramLMBuilder;
     use crate::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
+    use crate::number::NumberProvider;
 
     use super::*;
 
@@ -154,13 +128,13 @@ mod tests {
                 vec!["🍣".to_string()],
             )])),
             Arc::new(Mutex::new(UserData::default())),
-            Rc::new(
+            Arc::new(
                 MarisaSystemUnigramLMBuilder::default()
                     .set_default_cost(20_f32)
                     .set_default_cost_for_short(19_f32)
                     .build(),
             ),
-            Rc::new(
+            Arc::new(
                 MarisaSystemBigramLMBuilder::default()
                     .set_default_edge_cost(20_f32)
                     .build()?,
@@ -187,13 +161,13 @@ mod tests {
             HashmapVecKanaKanjiDict::new(HashMap::new()),
             HashmapVecKanaKanjiDict::new(HashMap::new()),
             Arc::new(Mutex::new(UserData::default())),
-            Rc::new(
+            Arc::new(
                 MarisaSystemUnigramLMBuilder::default()
                     .set_default_cost(20_f32)
                     .set_default_cost_for_short(19_f32)
                     .build(),
             ),
-            Rc::new(
+            Arc::new(
                 MarisaSystemBigramLMBuilder::default()
                     .set_default_edge_cost(20_f32)
                     .build()?,
@@ -210,6 +184,134 @@ mod tests {
         Ok(())
     }
 
+    // SKK 辞書の英字の読みのエントリー(abbrev)も、読みとして引ける。
+    #[test]
+    fn test_abbrev() -> anyhow::Result<()> {
+        let graph_builder = GraphBuilder::new(
+            HashmapVecKanaKanjiDict::new(HashMap::from([(
+                "cpu".to_string(),
+                vec!["CPU".to_string(), "シーピーユー".to_string()],
+            )])),
+            HashmapVecKanaKanjiDict::new(HashMap::new()),
+            Arc::new(Mutex::new(UserData::default())),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        let yomi = "cpu";
+        let got = graph_builder.construct(
+            yomi,
+            &SegmentationResult::new(BTreeMap::from([(3, vec!["cpu".to_string()])])),
+        );
+        let nodes = got.node_list(3).unwrap();
+        let got_surfaces: Vec<String> = nodes.iter().map(|f| f.surface.to_string()).collect();
+        assert_eq!(got_surfaces, vec!["CPU", "シーピーユー", "cpu"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_number() -> anyhow::Result<()> {
+        let user_data = Arc::new(Mutex::new(UserData::default()));
+        user_data.lock().unwrap().set_learn_number_forms(true);
+        let mut graph_builder = GraphBuilder::new(
+            HashmapVecKanaKanjiDict::new(HashMap::new()),
+            HashmapVecKanaKanjiDict::new(HashMap::new()),
+            user_data.clone(),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        graph_builder.add_provider(Arc::new(NumberProvider::new(user_data.clone())));
+        let yomi = "123";
+        let got = graph_builder.construct(
+            yomi,
+            &SegmentationResult::new(BTreeMap::from([(3, vec!["123".to_string()])])),
+        );
+        let nodes = got.node_list(3).unwrap();
+        let got_surfaces: Vec<String> = nodes.iter().map(|f| f.surface.to_string()).collect();
+        assert_eq!(
+            got_surfaces,
+            vec!["123", "１２３", "百二十三", "一二三", "壱百弐拾参"]
+        );
+
+        // 漢数字をよく使うなら、数字の候補の中では漢数字が先に来る。
+        user_data
+            .lock()
+            .unwrap()
+            .record_entries(&[Candidate::new("456", "四百五十六", 0_f32)]);
+        let got = graph_builder.construct(
+            yomi,
+            &SegmentationResult::new(BTreeMap::from([(3, vec!["123".to_string()])])),
+        );
+        let nodes = got.node_list(3).unwrap();
+        let got_surfaces: Vec<String> = nodes.iter().map(|f| f.surface.to_string()).collect();
+        assert_eq!(
+            got_surfaces,
+            vec!["123", "百二十三", "１２３", "一二三", "壱百弐拾参"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_provider() -> anyhow::Result<()> {
+        struct TestProvider;
+        impl CandidateProvider for TestProvider {
+            fn provide(&self, _yomi: &str, segmented_yomi: &str, start_pos: i32) -> Vec<WordNode> {
+                ["今日", "2026-10-18"]
+                    .iter()
+                    .map(|surface| WordNode::new(start_pos, surface, segmented_yomi, None, true))
+                    .collect()
+            }
+        }
+
+        let mut graph_builder = GraphBuilder::new(
+            HashmapVecKanaKanjiDict::new(HashMap::from([(
+                "きょう".to_string(),
+                vec!["今日".to_string()],
+            )])),
+            HashmapVecKanaKanjiDict::new(HashMap::new()),
+            Arc::new(Mutex::new(UserData::default())),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        graph_builder.add_provider(Arc::new(TestProvider));
+        let yomi = "きょう";
+        let got = graph_builder.construct(
+            yomi,
+            &SegmentationResult::new(BTreeMap::from([(9, vec!["きょう".to_string()])])),
+        );
+        let nodes = got.node_list(9).unwrap();
+        let got_surfaces: Vec<String> = nodes.iter().map(|f| f.surface.to_string()).collect();
+        // 辞書にある候補とは重複させない。
+        assert_eq!(got_surfaces, vec!["今日", "きょう", "キョウ", "2026-10-18"]);
+        Ok(())
+    }
+
     // ひらがな、カタカナがすでにかな漢字辞書から提供されている場合でも、重複させない。
     #[test]
     fn test_default_terms_duplicated() -> anyhow::Result<()> {
@@ -220,13 +322,13 @@ mod tests {
             )])),
             HashmapVecKanaKanjiDict::new(HashMap::new()),
             Arc::new(Mutex::new(UserData::default())),
-            Rc::new(
+            Arc::new(
                 MarisaSystemUnigramLMBuilder::default()
                     .set_default_cost(20_f32)
                     .set_default_cost_for_short(19_f32)
                     .build(),
             ),
-            Rc::new(
+            Arc::new(
                 MarisaSystemBigramLMBuilder::default()
                     .set_default_edge_cost(20_f32)
                     .build()?,
@@ -117,6 +117,7 @@ impl GraphResolver {
                 yomi: f.yomi.clone(),
                 cost: *costmap.get(f).unwrap(),
                 compound_word: false,
+                auto_generated: f.auto_generated,
             })
             .collect();
         strict_results.sort();
@@ -141,6 +142,7 @@ impl GraphResolver {
                 &costmap,
                 0_f32,
                 None,
+                false,
             );
             candidates.sort();
             for x in candidates {
@@ -152,6 +154,7 @@ impl GraphResolver {
     }
 
     /// - `tail_cost`: 末尾から辿った場合のコスト
+    /// - `auto_generated`: 日付などの、学習しない単語を含んでいるか
     #[allow(clippy::too_many_arguments)]
     fn collect_breakdown_results<U: SystemUnigramLM, B: SystemBigramLM>(
         node_yomi: &str,
@@ -166,6 +169,7 @@ impl GraphResolver {
         cost_map: &&HashMap<&WordNode, f32>,
         tail_cost: f32,
         next_node: Option<&WordNode>,
+        auto_generated: bool,
     ) {
         if depth > 4 {
             // depth が深過ぎたら諦める。
@@ -183,12 +187,12 @@ impl GraphResolver {
                 yomi: cur_yomi,
                 cost: tail_cost,
                 compound_word: true,
+                auto_generated,
             });
             return;
         }
 
-        let Some(targets) = lattice
-            .node_list(end_pos) else {
+        let Some(targets) = lattice.node_list(end_pos) else {
             // 直前のノードはない場合ある。
             return;
         };
@@ -245,6 +249,7 @@ impl GraphResolver {
                 cost_map,
                 tail_cost + target.tail_cost,
                 Some(&target.node),
+                auto_generated || target.node.auto_generated,
             )
         }
     }
@@ -280,7 +285,6 @@ mod tests {
     use std::collections::btree_map::BTreeMap;
     use std::fs::File;
     use std::io::Write;
-    use std::rc::Rc;
     use std::sync::{Arc, Mutex};
 
     use anyhow::Result;
@@ -330,8 +334,8 @@ mod tests {
             HashmapVecKanaKanjiDict::new(HashMap::new()),
             HashmapVecKanaKanjiDict::new(Default::default()),
             Arc::new(Mutex::new(user_data)),
-            Rc::new(system_unigram_lm),
-            Rc::new(system_bigram_lm),
+            Arc::new(system_unigram_lm),
+            Arc::new(system_bigram_lm),
         );
         let lattice = graph_builder.construct("abc", &graph);
         let resolver = GraphResolver::default();
@@ -384,8 +388,8 @@ mod tests {
             HashmapVecKanaKanjiDict::new(dict),
             HashmapVecKanaKanjiDict::new(HashMap::new()),
             Arc::new(Mutex::new(user_data)),
-            Rc::new(system_unigram_lm),
-            Rc::new(system_bigram_lm),
+            Arc::new(system_unigram_lm),
+            Arc::new(system_bigram_lm),
         );
         let lattice = graph_builder.construct(&yomi, &graph);
         // dot -Tpng -o /tmp/lattice.png /tmp/lattice.dot && open /tmp/lattice.png
@@ -468,8 +472,8 @@ mod tests {
             HashmapVecKanaKanjiDict::new(dict),
             HashmapVecKanaKanjiDict::new(HashMap::new()),
             Arc::new(Mutex::new(user_data)),
-            Rc::new(system_unigram_lm),
-            Rc::new(system_bigram_lm),
+            Arc::new(system_unigram_lm),
+            Arc::new(system_bigram_lm),
         );
         let lattice = graph_builder.construct(&yomi, &graph);
         // dot -Tpng -o /tmp/lattice.png /tmp/lattice.dot && open /tmp/lattice.png
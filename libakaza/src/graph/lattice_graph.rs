@@ -1,6 +1,5 @@
-use std::collections::btree_map::BTreeMap;
+use std::collections::btree_map::{BTreeMap, Iter};
 use std::fmt::{Debug, Formatter};
-use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 use log::{error, info, trace};
@@ -14,8 +13,8 @@ pub struct LatticeGraph<U: SystemUnigramLM, B: SystemBigramLM> {
     pub(crate) yomi: String,
     pub(crate) graph: BTreeMap<i32, Vec<WordNode>>,
     pub(crate) user_data: Arc<Mutex<UserData>>,
-    pub(crate) system_unigram_lm: Rc<U>,
-    pub(crate) system_bigram_lm: Rc<B>,
+    pub(crate) system_unigram_lm: Arc<U>,
+    pub(crate) system_bigram_lm: Arc<B>,
 }
 
 impl<U: SystemUnigramLM, B: SystemBigramLM> Debug for LatticeGraph<U, B> {
@@ -29,6 +28,16 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> Debug for LatticeGraph<U, B> {
 }
 
 impl<U: SystemUnigramLM, B: SystemBigramLM> LatticeGraph<U, B> {
+    /// 変換対象の読み
+    pub fn yomi(&self) -> &str {
+        self.yomi.as_str()
+    }
+
+    /// 終了位置(バイト数)と、その位置で終わるノードのリストを順に返す。
+    pub fn iter(&self) -> Iter<'_, i32, Vec<WordNode>> {
+        self.graph.iter()
+    }
+
     /// i文字目で終わるノードを探す
     pub fn node_list(&self, end_pos: i32) -> Option<&Vec<WordNode>> {
         self.graph.get(&end_pos)
@@ -38,7 +47,7 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> LatticeGraph<U, B> {
     // BOS わ た し
     //     [  ][ ]
     //     [     ]
-    pub(crate) fn get_prev_nodes(&self, node: &WordNode) -> Option<&Vec<WordNode>> {
+    pub fn get_prev_nodes(&self, node: &WordNode) -> Option<&Vec<WordNode>> {
         // ここの処理を簡単にするために BOS が入っている、のだとおもう。
         trace!("get_prev_nodes: {}", node.start_pos - 1);
         self.graph.get(&(node.start_pos))
@@ -120,7 +129,7 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> LatticeGraph<U, B> {
         buf
     }
 
-    pub(crate) fn get_node_cost(&self, node: &WordNode) -> f32 {
+    pub fn get_node_cost(&self, node: &WordNode) -> f32 {
         if let Some(user_cost) = self.user_data.lock().unwrap().get_unigram_cost(node) {
             info!("Use user's node score: {:?}", node);
             // use user's score. if it's exists.
@@ -140,7 +149,7 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> LatticeGraph<U, B> {
         };
     }
 
-    pub(crate) fn get_edge_cost(&self, prev: &WordNode, node: &WordNode) -> f32 {
+    pub fn get_edge_cost(&self, prev: &WordNode, node: &WordNode) -> f32 {
         if let Some(cost) = self.user_data.lock().unwrap().get_bigram_cost(prev, node) {
             return cost;
         }
@@ -1,4 +1,5 @@
 pub mod candidate;
+pub mod candidate_provider;
 pub mod graph_builder;
 pub mod graph_resolver;
 pub mod lattice_graph;
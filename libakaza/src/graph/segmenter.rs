@@ -5,6 +5,7 @@ use std::sync::{Arc, Mutex};
 
 use log::{debug, info, trace};
 
+use crate::graph::candidate_provider::CandidateProvider;
 use crate::kana_trie::base::KanaTrie;
 
 #[derive(PartialEq, Debug)]
@@ -38,12 +39,23 @@ impl SegmentationResult {
 
 pub struct Segmenter {
     tries: Vec<Arc<Mutex<dyn KanaTrie>>>,
+    /// かなトライにない読みを分節するための provider。
+    providers: Vec<Arc<dyn CandidateProvider>>,
 }
 
 impl Segmenter {
     pub fn new(tries: Vec<Arc<Mutex<dyn KanaTrie>>>) -> Segmenter {
         info!("Registering tries for Segmenter: {}", tries.len());
-        Segmenter { tries }
+        Segmenter {
+            tries,
+            providers: Vec::new(),
+        }
+    }
+
+    /// CandidateProvider::segment_lens で分節する provider を追加する。
+    /// GraphBuilder に追加した provider が候補を作れるように、同じものを追加すること。
+    pub fn add_provider(&mut self, provider: Arc<dyn CandidateProvider>) {
+        self.providers.push(provider);
     }
 
     /**
@@ -103,17 +115,31 @@ impl Segmenter {
             }
 
             let mut candidates: HashSet<String> = HashSet::new();
-            for trie in &self.tries {
-                let got = trie.lock().unwrap().common_prefix_search(yomi);
-                debug!("Common prefix search: {:?}", got);
+            let provided = self.providers.iter().map(|provider| {
+                provider
+                    .segment_lens(yomi)
+                    .into_iter()
+                    .map(|len| yomi[..len].to_string())
+                    .collect::<Vec<_>>()
+            });
+            let found = self
+                .tries
+                .iter()
+                .map(|trie| {
+                    let got = trie.lock().unwrap().common_prefix_search(yomi);
+                    debug!("Common prefix search: {:?}", got);
+                    got
+                })
+                .chain(provided);
+            for got in found {
                 'insert: for word in got {
                     let ends_at = start_pos + word.len();
 
-                    // end_pos が force の範囲に入っていたら処理しない。
+                    // force の範囲に重なっていたら処理しない。
+                    // "ぼくのiPhoneを" の "iPhone" のように、force の範囲が読みの途中にあることもある。
                     if let Some(force_ranges) = force_ranges {
                         for force_range in force_ranges {
-                            // force_range は exclusive で、厳しい。
-                            if force_range.contains(&ends_at) || force_range.end == ends_at {
+                            if start_pos < force_range.end && force_range.start < ends_at {
                                 trace!("Blocked candidate range: {}, {:?}", word, force_range);
                                 continue 'insert;
                             } else {
@@ -161,7 +187,10 @@ impl Segmenter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::calculator::CalculatorProvider;
     use crate::kana_trie::cedarwood_kana_trie::CedarwoodKanaTrie;
+    use crate::number::NumberProvider;
+    use crate::user_side_data::user_data::UserData;
 
     #[test]
     fn test_simple() {
@@ -198,6 +227,55 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_number() {
+        let kana_trie = CedarwoodKanaTrie::build(vec!["えん".to_string()]);
+
+        let mut segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        // 数字の provider がなければ、数字の並びは一文字ずつになる。
+        assert_eq!(
+            segmenter.build("12えん", None),
+            SegmentationResult::new(BTreeMap::from([
+                (1, vec!["1".to_string()]),
+                (2, vec!["2".to_string()]),
+                (8, vec!["えん".to_string()]),
+            ]))
+        );
+
+        segmenter.add_provider(Arc::new(NumberProvider::new(Arc::new(Mutex::new(
+            UserData::default(),
+        )))));
+        let graph = segmenter.build("123えん", None);
+        assert_eq!(
+            graph,
+            SegmentationResult::new(BTreeMap::from([
+                (3, vec!["123".to_string()]),
+                (9, vec!["えん".to_string()]),
+            ]))
+        )
+    }
+
+    #[test]
+    fn test_expression() {
+        let kana_trie = CedarwoodKanaTrie::build(vec![]);
+
+        let mut segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        segmenter.add_provider(Arc::new(NumberProvider::new(Arc::new(Mutex::new(
+            UserData::default(),
+        )))));
+        segmenter.add_provider(Arc::new(CalculatorProvider));
+        let graph = segmenter.build("12*3=", None);
+        assert_eq!(
+            graph,
+            SegmentationResult::new(BTreeMap::from([
+                (2, vec!["12".to_string()]),
+                (3, vec!["*".to_string()]),
+                (4, vec!["3".to_string()]),
+                (5, vec!["12*3=".to_string(), "=".to_string()]),
+            ]))
+        )
+    }
+
     #[test]
     fn test_force() -> anyhow::Result<()> {
         // env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info");
@@ -225,4 +303,31 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_force_in_middle() {
+        let kana_trie = CedarwoodKanaTrie::build(Vec::from([
+            "ぼく".to_string(),
+            "ぼくの".to_string(),
+            "の".to_string(),
+            "を".to_string(),
+            "のい".to_string(),
+        ]));
+
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let yomi = "ぼくのiPhoneを";
+        // 英数字の部分だけを force_range に指定する。
+        let start = "ぼくの".len();
+        let range = start..start + "iPhone".len();
+        let graph = segmenter.build(yomi, Some(std::slice::from_ref(&range)));
+        assert_eq!(
+            graph,
+            SegmentationResult::new(BTreeMap::from([
+                (6, vec!["ぼく".to_string()]),
+                (9, vec!["ぼくの".to_string(), "の".to_string()]),
+                (15, vec!["iPhone".to_string()]),
+                (18, vec!["を".to_string()]),
+            ]))
+        );
+    }
 }
@@ -10,6 +10,7 @@ pub struct WordNode {
     pub yomi: String,
     pub cost: f32,
     pub word_id_and_score: Option<(i32, f32)>,
+    /// 日付や計算結果のように、変換するたびに作られる候補。学習しない。
     pub auto_generated: bool,
 }
 
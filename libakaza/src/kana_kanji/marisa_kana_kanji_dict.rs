@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::fs;
 
 use log::trace;
 
@@ -29,7 +30,11 @@ impl MarisaKanaKanjiDict {
 
         let mut marisa = Marisa::default();
         marisa.build(&keyset);
-        marisa.save(cache_path)?;
+        // 他のプロセスがキャッシュファイルをメモリマップしているかもしれないので、
+        // 直接上書きせずに、一時ファイルに書いてから rename する。
+        let tmp_path = format!("{}.tmp", cache_path);
+        marisa.save(&tmp_path)?;
+        fs::rename(&tmp_path, cache_path)?;
         Ok(MarisaKanaKanjiDict { marisa })
     }
 
@@ -62,6 +67,20 @@ impl MarisaKanaKanjiDict {
         Ok(MarisaKanaKanjiDict { marisa })
     }
 
+    /// 辞書ファイルをメモリマップして読み込む。
+    pub fn mmap(file_name: &str) -> anyhow::Result<MarisaKanaKanjiDict> {
+        let mut marisa = Marisa::default();
+        marisa.mmap(file_name)?;
+        Ok(MarisaKanaKanjiDict { marisa })
+    }
+
+    /// ファイルシステムを経由せずに、メモリ上のデータを読み込む。
+    pub fn load_from_bytes(bytes: Vec<u8>) -> anyhow::Result<MarisaKanaKanjiDict> {
+        let mut marisa = Marisa::default();
+        marisa.load_from_bytes(bytes)?;
+        Ok(MarisaKanaKanjiDict { marisa })
+    }
+
     pub fn cache_serialized(&self) -> String {
         let mut p = String::new();
         self.marisa
@@ -126,4 +145,22 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn write_mmap() -> anyhow::Result<()> {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let path = tmpfile.path().to_str().unwrap().to_string();
+
+        MarisaKanaKanjiDict::build_with_cache(
+            HashMap::from([("たなか".to_string(), vec!["田中".to_string()])]),
+            path.as_str(),
+            "serialized",
+        )?;
+
+        let dict = MarisaKanaKanjiDict::mmap(path.as_str())?;
+        assert_eq!(dict.get("たなか"), Some(vec!["田中".to_string()]));
+        assert_eq!(dict.cache_serialized(), "serialized");
+
+        Ok(())
+    }
 }
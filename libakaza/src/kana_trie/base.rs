@@ -1,3 +1,3 @@
-pub trait KanaTrie {
+pub trait KanaTrie: Send {
     fn common_prefix_search(&self, query: &str) -> Vec<String>;
 }
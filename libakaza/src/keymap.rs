@@ -1,12 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::BufReader;
 
-use crate::resource::detect_resource_path;
+use crate::atomic_file::write_file_with_backup;
+use crate::resource::{detect_resource_path, extends_chain};
 use anyhow::{bail, Context, Result};
 use log::info;
 use serde::{Deserialize, Serialize};
 
+/// 状態ごとに展開したキー割り当て。(状態, "C-S-j" のような形式のキー) → コマンド名
+pub type KeyBindings = BTreeMap<(KeyState, String), String>;
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Keymap {
     pub extends: Option<String>,
@@ -14,6 +18,8 @@ pub struct Keymap {
 }
 
 impl Keymap {
+    /// 状態ごとに展開する。
+    /// extends したキーマップで、状態ごとに上書き・削除できるようにするため。
     fn to_map(&self) -> Result<HashMap<KeyPattern, Option<String>>> {
         let mut retval = HashMap::new();
 
@@ -21,15 +27,17 @@ impl Keymap {
             for key in &kc.key {
                 let (ctrl, shift, key) = Self::parse_key(key.as_str())?;
 
-                retval.insert(
-                    KeyPattern {
-                        states: kc.states.clone(),
-                        ctrl,
-                        shift,
-                        key,
-                    },
-                    kc.command.clone(),
-                );
+                for state in &kc.states {
+                    retval.insert(
+                        KeyPattern {
+                            states: vec![*state],
+                            ctrl,
+                            shift,
+                            key: key.clone(),
+                        },
+                        kc.command.clone(),
+                    );
+                }
             }
         }
 
@@ -80,7 +88,7 @@ pub struct KeyPattern {
 
 impl Eq for KeyPattern {}
 
-#[derive(Debug, Hash, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Serialize, Deserialize)]
 pub enum KeyState {
     // 何も入力されていない状態。
     PreComposition,
@@ -90,6 +98,53 @@ pub enum KeyState {
     Conversion,
 }
 
+impl KeyState {
+    pub fn all() -> [KeyState; 3] {
+        [
+            KeyState::PreComposition,
+            KeyState::Composition,
+            KeyState::Conversion,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyState::PreComposition => "PreComposition",
+            KeyState::Composition => "Composition",
+            KeyState::Conversion => "Conversion",
+        }
+    }
+
+    pub fn from(s: &str) -> Result<KeyState> {
+        match s {
+            "PreComposition" => Ok(KeyState::PreComposition),
+            "Composition" => Ok(KeyState::Composition),
+            "Conversion" => Ok(KeyState::Conversion),
+            _ => bail!("Unknown key state: {}", s),
+        }
+    }
+
+    pub fn text_jp(&self) -> &'static str {
+        match self {
+            KeyState::PreComposition => "未入力",
+            KeyState::Composition => "入力中",
+            KeyState::Conversion => "変換中",
+        }
+    }
+}
+
+/// parse_key の逆。"C-S-j" のような形式の文字列にする。
+pub fn format_key(ctrl: bool, shift: bool, key: &str) -> String {
+    let mut result = String::new();
+    if ctrl {
+        result += "C-";
+    }
+    if shift {
+        result += "S-";
+    }
+    result + key
+}
+
 impl Keymap {
     pub fn load(keymap_path: &str) -> Result<HashMap<KeyPattern, String>> {
         info!("Load {}", keymap_path);
@@ -123,6 +178,60 @@ impl Keymap {
     }
 }
 
+impl Keymap {
+    /// キーマップエディタ用に、状態ごとに展開したキー割り当てを読み込む。
+    pub fn load_bindings(keymap_path: &str) -> Result<KeyBindings> {
+        Ok(Keymap::load(keymap_path)?
+            .into_iter()
+            .flat_map(|(kp, command)| {
+                let key = format_key(kp.ctrl, kp.shift, &kp.key);
+                kp.states
+                    .into_iter()
+                    .map(move |state| ((state, key.clone()), command.clone()))
+            })
+            .collect())
+    }
+
+    /// keymap_path と、extends している親のキーマップのパスを返す。
+    pub fn extends_chain(keymap_path: &str) -> Vec<String> {
+        extends_chain("keymap", keymap_path)
+    }
+
+    /// base_name のキーマップを extends して、edited との差分だけを持つキーマップを作る。
+    /// base にあって edited にない割り当ては null にして消す。
+    pub fn from_diff(base_name: &str, base: &KeyBindings, edited: &KeyBindings) -> Keymap {
+        let mut keys = Vec::new();
+        for ((state, key), command) in edited {
+            if base.get(&(*state, key.clone())) != Some(command) {
+                keys.push(KeyConfig {
+                    states: vec![*state],
+                    key: vec![key.clone()],
+                    command: Some(command.clone()),
+                });
+            }
+        }
+        for (state, key) in base.keys() {
+            if !edited.contains_key(&(*state, key.clone())) {
+                keys.push(KeyConfig {
+                    states: vec![*state],
+                    key: vec![key.clone()],
+                    command: None,
+                });
+            }
+        }
+
+        Keymap {
+            extends: Some(base_name.to_string()),
+            keys,
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        info!("Save keymap to {}", path);
+        write_file_with_backup(path, |w| Ok(serde_yaml::to_writer(w, self)?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::File;
@@ -158,6 +267,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_format_key() {
+        assert_eq!(format_key(true, true, "h"), "C-S-h");
+        assert_eq!(format_key(false, true, "Right"), "S-Right");
+        assert_eq!(format_key(false, false, "space"), "space");
+    }
+
+    #[test]
+    fn test_from_diff() -> Result<()> {
+        let base = Keymap::load_bindings("../keymap/default.yml")?;
+        assert_eq!(
+            base.get(&(KeyState::Conversion, "C-h".to_string())),
+            Some(&"erase_character_before_cursor".to_string())
+        );
+
+        let mut edited = base.clone();
+        edited.insert(
+            (KeyState::Conversion, "C-h".to_string()),
+            "cursor_left".to_string(),
+        );
+        edited.remove(&(KeyState::Composition, "C-h".to_string()));
+
+        let keymap = Keymap::from_diff("default", &base, &edited);
+        assert_eq!(keymap.extends, Some("default".to_string()));
+        assert_eq!(keymap.keys.len(), 2);
+
+        let tmpdir = tempfile::tempdir()?;
+        let path = tmpdir.path().join("user.yml");
+        let path = path.to_str().unwrap();
+        keymap.save(path)?;
+
+        // 保存したキーマップを読み込むと、状態ごとに上書き・削除されている。
+        assert_eq!(Keymap::load_bindings(path)?, edited);
+        Ok(())
+    }
+
     #[test]
     fn test_shift() -> Result<()> {
         let (ctrl, shift, key) = Keymap::parse_key("h")?;
@@ -2,10 +2,15 @@
 
 extern crate core;
 
+mod atomic_file;
+pub mod calculator;
+pub mod code_input;
 pub mod config;
+mod config_migration;
 pub mod consonant;
 pub mod corpus;
 pub mod cost;
+pub mod date_time;
 pub mod dict;
 pub mod engine;
 pub mod extend_clause;
@@ -14,6 +19,7 @@ pub mod kana_kanji;
 pub mod kana_trie;
 pub mod keymap;
 pub mod lm;
+pub mod number;
 mod resource;
 pub mod romkan;
 pub mod search_result;
@@ -1,13 +1,12 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
 use crate::cost::calc_cost;
 use crate::lm::base::SystemBigramLM;
 
 pub struct OnMemorySystemBigramLM {
     // (word_id, word_id) -> cost
-    map: Rc<RefCell<HashMap<(i32, i32), u32>>>,
+    map: Arc<Mutex<HashMap<(i32, i32), u32>>>,
     default_edge_cost: f32,
     pub total_words: u32,
     pub unique_words: u32,
@@ -15,7 +14,7 @@ pub struct OnMemorySystemBigramLM {
 
 impl OnMemorySystemBigramLM {
     pub fn new(
-        map: Rc<RefCell<HashMap<(i32, i32), u32>>>,
+        map: Arc<Mutex<HashMap<(i32, i32), u32>>>,
         default_edge_cost: f32,
         c: u32,
         v: u32,
@@ -29,11 +28,11 @@ impl OnMemorySystemBigramLM {
     }
 
     pub fn update(&self, word_id1: i32, word_id2: i32, cnt: u32) {
-        self.map.borrow_mut().insert((word_id1, word_id2), cnt);
+        self.map.lock().unwrap().insert((word_id1, word_id2), cnt);
     }
 
     pub fn get_edge_cnt(&self, word_id1: i32, word_id2: i32) -> Option<u32> {
-        self.map.borrow().get(&(word_id1, word_id2)).copied()
+        self.map.lock().unwrap().get(&(word_id1, word_id2)).copied()
     }
 }
 
@@ -45,14 +44,16 @@ impl SystemBigramLM for OnMemorySystemBigramLM {
 
     fn get_edge_cost(&self, word_id1: i32, word_id2: i32) -> Option<f32> {
         self.map
-            .borrow()
+            .lock()
+            .unwrap()
             .get(&(word_id1, word_id2))
             .map(|f| calc_cost(*f, self.total_words, self.unique_words))
     }
 
     fn as_hash_map(&self) -> HashMap<(i32, i32), f32> {
         self.map
-            .borrow()
+            .lock()
+            .unwrap()
             .iter()
             .map(|((id1, id2), cnt)| {
                 (
@@ -1,13 +1,12 @@
 use crate::cost::calc_cost;
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
 use crate::lm::base::SystemUnigramLM;
 
 pub struct OnMemorySystemUnigramLM {
     // word -> (word_id, cost)
-    map: Rc<RefCell<HashMap<String, (i32, u32)>>>,
+    map: Arc<Mutex<HashMap<String, (i32, u32)>>>,
     pub default_cost: f32,
     pub default_cost_for_short: f32,
     pub total_words: u32,
@@ -16,7 +15,7 @@ pub struct OnMemorySystemUnigramLM {
 
 impl OnMemorySystemUnigramLM {
     pub fn new(
-        map: Rc<RefCell<HashMap<String, (i32, u32)>>>,
+        map: Arc<Mutex<HashMap<String, (i32, u32)>>>,
         default_cost: f32,
         default_cost_for_short: f32,
         c: u32,
@@ -38,13 +37,15 @@ impl OnMemorySystemUnigramLM {
         };
 
         self.map
-            .borrow_mut()
+            .lock()
+            .unwrap()
             .insert(word.to_string(), (word_id, cnt));
     }
 
     pub fn reverse_lookup(&self, word_id: i32) -> Option<String> {
         self.map
-            .borrow()
+            .lock()
+            .unwrap()
             .iter()
             .filter(|(_, (id, _))| *id == word_id)
             .map(|(key, (_, _))| key.clone())
@@ -52,7 +53,7 @@ impl OnMemorySystemUnigramLM {
     }
 
     pub fn find_cnt(&self, word: &str) -> Option<(i32, u32)> {
-        self.map.borrow().get(word).copied()
+        self.map.lock().unwrap().get(word).copied()
     }
 }
 
@@ -67,14 +68,16 @@ impl SystemUnigramLM for OnMemorySystemUnigramLM {
 
     fn find(&self, word: &str) -> Option<(i32, f32)> {
         self.map
-            .borrow()
+            .lock()
+            .unwrap()
             .get(word)
             .map(|(id, cnt)| (*id, calc_cost(*cnt, self.total_words, self.unique_words)))
     }
 
     fn as_hash_map(&self) -> HashMap<String, (i32, f32)> {
         self.map
-            .borrow()
+            .lock()
+            .unwrap()
             .iter()
             .map(|(key, (id, cnt))| {
                 (
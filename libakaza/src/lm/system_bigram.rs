@@ -99,6 +99,29 @@ impl MarisaSystemBigramLM {
         })
     }
 
+    /// モデルファイルをメモリマップして読み込む。
+    pub fn mmap(filename: &str) -> Result<MarisaSystemBigramLM> {
+        info!("Mapping system-bigram: {}", filename);
+        let mut marisa = Marisa::default();
+        marisa.mmap(filename)?;
+        let default_edge_cost = Self::read_default_edge_cost(&marisa)?;
+        Ok(MarisaSystemBigramLM {
+            marisa,
+            default_edge_cost,
+        })
+    }
+
+    /// ファイルシステムを経由せずに、メモリ上のモデルデータを読み込む。
+    pub fn load_from_bytes(bytes: Vec<u8>) -> Result<MarisaSystemBigramLM> {
+        let mut marisa = Marisa::default();
+        marisa.load_from_bytes(bytes)?;
+        let default_edge_cost = Self::read_default_edge_cost(&marisa)?;
+        Ok(MarisaSystemBigramLM {
+            marisa,
+            default_edge_cost,
+        })
+    }
+
     pub fn num_keys(&self) -> usize {
         self.marisa.num_keys()
     }
@@ -95,6 +95,25 @@ impl MarisaSystemUnigramLM {
         info!("Reading {}", fname);
         let mut marisa = Marisa::default();
         marisa.load(fname)?;
+        Self::from_marisa(marisa)
+    }
+
+    /// モデルファイルをメモリマップして読み込む。
+    pub fn mmap(fname: &str) -> Result<MarisaSystemUnigramLM> {
+        info!("Mapping {}", fname);
+        let mut marisa = Marisa::default();
+        marisa.mmap(fname)?;
+        Self::from_marisa(marisa)
+    }
+
+    /// ファイルシステムを経由せずに、メモリ上のモデルデータを読み込む。
+    pub fn load_from_bytes(bytes: Vec<u8>) -> Result<MarisaSystemUnigramLM> {
+        let mut marisa = Marisa::default();
+        marisa.load_from_bytes(bytes)?;
+        Self::from_marisa(marisa)
+    }
+
+    fn from_marisa(marisa: Marisa) -> Result<MarisaSystemUnigramLM> {
         let Some((_, default_cost_for_short)) = Self::find_from_trie(&marisa, DEFAULT_COST_FOR_SHORT_KEY) else {
             bail!("Missing key for {}", DEFAULT_COST_FOR_SHORT_KEY);
         };
@@ -189,4 +208,40 @@ mod tests {
             assert_eq!(p, None);
         }
     }
+
+    #[test]
+    fn test_load_from_bytes() -> anyhow::Result<()> {
+        let named_tmpfile = NamedTempFile::new()?;
+        let tmpfile = named_tmpfile.path().to_str().unwrap().to_string();
+
+        let mut builder = MarisaSystemUnigramLMBuilder::default();
+        builder.add("hello", 0.4);
+        builder
+            .set_default_cost(20_f32)
+            .set_default_cost_for_short(19_f32)
+            .save(&tmpfile)?;
+
+        let lm = MarisaSystemUnigramLM::load_from_bytes(std::fs::read(&tmpfile)?)?;
+        assert_eq!(lm.find("hello").unwrap().1, 0.4_f32);
+        assert_eq!(lm.get_default_cost_for_short(), 19_f32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmap() -> anyhow::Result<()> {
+        let named_tmpfile = NamedTempFile::new()?;
+        let tmpfile = named_tmpfile.path().to_str().unwrap().to_string();
+
+        let mut builder = MarisaSystemUnigramLMBuilder::default();
+        builder.add("hello", 0.4);
+        builder
+            .set_default_cost(20_f32)
+            .set_default_cost_for_short(19_f32)
+            .save(&tmpfile)?;
+
+        let lm = MarisaSystemUnigramLM::mmap(&tmpfile)?;
+        assert_eq!(lm.find("hello").unwrap().1, 0.4_f32);
+        assert_eq!(lm.get_default_cost_for_short(), 19_f32);
+        Ok(())
+    }
 }
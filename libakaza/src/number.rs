@@ -0,0 +1,493 @@
+//! 数字の変換候補。
+//!
+//! 「123」や「にじゅうさん」のような読みから、123 / １２３ / 百二十三 / 一二三 / 壱百弐拾参 のような
+//! 候補を生成する。
+
+use std::sync::{Arc, Mutex};
+
+use crate::graph::candidate_provider::CandidateProvider;
+use crate::graph::word_node::WordNode;
+use crate::user_side_data::user_data::UserData;
+
+/// 数字の表記
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NumberForm {
+    /// 123
+    Arabic,
+    /// １２３
+    FullWidth,
+    /// 1,234
+    Grouped,
+    /// 百二十三
+    Kanji,
+    /// 一二三
+    KanjiDigits,
+    /// 壱百弐拾参
+    Daiji,
+}
+
+const KANJI_DIGITS: [&str; 10] = ["〇", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+const DAIJI_DIGITS: [&str; 10] = ["零", "壱", "弐", "参", "四", "伍", "六", "七", "八", "九"];
+
+impl NumberForm {
+    pub const ALL: [NumberForm; 6] = [
+        NumberForm::Arabic,
+        NumberForm::FullWidth,
+        NumberForm::Grouped,
+        NumberForm::Kanji,
+        NumberForm::KanjiDigits,
+        NumberForm::Daiji,
+    ];
+
+    /// 数字の並び(ASCII)をこの表記にする。表記できなければ None を返す。
+    pub fn format(&self, digits: &str) -> Option<String> {
+        match self {
+            NumberForm::Arabic => Some(digits.to_string()),
+            NumberForm::FullWidth => Some(
+                digits
+                    .chars()
+                    .map(|c| char::from_u32(c as u32 - '0' as u32 + '０' as u32).unwrap())
+                    .collect(),
+            ),
+            NumberForm::Grouped => {
+                // 4桁以上でなければ、区切る必要がない。
+                if digits.len() < 4 {
+                    return None;
+                }
+                let head = match digits.len() % 3 {
+                    0 => 3,
+                    n => n,
+                };
+                let mut result = digits[..head].to_string();
+                for chunk in digits.as_bytes()[head..].chunks(3) {
+                    result.push(',');
+                    result += std::str::from_utf8(chunk).unwrap();
+                }
+                Some(result)
+            }
+            NumberForm::Kanji => to_kanji(digits, &KANJI_DIGITS, ["十", "百", "千"], "万", true),
+            NumberForm::KanjiDigits => Some(
+                digits
+                    .chars()
+                    .map(|c| KANJI_DIGITS[c.to_digit(10).unwrap() as usize])
+                    .collect(),
+            ),
+            NumberForm::Daiji => to_kanji(digits, &DAIJI_DIGITS, ["拾", "百", "千"], "萬", false),
+        }
+    }
+
+    /// 「どの表記を好むか」を学習するときのキー。
+    /// 数字の表記の好みの統計データに "(*(*(NUMBER-KANJI/(*(*(NUMBER" のように記録する。
+    pub(crate) fn learning_key(&self) -> &'static str {
+        match self {
+            NumberForm::Arabic => "(*(*(NUMBER-ARABIC",
+            NumberForm::FullWidth => "(*(*(NUMBER-FULLWIDTH",
+            NumberForm::Grouped => "(*(*(NUMBER-GROUPED",
+            NumberForm::Kanji => "(*(*(NUMBER-KANJI",
+            NumberForm::KanjiDigits => "(*(*(NUMBER-KANJI-DIGITS",
+            NumberForm::Daiji => "(*(*(NUMBER-DAIJI",
+        }
+    }
+
+    /// 読みと表記から、どの表記で変換されたかを調べる。
+    pub fn detect(surface: &str, yomi: &str) -> Option<NumberForm> {
+        let digits = to_digits(yomi)?;
+        // 同じ表記になるものがあれば、先にあるほうを採用する。("1" は Arabic であり Grouped ではない)
+        NumberForm::ALL
+            .into_iter()
+            .find(|form| form.format(&digits).as_deref() == Some(surface))
+    }
+}
+
+/// 学習キーの読みの部分
+pub(crate) const NUMBER_LEARNING_YOMI: &str = "(*(*(NUMBER";
+
+/// 位取りのある漢数字にする。1京以上は表記できないので None を返す。
+/// omit_one が true なら「一十」「一百」「一千」の「一」を省略する。
+fn to_kanji(
+    digits: &str,
+    numerals: &[&str; 10],
+    small_units: [&str; 3],
+    man: &str,
+    omit_one: bool,
+) -> Option<String> {
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Some(numerals[0].to_string());
+    }
+    if digits.len() > 16 {
+        return None;
+    }
+
+    let big_units = ["", man, "億", "兆"];
+    let digits = digits
+        .chars()
+        .map(|c| c.to_digit(10).unwrap() as usize)
+        .collect::<Vec<_>>();
+
+    let mut result = String::new();
+    // 下から4桁ずつに区切って、上の位から処理する。
+    let groups = (digits.len() - 1) / 4 + 1;
+    for g in (0..groups).rev() {
+        let mut group = String::new();
+        for k in (0..4).rev() {
+            let pos = g * 4 + k;
+            if pos >= digits.len() {
+                continue;
+            }
+            let d = digits[digits.len() - 1 - pos];
+            if d == 0 {
+                continue;
+            }
+            if k == 0 || d != 1 || !omit_one {
+                group += numerals[d];
+            }
+            if k > 0 {
+                group += small_units[k - 1];
+            }
+        }
+        if !group.is_empty() {
+            result += &group;
+            result += big_units[g];
+        }
+    }
+    Some(result)
+}
+
+/// 読みの全体が数を表していれば、ASCII の数字の並びにして返す。
+pub fn to_digits(yomi: &str) -> Option<String> {
+    if yomi.is_empty() {
+        return None;
+    }
+    if yomi.chars().all(is_digit) {
+        // 全角数字は半角にそろえる
+        return Some(
+            yomi.chars()
+                .map(|c| char::from_digit(digit_value(c).unwrap(), 10).unwrap())
+                .collect(),
+        );
+    }
+
+    let (value, len) = parse_spoken_number(yomi).into_iter().last()?;
+    if len == yomi.len() {
+        Some(value.to_string())
+    } else {
+        None
+    }
+}
+
+/// 読みの全体が数を表していれば、候補の一覧を返す。
+pub fn number_candidates(yomi: &str) -> Vec<String> {
+    number_candidates_with_forms(yomi)
+        .into_iter()
+        .map(|(_, surface)| surface)
+        .collect()
+}
+
+fn number_candidates_with_forms(yomi: &str) -> Vec<(NumberForm, String)> {
+    let Some(digits) = to_digits(yomi) else {
+        return Vec::new();
+    };
+    let mut result: Vec<(NumberForm, String)> = Vec::new();
+    for form in NumberForm::ALL {
+        if let Some(surface) = form.format(&digits) {
+            if !result.iter().any(|(_, it)| *it == surface) {
+                result.push((form, surface));
+            }
+        }
+    }
+    result
+}
+
+/// yomi の先頭から、数として読める部分の長さ(バイト数)を短い順に返す。
+/// Segmenter が、辞書にない数字の並びや数の読みを一つの単語として扱えるようにするため。
+pub fn number_prefix_lens(yomi: &str) -> Vec<usize> {
+    let digits_len: usize = yomi
+        .chars()
+        .take_while(|c| is_digit(*c))
+        .map(|c| c.len_utf8())
+        .sum();
+    if digits_len > 0 {
+        return vec![digits_len];
+    }
+    parse_spoken_number(yomi)
+        .into_iter()
+        .map(|(_, len)| len)
+        .collect()
+}
+
+/// 半角・全角の数字の値
+fn digit_value(c: char) -> Option<u32> {
+    c.to_digit(10)
+        .or_else(|| ('０'..='９').contains(&c).then(|| c as u32 - '０' as u32))
+}
+
+fn is_digit(c: char) -> bool {
+    digit_value(c).is_some()
+}
+
+enum Token {
+    Digit(u64),
+    /// 「いっ」「はっ」のように、後ろに位がつかないと数として完結しないもの。
+    SokuonDigit(u64),
+    /// 「し」「く」のように、決まった位の前でだけ使う読み。(数字, 直後に来てよい位)
+    /// 「しせん」「くじゅう」のようには読まないが、「にじゅうし」のように末尾には来る。
+    LimitedDigit(u64, &'static [u64]),
+    /// 十、百、千
+    SmallUnit(u64),
+    /// 「びゃく」「ぴゃく」「ぜん」のように、特定の数字の後ろでだけ音が変わる位。(位, 直前に来る数字)
+    VoicedSmallUnit(u64, &'static [u64]),
+    /// 万、億、兆
+    BigUnit(u64),
+}
+
+const TOKENS: &[(&str, Token)] = &[
+    ("いち", Token::Digit(1)),
+    ("いっ", Token::SokuonDigit(1)),
+    ("に", Token::Digit(2)),
+    ("さん", Token::Digit(3)),
+    ("よん", Token::Digit(4)),
+    ("し", Token::LimitedDigit(4, &[10])),
+    ("ご", Token::Digit(5)),
+    ("ろく", Token::Digit(6)),
+    ("ろっ", Token::SokuonDigit(6)),
+    ("なな", Token::Digit(7)),
+    ("しち", Token::Digit(7)),
+    ("はち", Token::Digit(8)),
+    ("はっ", Token::SokuonDigit(8)),
+    ("きゅう", Token::Digit(9)),
+    ("く", Token::LimitedDigit(9, &[])),
+    ("じゅう", Token::SmallUnit(10)),
+    ("じゅっ", Token::SmallUnit(10)),
+    ("ひゃく", Token::SmallUnit(100)),
+    ("びゃく", Token::VoicedSmallUnit(100, &[3])),
+    ("ぴゃく", Token::VoicedSmallUnit(100, &[6, 8])),
+    ("せん", Token::SmallUnit(1000)),
+    ("ぜん", Token::VoicedSmallUnit(1000, &[3])),
+    ("まん", Token::BigUnit(10_000)),
+    ("おく", Token::BigUnit(100_000_000)),
+    ("ちょう", Token::BigUnit(1_000_000_000_000)),
+];
+
+/// 「にじゅうさん」のような数の読みを先頭から解析する。
+/// 数として完結する位置ごとに、(値, バイト数) を返す。
+///
+/// 「に」→ 2 のように位を含まないものまで数として扱うと、ほとんどの読みが数の候補を持ってしまうので、
+/// 位(じゅう、ひゃく、まん など)を一つ以上含むものだけを返す。
+/// また、「ぜんご」「じゅうし」のような言葉を数として扱わないように、数字で始まるものだけを返す。
+fn parse_spoken_number(yomi: &str) -> Vec<(u64, usize)> {
+    let mut result = Vec::new();
+
+    let mut pos = 0;
+    let mut total: u64 = 0;
+    let mut section: u64 = 0;
+    let mut digit: Option<u64> = None;
+    // 直前の数字の後ろに来てよい位。None ならどの位でもよい。
+    let mut allowed_units: Option<&[u64]> = None;
+    let mut has_unit = false;
+    let mut last_small_unit = u64::MAX;
+    let mut last_big_unit = u64::MAX;
+
+    while pos < yomi.len() {
+        // 最長一致
+        let Some((word, token)) = TOKENS
+            .iter()
+            .filter(|(word, _)| yomi[pos..].starts_with(word))
+            .max_by_key(|(word, _)| word.len())
+        else {
+            break;
+        };
+
+        match token {
+            Token::Digit(d) | Token::SokuonDigit(d) | Token::LimitedDigit(d, _) => {
+                if digit.is_some() {
+                    break;
+                }
+                digit = Some(*d);
+                allowed_units = match token {
+                    Token::LimitedDigit(_, units) => Some(units),
+                    _ => None,
+                };
+            }
+            Token::SmallUnit(unit) | Token::VoicedSmallUnit(unit, _) => {
+                if *unit >= last_small_unit {
+                    break;
+                }
+                if allowed_units.is_some_and(|units| !units.contains(unit)) {
+                    break;
+                }
+                if let Token::VoicedSmallUnit(_, after) = token {
+                    if !digit.is_some_and(|d| after.contains(&d)) {
+                        break;
+                    }
+                }
+                // 位で始まるものは数としない。
+                if pos == 0 {
+                    break;
+                }
+                section += digit.unwrap_or(1) * unit;
+                digit = None;
+                last_small_unit = *unit;
+                has_unit = true;
+            }
+            Token::BigUnit(unit) => {
+                if *unit >= last_big_unit {
+                    break;
+                }
+                if allowed_units.is_some_and(|units| !units.contains(unit)) {
+                    break;
+                }
+                let value = section + digit.unwrap_or(0);
+                if value == 0 {
+                    break;
+                }
+                total += value * unit;
+                section = 0;
+                digit = None;
+                last_small_unit = u64::MAX;
+                last_big_unit = *unit;
+                has_unit = true;
+            }
+        }
+        pos += word.len();
+
+        // 「いっ」で終わっていたら、まだ数として完結していない。
+        if has_unit && !matches!(token, Token::SokuonDigit(_)) {
+            result.push((total + section + digit.unwrap_or(0), pos));
+        }
+    }
+
+    result
+}
+
+/// 数字の候補を作る CandidateProvider。
+/// ユーザーがよく使う表記の候補ほど先に並べる。
+pub struct NumberProvider {
+    user_data: Arc<Mutex<UserData>>,
+}
+
+impl NumberProvider {
+    pub fn new(user_data: Arc<Mutex<UserData>>) -> Self {
+        NumberProvider { user_data }
+    }
+}
+
+impl CandidateProvider for NumberProvider {
+    fn provide(&self, _yomi: &str, segmented_yomi: &str, start_pos: i32) -> Vec<WordNode> {
+        let mut candidates = number_candidates_with_forms(segmented_yomi);
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+        {
+            let user_data = self.user_data.lock().unwrap();
+            // 同じコストの候補は並んだ順に選ばれるので、学習していない表記は元の順番のまま後ろに置く。
+            candidates.sort_by(|(a, _), (b, _)| {
+                let a = user_data.get_number_form_cost(*a).unwrap_or(f32::MAX);
+                let b = user_data.get_number_form_cost(*b).unwrap_or(f32::MAX);
+                a.total_cmp(&b)
+            });
+        }
+        candidates
+            .iter()
+            .map(|(_, surface)| WordNode::new(start_pos, surface, segmented_yomi, None, false))
+            .collect()
+    }
+
+    /// 辞書にない数字の並びや数の読み("にじゅうさん")も、一つの単語として扱う。
+    fn segment_lens(&self, yomi: &str) -> Vec<usize> {
+        number_prefix_lens(yomi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_candidates() {
+        assert_eq!(
+            number_candidates("123"),
+            vec!["123", "１２３", "百二十三", "一二三", "壱百弐拾参"]
+        );
+        assert_eq!(
+            number_candidates("１２３４"),
+            vec![
+                "1234",
+                "１２３４",
+                "1,234",
+                "千二百三十四",
+                "一二三四",
+                "壱千弐百参拾四"
+            ]
+        );
+        assert!(number_candidates("あいう").is_empty());
+    }
+
+    #[test]
+    fn test_to_kanji() {
+        let kanji = |digits: &str| NumberForm::Kanji.format(digits).unwrap();
+        assert_eq!(kanji("0"), "〇");
+        assert_eq!(kanji("10"), "十");
+        assert_eq!(kanji("1001"), "千一");
+        assert_eq!(kanji("10000"), "一万");
+        assert_eq!(kanji("100020003"), "一億二万三");
+        assert_eq!(
+            kanji("1234567890123456"),
+            "千二百三十四兆五千六百七十八億九千十二万三千四百五十六"
+        );
+        assert_eq!(NumberForm::Kanji.format("12345678901234567"), None);
+        assert_eq!(NumberForm::Grouped.format("1234567").unwrap(), "1,234,567");
+    }
+
+    #[test]
+    fn test_spoken_number() {
+        assert_eq!(to_digits("にじゅうさん"), Some("23".to_string()));
+        assert_eq!(to_digits("さんびゃくろくじゅうご"), Some("365".to_string()));
+        assert_eq!(to_digits("いっせんまん"), Some("10000000".to_string()));
+        assert_eq!(to_digits("はっぴゃく"), Some("800".to_string()));
+        assert_eq!(to_digits("にせんにじゅうろく"), Some("2026".to_string()));
+        // 位を含まないものは数として扱わない
+        assert_eq!(to_digits("に"), None);
+        assert_eq!(to_digits("にさん"), None);
+        assert_eq!(to_digits("じゅうひゃく"), None);
+        // 位で始まるものは数として扱わない
+        assert_eq!(to_digits("ぜんご"), None);
+        assert_eq!(to_digits("じゅうし"), None);
+        assert_eq!(to_digits("ひゃく"), None);
+        // 「し」「く」は、決まった位の前でだけ数字として読む
+        assert_eq!(to_digits("しじゅう"), Some("40".to_string()));
+        assert_eq!(to_digits("にじゅうし"), Some("24".to_string()));
+        assert_eq!(to_digits("しせん"), None);
+        assert_eq!(to_digits("しまん"), None);
+        assert_eq!(to_digits("くせん"), None);
+        assert_eq!(to_digits("くじゅう"), None);
+        // 濁る位は、決まった数字の後ろでだけ使う
+        assert_eq!(to_digits("さんぜん"), Some("3000".to_string()));
+        assert_eq!(to_digits("ろっぴゃく"), Some("600".to_string()));
+        assert_eq!(to_digits("にびゃく"), None);
+        assert_eq!(to_digits("よんぜん"), None);
+    }
+
+    #[test]
+    fn test_number_prefix_lens() {
+        assert_eq!(number_prefix_lens("123えん"), vec![3]);
+        assert_eq!(number_prefix_lens("１２えん"), vec![6]);
+        // にじゅう, にじゅうに
+        assert_eq!(number_prefix_lens("にじゅうにち"), vec![12, 15]);
+        assert!(number_prefix_lens("わたし").is_empty());
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            NumberForm::detect("二十三", "にじゅうさん"),
+            Some(NumberForm::Kanji)
+        );
+        assert_eq!(
+            NumberForm::detect("1,234", "1234"),
+            Some(NumberForm::Grouped)
+        );
+        assert_eq!(NumberForm::detect("12", "12"), Some(NumberForm::Arabic));
+        assert_eq!(NumberForm::detect("十二", "じゅうさん"), None);
+    }
+}
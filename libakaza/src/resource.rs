@@ -1,25 +1,141 @@
 use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
 
 use anyhow::{bail, Context};
+use serde::Deserialize;
 
 pub fn detect_resource_path(base: &str, name: &str) -> anyhow::Result<String> {
     let pathstr: String = if cfg!(test) {
         format!("{}/../{}/{}", env!("CARGO_MANIFEST_DIR"), base, name)
     } else {
-        let target_path = format!("{}/{}", base, name);
-        let basedirs = xdg::BaseDirectories::with_prefix("akaza")
-            .with_context(|| "Opening xdg directory with 'akaza' prefix")?;
-        let pathbuf = basedirs.find_data_file(&target_path);
-        let Some(pathbuf) = pathbuf else {
-            bail!("Cannot find {:?} in XDG_DATA_HOME or XDG_DATA_DIRS(XDG_DATA_HOME={:?}, XDG_DATA_DIRS={:?}, base={:?}, name={:?})",
-                target_path,
-                basedirs.get_data_home().to_string_lossy().to_string(),
-                basedirs.get_data_dirs().iter().map(|x| x.to_string_lossy().to_string()).collect::<Vec<_>>(),
-                base,
-                name
-            )
-        };
-        pathbuf.to_string_lossy().to_string()
+        find_data_file(base, name)?
     };
     Ok(pathstr)
 }
+
+#[cfg(unix)]
+fn find_data_file(base: &str, name: &str) -> anyhow::Result<String> {
+    let target_path = format!("{}/{}", base, name);
+    let basedirs = xdg::BaseDirectories::with_prefix("akaza")
+        .with_context(|| "Opening xdg directory with 'akaza' prefix")?;
+    let pathbuf = basedirs.find_data_file(&target_path);
+    let Some(pathbuf) = pathbuf else {
+        bail!("Cannot find {:?} in XDG_DATA_HOME or XDG_DATA_DIRS(XDG_DATA_HOME={:?}, XDG_DATA_DIRS={:?}, base={:?}, name={:?})",
+            target_path,
+            basedirs.get_data_home().to_string_lossy().to_string(),
+            basedirs.get_data_dirs().iter().map(|x| x.to_string_lossy().to_string()).collect::<Vec<_>>(),
+            base,
+            name
+        )
+    };
+    Ok(pathbuf.to_string_lossy().to_string())
+}
+
+#[derive(Deserialize)]
+struct Extends {
+    extends: Option<String>,
+}
+
+/// キーマップやローマ字かな変換テーブルの `extends` をたどって、読み込まれるファイルを列挙する。
+/// path 自身が先頭になる。読めないファイルがあったら、そこで打ち切る。
+pub(crate) fn extends_chain(base: &str, path: &str) -> Vec<String> {
+    let mut chain = vec![path.to_string()];
+    loop {
+        let Ok(file) = File::open(chain.last().unwrap()) else {
+            break;
+        };
+        let Ok(Extends {
+            extends: Some(parent),
+        }) = serde_yaml::from_reader(BufReader::new(file))
+        else {
+            break;
+        };
+        let Ok(parent) = detect_resource_path(base, &format!("{}.yml", parent)) else {
+            break;
+        };
+        // 循環している場合に止まるように。
+        if chain.contains(&parent) {
+            break;
+        }
+        chain.push(parent);
+    }
+    chain
+}
+
+/// `~/.config/akaza/{name}` のパスを得る。
+#[cfg(unix)]
+pub(crate) fn config_file_path(name: &str) -> anyhow::Result<PathBuf> {
+    let basedir = xdg::BaseDirectories::with_prefix("akaza")?;
+    Ok(basedir.get_config_file(name))
+}
+
+/// `~/.local/share/akaza/{name}` のパスを得る。必要であれば、親ディレクトリを作成する。
+#[cfg(unix)]
+pub(crate) fn data_file_path(name: &str) -> anyhow::Result<PathBuf> {
+    let basedir = xdg::BaseDirectories::with_prefix("akaza")?;
+    Ok(basedir.place_data_file(name)?)
+}
+
+/// `~/.cache/akaza/{name}` のパスを得る。必要であれば、親ディレクトリを作成する。
+#[cfg(unix)]
+pub(crate) fn cache_file_path(name: &str) -> anyhow::Result<PathBuf> {
+    let basedir = xdg::BaseDirectories::with_prefix("akaza")
+        .with_context(|| "xdg directory with 'akaza' prefix")?;
+    Ok(basedir.place_cache_file(name)?)
+}
+
+// wasm32 などの XDG ディレクトリが存在しない環境では、ファイルシステム上のリソースは利用できない。
+// メモリ上のデータからエンジンを作成すること。
+
+#[cfg(not(unix))]
+fn find_data_file(base: &str, name: &str) -> anyhow::Result<String> {
+    bail!(
+        "XDG data directories are not supported on this platform: {}/{}",
+        base,
+        name
+    )
+}
+
+#[cfg(not(unix))]
+pub(crate) fn config_file_path(name: &str) -> anyhow::Result<PathBuf> {
+    bail!(
+        "XDG config directory is not supported on this platform: {}",
+        name
+    )
+}
+
+#[cfg(not(unix))]
+pub(crate) fn data_file_path(name: &str) -> anyhow::Result<PathBuf> {
+    bail!(
+        "XDG data directory is not supported on this platform: {}",
+        name
+    )
+}
+
+#[cfg(not(unix))]
+pub(crate) fn cache_file_path(name: &str) -> anyhow::Result<PathBuf> {
+    bail!(
+        "XDG cache directory is not supported on this platform: {}",
+        name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extends_chain() -> anyhow::Result<()> {
+        let atok = detect_resource_path("romkan", "atok.yml")?;
+        let default = detect_resource_path("romkan", "default.yml")?;
+        assert_eq!(extends_chain("romkan", &atok), vec![atok, default.clone()]);
+        assert_eq!(extends_chain("romkan", &default), vec![default]);
+        assert_eq!(
+            extends_chain("romkan", "/path/to/missing.yml"),
+            vec!["/path/to/missing.yml".to_string()]
+        );
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::BufReader;
 
-use crate::resource::detect_resource_path;
+use crate::atomic_file::write_file_with_backup;
+use crate::resource::{detect_resource_path, extends_chain};
 use anyhow::Context;
 use log::info;
 use regex::{Captures, Regex};
@@ -10,15 +11,72 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct RomKanConfig {
-    mapping: HashMap<String, Option<String>>,
-    extends: Option<String>,
+    pub extends: Option<String>,
+    pub mapping: BTreeMap<String, Option<String>>,
 }
 
-fn load_romkan_map(file_path: &str) -> anyhow::Result<HashMap<String, String>> {
+impl RomKanConfig {
+    pub fn load(file_path: &str) -> anyhow::Result<RomKanConfig> {
+        Ok(serde_yaml::from_reader(BufReader::new(
+            File::open(file_path).with_context(|| file_path.to_string())?,
+        ))?)
+    }
+
+    /// file_path と、extends している親のテーブルのパスを返す。
+    pub fn extends_chain(file_path: &str) -> Vec<String> {
+        extends_chain("romkan", file_path)
+    }
+
+    /// [`crate::keymap::Keymap::from_diff`] のローマ字かな変換テーブル版。
+    /// 削除したエントリは、null で親のエントリを消す。
+    pub fn from_diff(
+        base_name: &str,
+        base: &HashMap<String, String>,
+        edited: &HashMap<String, String>,
+    ) -> RomKanConfig {
+        let mut mapping = BTreeMap::new();
+        for (roma, kana) in edited {
+            if base.get(roma) != Some(kana) {
+                mapping.insert(roma.clone(), Some(kana.clone()));
+            }
+        }
+        for roma in base.keys() {
+            if !edited.contains_key(roma) {
+                mapping.insert(roma.clone(), None);
+            }
+        }
+
+        RomKanConfig {
+            extends: Some(base_name.to_string()),
+            mapping,
+        }
+    }
+
+    pub fn save(&self, file_path: &str) -> anyhow::Result<()> {
+        info!("Save romkan map to {}", file_path);
+        write_file_with_backup(file_path, |w| Ok(serde_yaml::to_writer(w, self)?))
+    }
+}
+
+/// roma と前方一致で衝突するエントリを探す。
+///
+/// 入力中はローマ字をかなに変換して表示するので、"k" のようなエントリがあると、
+/// "ka" を入力しようとしても途中で "k" の変換結果が表示されてしまう。
+/// "n" と "na" のように意図的なものもあるので、警告するだけにとどめること。
+pub fn find_prefix_conflicts(mapping: &HashMap<String, String>, roma: &str) -> Vec<String> {
+    let mut conflicts = mapping
+        .keys()
+        .filter(|it| it.as_str() != roma && (it.starts_with(roma) || roma.starts_with(it.as_str())))
+        .cloned()
+        .collect::<Vec<_>>();
+    conflicts.sort();
+    conflicts
+}
+
+/// extends を解決して、実際に使われるローマ字かな変換テーブルを読み込む。
+pub fn load_romkan_map(file_path: &str) -> anyhow::Result<HashMap<String, String>> {
     info!("Loading romkan map: {}", file_path);
-    let got: RomKanConfig = serde_yaml::from_reader(BufReader::new(
-        File::open(file_path).with_context(|| file_path.to_string())?,
-    ))?;
+    let got = RomKanConfig::load(file_path)?;
 
     if let Some(parent) = got.extends {
         // 継承しているので親を読み込む。
@@ -101,6 +159,12 @@ impl RomKanConverter {
     pub fn remove_last_char(&self, src: &str) -> String {
         self.last_char_pattern.replace(src, "").to_string()
     }
+
+    /// roma に対応するかながあるかどうか。
+    /// かな入力のテーブルの "/" → "め" のように、キーに文字が割り当てられているかを調べるのに使う。
+    pub fn has_mapping(&self, roma: &str) -> bool {
+        self.romkan_map.contains_key(roma)
+    }
 }
 
 #[cfg(test)]
@@ -195,6 +259,9 @@ mod tests {
         assert_eq!(converter.to_hiragana("zya"), "zや");
         // 追加したぶんが効いてる
         assert_eq!(converter.to_hiragana("tso"), "つぉ");
+        // "/" は「・」になる
+        assert!(converter.has_mapping("/"));
+        assert!(!RomKanConverter::default_mapping()?.has_mapping("/"));
         Ok(())
     }
 
@@ -208,4 +275,45 @@ mod tests {
         assert_eq!(converter.to_hiragana("dn"), "だん");
         Ok(())
     }
+
+    #[test]
+    fn test_find_prefix_conflicts() {
+        let mapping = HashMap::from([
+            ("n".to_string(), "ん".to_string()),
+            ("na".to_string(), "な".to_string()),
+            ("nya".to_string(), "にゃ".to_string()),
+            ("ka".to_string(), "か".to_string()),
+        ]);
+        assert_eq!(
+            find_prefix_conflicts(&mapping, "ny"),
+            vec!["n".to_string(), "nya".to_string()]
+        );
+        assert!(find_prefix_conflicts(&mapping, "ka").is_empty());
+    }
+
+    #[test]
+    fn test_from_diff() -> anyhow::Result<()> {
+        let base = load_romkan_map("../romkan/default.yml")?;
+        let mut edited = base.clone();
+        edited.insert("tso".to_string(), "つぉ".to_string());
+        edited.insert("wi".to_string(), "ゐ".to_string());
+        edited.remove("zya");
+
+        let config = RomKanConfig::from_diff("default", &base, &edited);
+        assert_eq!(
+            config.mapping,
+            BTreeMap::from([
+                ("tso".to_string(), Some("つぉ".to_string())),
+                ("wi".to_string(), Some("ゐ".to_string())),
+                ("zya".to_string(), None),
+            ])
+        );
+
+        let tmpdir = tempfile::tempdir()?;
+        let path = tmpdir.path().join("user.yml");
+        let path = path.to_str().unwrap();
+        config.save(path)?;
+        assert_eq!(load_romkan_map(path)?, edited);
+        Ok(())
+    }
 }
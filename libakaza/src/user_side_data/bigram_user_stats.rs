@@ -1,30 +1,82 @@
 use std::collections::HashMap;
 
-use crate::cost::calc_cost;
+use crate::cost::{calc_cost_f32, decay_count};
 use crate::graph::candidate::Candidate;
+use crate::user_side_data::unigram_user_stats::MIN_DECAYED_COUNT;
+use crate::user_side_data::user_stats_utils::now_epoch_secs;
 
 #[derive(Default)]
 pub(crate) struct BiGramUserStats {
     /// ユニーク単語数
     unique_words: u32,
     // C
-    /// 総単語出現数
-    total_words: u32,
+    /// 総単語出現数。`total_updated` の時点まで減衰させた値。
+    total_words: f32,
+    /// `total_words` を最後に計算した時刻(UNIX time)
+    total_updated: u64,
     // V
     /// その単語の出現頻度。「漢字/漢字」がキー。
-    pub(crate) word_count: HashMap<String, u32>,
+    /// 最後に使われた時点まで減衰させた値なので、整数とは限らない。
+    pub(crate) word_count: HashMap<String, f32>,
+    /// その組み合わせが最後に使われた時刻(UNIX time)。
+    pub(crate) last_used: HashMap<String, u64>,
+    /// 出現頻度を減衰させる半減期(秒)。None なら減衰させない。
+    pub(crate) half_life: Option<f32>,
 }
 
 impl BiGramUserStats {
     pub(crate) fn new(
-        unique_words: u32,
-        total_words: u32,
-        word_count: HashMap<String, u32>,
+        word_count: HashMap<String, f32>,
+        last_used: HashMap<String, u64>,
     ) -> BiGramUserStats {
         BiGramUserStats {
-            unique_words,
-            total_words,
+            unique_words: u32::try_from(word_count.len()).unwrap_or(u32::MAX),
+            total_words: word_count.values().sum(),
+            total_updated: 0,
             word_count,
+            last_used,
+            half_life: None,
+        }
+    }
+
+    /// 半減期を設定する。
+    /// 総単語出現数を現在時刻まで減衰させて計算しなおし、十分に減衰した組み合わせは削除する。
+    pub(crate) fn set_half_life(&mut self, half_life: Option<f32>) {
+        // wasm32 のように時刻が取れない環境もあるので、減衰させるデータがあるときだけ時刻を読む。
+        let now = if half_life.is_some() && !self.word_count.is_empty() {
+            now_epoch_secs()
+        } else {
+            0
+        };
+        self.half_life = half_life;
+
+        let decayed = self
+            .word_count
+            .iter()
+            .map(|(key, count)| (key.clone(), self.decay(*count, self.last_used(key), now)))
+            .collect::<Vec<_>>();
+        self.total_words = 0_f32;
+        for (key, count) in decayed {
+            if count < MIN_DECAYED_COUNT {
+                self.word_count.remove(&key);
+                self.last_used.remove(&key);
+            } else {
+                self.total_words += count;
+            }
+        }
+        self.unique_words = u32::try_from(self.word_count.len()).unwrap_or(u32::MAX);
+        self.total_updated = now;
+    }
+
+    fn last_used(&self, key: &str) -> u64 {
+        *self.last_used.get(key).unwrap_or(&0)
+    }
+
+    /// since の時点の出現回数 count を、now の時点まで減衰させる。
+    fn decay(&self, count: f32, since: u64, now: u64) -> f32 {
+        match self.half_life {
+            Some(half_life) => decay_count(count, now.saturating_sub(since), half_life),
+            None => count,
         }
     }
 
@@ -34,17 +86,30 @@ impl BiGramUserStats {
      */
     pub(crate) fn get_cost(&self, key1: &str, key2: &str) -> Option<f32> {
         let key = key1.to_owned() + "\t" + key2;
-        let Some(count) = self.word_count.get(key.as_str()) else {
-            return None;
+        let count = self.word_count.get(key.as_str())?;
+        // 時刻が必要なのは、減衰させる組み合わせが見つかったときだけ。
+        let now = if self.half_life.is_some() {
+            now_epoch_secs()
+        } else {
+            0
         };
-        Some(calc_cost(*count, self.unique_words, self.total_words))
+
+        let count = self.decay(*count, self.last_used(&key), now);
+        if count < MIN_DECAYED_COUNT {
+            return None;
+        }
+        let total_words = self.decay(self.total_words, self.total_updated, now);
+        Some(calc_cost_f32(count, total_words, self.unique_words))
     }
 
-    pub(crate) fn record_entries(&mut self, candidates: &[Candidate]) {
+    pub(crate) fn record_entries(&mut self, candidates: &[Candidate], now: u64) {
         if candidates.len() < 2 {
             return;
         }
 
+        self.total_words = self.decay(self.total_words, self.total_updated, now);
+        self.total_updated = now;
+
         // bigram
         for i in 1..candidates.len() {
             let Some(candidate1) = candidates.get(i - 1) else {
@@ -55,13 +120,38 @@ impl BiGramUserStats {
             };
 
             let key = candidate1.key() + "\t" + candidate2.key().as_str();
-            if let Some(cnt) = self.word_count.get(&key) {
-                self.word_count.insert(key, cnt + 1);
+            // 古い出現回数は、減衰させてから足す。
+            let count = if let Some(count) = self.word_count.get(&key) {
+                self.decay(*count, self.last_used(&key), now)
             } else {
-                self.word_count.insert(key, 1);
-                self.unique_words += 1;
+                self.unique_words = self.unique_words.saturating_add(1);
+                0_f32
+            };
+            self.word_count.insert(key.clone(), count + 1_f32);
+            self.last_used.insert(key, now);
+            self.total_words += 1_f32;
+        }
+    }
+
+    /// 単語を含む組み合わせの統計データをすべて削除する。
+    pub(crate) fn forget(&mut self, key: &str) {
+        let targets = self
+            .word_count
+            .keys()
+            .filter(|it| {
+                it.split_once('\t')
+                    .map(|(key1, key2)| key1 == key || key2 == key)
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for target in targets {
+            if let Some(count) = self.word_count.remove(&target) {
+                let count = self.decay(count, self.last_used(&target), self.total_updated);
+                self.unique_words = self.unique_words.saturating_sub(1);
+                self.total_words = (self.total_words - count).max(0_f32);
             }
-            self.total_words += 1;
+            self.last_used.remove(&target);
         }
     }
 }
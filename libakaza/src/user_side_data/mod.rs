@@ -2,4 +2,5 @@ mod bigram_user_stats;
 mod unigram_user_stats;
 // 調整めんどくさいのでいったんオフ。
 pub mod user_data;
+pub mod user_data_bundle;
 mod user_stats_utils;
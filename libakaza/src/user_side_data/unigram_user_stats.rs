@@ -1,30 +1,88 @@
 use std::collections::HashMap;
 
-use crate::cost::calc_cost;
+use crate::cost::{calc_cost_f32, decay_count};
 use crate::graph::candidate::Candidate;
+use crate::user_side_data::user_stats_utils::now_epoch_secs;
+
+/// 減衰した出現回数がこれより小さくなったら、ユーザーの統計は使わずにシステム言語モデルのコストを使う。
+pub(crate) const MIN_DECAYED_COUNT: f32 = 0.01;
 
 #[derive(Default)]
 pub(crate) struct UniGramUserStats {
     /// ユニーク単語数
     unique_words: u32,
     // C
-    /// 総単語出現数
-    total_words: u32,
+    /// 総単語出現数。`total_updated` の時点まで減衰させた値。
+    total_words: f32,
+    /// `total_words` を最後に計算した時刻(UNIX time)
+    total_updated: u64,
     // V
     /// その単語の出現頻度。「漢字/かな」がキー。
-    pub(crate) word_count: HashMap<String, u32>,
+    /// 最後に使われた時点まで減衰させた値なので、整数とは限らない。
+    pub(crate) word_count: HashMap<String, f32>,
+    /// その単語が最後に使われた時刻(UNIX time)。「漢字/かな」がキー。
+    pub(crate) last_used: HashMap<String, u64>,
+    /// 出現頻度を減衰させる半減期(秒)。None なら減衰させない。
+    pub(crate) half_life: Option<f32>,
 }
 
 impl UniGramUserStats {
     pub(crate) fn new(
-        unique_words: u32,
-        total_words: u32,
-        word_count: HashMap<String, u32>,
+        word_count: HashMap<String, f32>,
+        last_used: HashMap<String, u64>,
     ) -> UniGramUserStats {
         UniGramUserStats {
-            unique_words,
-            total_words,
+            unique_words: u32::try_from(word_count.len()).unwrap_or(u32::MAX),
+            total_words: word_count.values().sum(),
+            total_updated: 0,
             word_count,
+            last_used,
+            half_life: None,
+        }
+    }
+
+    /// 半減期を設定する。
+    /// 総単語出現数を現在時刻まで減衰させて計算しなおし、十分に減衰した単語は削除する。
+    pub(crate) fn set_half_life(&mut self, half_life: Option<f32>) {
+        // wasm32 のように時刻が取れない環境もあるので、減衰させるデータがあるときだけ時刻を読む。
+        let now = if half_life.is_some() && !self.word_count.is_empty() {
+            now_epoch_secs()
+        } else {
+            0
+        };
+        self.set_half_life_at(half_life, now);
+    }
+
+    fn set_half_life_at(&mut self, half_life: Option<f32>, now: u64) {
+        self.half_life = half_life;
+
+        let decayed = self
+            .word_count
+            .iter()
+            .map(|(key, count)| (key.clone(), self.decay(*count, self.last_used(key), now)))
+            .collect::<Vec<_>>();
+        self.total_words = 0_f32;
+        for (key, count) in decayed {
+            if count < MIN_DECAYED_COUNT {
+                self.word_count.remove(&key);
+                self.last_used.remove(&key);
+            } else {
+                self.total_words += count;
+            }
+        }
+        self.unique_words = u32::try_from(self.word_count.len()).unwrap_or(u32::MAX);
+        self.total_updated = now;
+    }
+
+    fn last_used(&self, key: &str) -> u64 {
+        *self.last_used.get(key).unwrap_or(&0)
+    }
+
+    /// since の時点の出現回数 count を、now の時点まで減衰させる。
+    fn decay(&self, count: f32, since: u64, now: u64) -> f32 {
+        match self.half_life {
+            Some(half_life) => decay_count(count, now.saturating_sub(since), half_life),
+            None => count,
         }
     }
 
@@ -32,23 +90,115 @@ impl UniGramUserStats {
      * ノードコストを計算する。
      */
     pub(crate) fn get_cost(&self, key: String) -> Option<f32> {
-        let Some(count) = self.word_count.get(key.as_str()) else {
-            return None;
+        // 時刻が必要なのは、減衰させる単語が見つかったときだけ。
+        let now = if self.half_life.is_some() && self.word_count.contains_key(&key) {
+            now_epoch_secs()
+        } else {
+            0
         };
+        self.get_cost_at(key, now)
+    }
 
-        Some(calc_cost(*count, self.unique_words, self.total_words))
+    fn get_cost_at(&self, key: String, now: u64) -> Option<f32> {
+        let count = self.word_count.get(key.as_str())?;
+
+        let count = self.decay(*count, self.last_used(&key), now);
+        if count < MIN_DECAYED_COUNT {
+            return None;
+        }
+        let total_words = self.decay(self.total_words, self.total_updated, now);
+
+        Some(calc_cost_f32(count, total_words, self.unique_words))
     }
 
-    pub(crate) fn record_entries(&mut self, candidates: &[Candidate]) {
+    pub(crate) fn record_entries(&mut self, candidates: &[Candidate], now: u64) {
+        self.total_words = self.decay(self.total_words, self.total_updated, now);
+        self.total_updated = now;
+
         for candidate in candidates {
             let key = candidate.key();
-            if let Some(i) = self.word_count.get(&key) {
-                self.word_count.insert(key, i + 1);
+            // 古い出現回数は、減衰させてから足す。
+            let count = if let Some(count) = self.word_count.get(&key) {
+                self.decay(*count, self.last_used(&key), now)
             } else {
-                self.word_count.insert(key, 1);
-                self.unique_words += 1;
-            }
-            self.total_words += 1;
+                self.unique_words = self.unique_words.saturating_add(1);
+                0_f32
+            };
+            self.word_count.insert(key.clone(), count + 1_f32);
+            self.last_used.insert(key, now);
+            self.total_words += 1_f32;
         }
     }
+
+    /// 単語の統計データを削除する。
+    pub(crate) fn forget(&mut self, key: &str) {
+        if let Some(count) = self.word_count.remove(key) {
+            let count = self.decay(count, self.last_used(key), self.total_updated);
+            self.unique_words = self.unique_words.saturating_sub(1);
+            self.total_words = (self.total_words - count).max(0_f32);
+        }
+        self.last_used.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decay() {
+        let day = 24 * 60 * 60;
+        let now = 1677000000;
+        let mut stats = UniGramUserStats::new(
+            HashMap::from([
+                ("新/しん".to_string(), 3_f32),
+                ("古/ふる".to_string(), 3_f32),
+            ]),
+            HashMap::from([
+                ("新/しん".to_string(), now - day),
+                ("古/ふる".to_string(), now - 365 * day),
+            ]),
+        );
+
+        // 減衰させなければ、同じコストになる。
+        assert_eq!(
+            stats.get_cost_at("新/しん".to_string(), now),
+            stats.get_cost_at("古/ふる".to_string(), now)
+        );
+
+        // 最近使ったもののほうがコストが低くなる。
+        stats.half_life = Some((180 * day) as f32);
+        assert!(
+            stats.get_cost_at("新/しん".to_string(), now).unwrap()
+                < stats.get_cost_at("古/ふる".to_string(), now).unwrap()
+        );
+
+        // 十分に古いものは使わない。
+        stats.half_life = Some((30 * day) as f32);
+        assert!(stats.get_cost_at("新/しん".to_string(), now).is_some());
+        assert_eq!(stats.get_cost_at("古/ふる".to_string(), now), None);
+
+        // 半減期を設定すると、十分に古いものは削除され、総単語出現数も減衰する。
+        stats.set_half_life_at(Some((30 * day) as f32), now);
+        assert_eq!(stats.unique_words, 1);
+        assert!(!stats.word_count.contains_key("古/ふる"));
+        assert!(2.9 < stats.total_words && stats.total_words < 3.0);
+    }
+
+    #[test]
+    fn test_record_decayed_count() {
+        let day = 24 * 60 * 60;
+        let now = 1677000000;
+        let mut stats = UniGramUserStats::new(
+            HashMap::from([("古/ふる".to_string(), 8_f32)]),
+            HashMap::from([("古/ふる".to_string(), now - 60 * day)]),
+        );
+        stats.set_half_life_at(Some((30 * day) as f32), now - 60 * day);
+
+        // 2回半減したあとに使われたので、8 / 4 + 1 になる。
+        stats.record_entries(&[Candidate::new("ふる", "古", 0_f32)], now);
+        assert_eq!(stats.word_count.get("古/ふる"), Some(&3_f32));
+        assert_eq!(stats.total_words, 3_f32);
+        assert_eq!(stats.unique_words, 1);
+    }
 }
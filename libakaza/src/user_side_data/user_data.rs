@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
@@ -7,14 +7,22 @@ use anyhow::Result;
 use encoding_rs::UTF_8;
 use log::{info, warn};
 
+use crate::atomic_file::{read_file_with_backup, write_file_with_backup};
 use crate::dict::skk::read::read_skkdict;
-use crate::dict::skk::write::write_skk_dict;
+use crate::dict::skk::write::write_skk_dict_to;
 use crate::graph::candidate::Candidate;
 use crate::graph::word_node::WordNode;
 use crate::kana_trie::cedarwood_kana_trie::CedarwoodKanaTrie;
+use crate::number::{NumberForm, NUMBER_LEARNING_YOMI};
+use crate::resource::data_file_path;
 use crate::user_side_data::bigram_user_stats::BiGramUserStats;
 use crate::user_side_data::unigram_user_stats::UniGramUserStats;
-use crate::user_side_data::user_stats_utils::{read_user_stats_file, write_user_stats_file};
+use crate::user_side_data::user_data_bundle::{
+    UserDataBundle, UserStatsEntry, USER_DATA_BUNDLE_VERSION,
+};
+use crate::user_side_data::user_stats_utils::{
+    migrate_user_stats_file, now_epoch_secs, read_user_stats_file, write_user_stats_file,
+};
 
 /**
  * ユーザー固有データ
@@ -29,90 +37,101 @@ pub struct UserData {
 
     unigram_user_stats: UniGramUserStats,
     bigram_user_stats: BiGramUserStats,
+    /// 数字の表記の好み。単語ではないので、ユニグラムの統計とは分けて持つ。
+    number_form_stats: UniGramUserStats,
 
     unigram_path: Option<String>,
     bigram_path: Option<String>,
+    number_form_path: Option<String>,
     dict_path: Option<String>,
 
     pub dict: HashMap<String, Vec<String>>,
+    /// 最後に読み書きしたときの、ユーザー辞書のファイルの内容。
+    /// ほかのプロセスがファイルを更新したときに、手元の変更とマージするために使う。
+    dict_base: HashMap<String, Vec<String>>,
 
     pub(crate) need_save: bool,
+    /// 数字の表記の好みを学習するか。数字の候補を出さない設定なら、学習しても使われない。
+    learn_number_forms: bool,
 }
 
 impl UserData {
     pub fn load_from_default_path() -> Result<Self> {
-        let basedir = xdg::BaseDirectories::with_prefix("akaza")?;
-        let unigram_path = basedir
-            .place_data_file(Path::new("unigram.v1.txt"))?
+        let unigram_path = data_file_path("unigram.v2.txt")?
             .to_str()
             .unwrap()
             .to_string();
-        let bigram_path = basedir
-            .place_data_file(Path::new("bigram.v1.txt"))?
+        let bigram_path = data_file_path("bigram.v2.txt")?
             .to_str()
             .unwrap()
             .to_string();
-        let dict_path = basedir
-            .place_data_file(Path::new("SKK-JISYO.user"))?
+        // v1 形式のファイルしかなければ、v2 形式に変換しておく。
+        for (v1_name, v2_path) in [
+            ("unigram.v1.txt", &unigram_path),
+            ("bigram.v1.txt", &bigram_path),
+        ] {
+            let v1_path = data_file_path(v1_name)?.to_string_lossy().to_string();
+            if let Err(err) = migrate_user_stats_file(&v1_path, v2_path) {
+                warn!("Cannot migrate {} to {}: {}", v1_path, v2_path, err);
+            }
+        }
+        let number_form_path = data_file_path("number_form.v2.txt")?
+            .to_str()
+            .unwrap()
+            .to_string();
+        let dict_path = data_file_path("SKK-JISYO.user")?
             .to_str()
             .unwrap()
             .to_string();
         info!(
-            "Load user data from default path: unigram={}, bigram={}",
-            unigram_path, bigram_path
+            "Load user data from default path: unigram={}, bigram={}, number_form={}",
+            unigram_path, bigram_path, number_form_path
         );
-        Ok(UserData::load(&unigram_path, &bigram_path, &dict_path))
+        Ok(UserData::load(
+            &unigram_path,
+            &bigram_path,
+            &number_form_path,
+            &dict_path,
+        ))
     }
 
-    pub fn load(unigram_path: &String, bigram_path: &String, dict_path: &String) -> Self {
+    pub fn load(
+        unigram_path: &String,
+        bigram_path: &String,
+        number_form_path: &String,
+        dict_path: &String,
+    ) -> Self {
         // ユーザーデータが読み込めないことは fatal エラーではない。
         // 初回起動時にはデータがないので。
         // データがなければ初期所状態から始める
-        let unigram_user_stats = match read_user_stats_file(unigram_path) {
-            Ok(dat) => {
-                let unique_count = dat.len() as u32;
-                let total_count: u32 = dat.iter().map(|f| f.1).sum();
-                let mut word_count: HashMap<String, u32> = HashMap::new();
-                for (word, count) in dat {
-                    word_count.insert(word, count);
-                }
-                UniGramUserStats::new(unique_count, total_count, word_count)
-            }
-            Err(err) => {
-                warn!(
-                    "Cannot load user unigram data from {}: {}",
-                    unigram_path, err
-                );
-
-                UniGramUserStats::new(0, 0, HashMap::new())
-            }
-        };
+        let unigram_user_stats = load_unigram_user_stats(unigram_path);
 
         // build bigram
-        let bigram_user_stats = match read_user_stats_file(bigram_path) {
+        let bigram_user_stats = match read_file_with_backup(bigram_path, read_user_stats_file) {
             Ok(dat) => {
-                let unique_count = dat.len() as u32;
-                let total_count: u32 = dat.iter().map(|f| f.1).sum();
-                let mut words_count: HashMap<String, u32> = HashMap::new();
-                for (words, count) in dat {
+                let mut words_count: HashMap<String, f32> = HashMap::new();
+                let mut last_used: HashMap<String, u64> = HashMap::new();
+                for (words, count, time) in dat {
+                    last_used.insert(words.clone(), time);
                     words_count.insert(words, count);
                 }
-                BiGramUserStats::new(unique_count, total_count, words_count)
+                BiGramUserStats::new(words_count, last_used)
             }
             Err(err) => {
                 warn!("Cannot load user bigram data from {}: {}", bigram_path, err);
                 // ユーザーデータは初回起動時などにはないので、データがないものとして処理を続行する
-                BiGramUserStats::new(0, 0, HashMap::new())
+                BiGramUserStats::default()
             }
         };
 
-        let dict = match read_skkdict(Path::new(dict_path), UTF_8) {
-            Ok(d) => d,
-            Err(err) => {
-                warn!("Cannot load user dict: {:?} {:?}", dict_path, err);
-                Default::default()
-            }
-        };
+        let dict =
+            match read_file_with_backup(dict_path, |path| read_skkdict(Path::new(path), UTF_8)) {
+                Ok(d) => d,
+                Err(err) => {
+                    warn!("Cannot load user dict: {:?} {:?}", dict_path, err);
+                    Default::default()
+                }
+            };
 
         // let kana_trie = match CedarwoodKanaTrie::load(kana_trie_path) {
         //     Ok(trie) => trie,
@@ -122,43 +141,51 @@ impl UserData {
         //     }
         // };
 
-        // cedarwood トライを構築する。
-        // キャッシュせずに動的に構築する方向性。
-        let t1 = SystemTime::now();
-        let mut yomis = unigram_user_stats
-            .word_count
-            .keys()
-            .filter_map(|it| it.split_once('/'))
-            .map(|(_, yomi)| yomi.to_string())
-            .collect::<Vec<_>>();
-        // ユーザー辞書の内容も追加
-        dict.keys().for_each(|yomi| yomis.push(yomi.to_string()));
-        let yomi_len = yomis.len();
-        let kana_trie = CedarwoodKanaTrie::build(yomis);
-        let t2 = SystemTime::now();
-        info!(
-            "Built kana trie in {}msec({} entries)",
-            t2.duration_since(t1).unwrap().as_millis(),
-            yomi_len
-        );
+        let number_form_stats = load_unigram_user_stats(number_form_path);
+
+        let kana_trie = build_kana_trie(&unigram_user_stats, &dict);
 
         UserData {
             unigram_user_stats,
             bigram_user_stats,
+            number_form_stats,
+            dict_base: dict.clone(),
             dict,
             kana_trie: Arc::new(Mutex::new(kana_trie)),
             unigram_path: Some(unigram_path.clone()),
             bigram_path: Some(bigram_path.clone()),
+            number_form_path: Some(number_form_path.clone()),
             dict_path: Some(dict_path.clone()),
             need_save: false,
+            learn_number_forms: false,
         }
     }
 
     /// 入力確定した漢字のリストをユーザー統計データとして記録する。
     /// "Surface/Kana" のフォーマットで渡すこと。
+    /// 日付や計算結果のように、変換するたびに作られる候補は学習しない。
     pub fn record_entries(&mut self, candidates: &[Candidate]) {
-        self.unigram_user_stats.record_entries(candidates);
-        self.bigram_user_stats.record_entries(candidates);
+        let now = now_epoch_secs();
+        // 学習しない候補をはさんだ前後は隣りあっていないので、バイグラムは区切って記録する。
+        for run in candidates.split(|candidate| candidate.auto_generated) {
+            self.bigram_user_stats.record_entries(run, now);
+        }
+        let candidates = candidates
+            .iter()
+            .filter(|candidate| !candidate.auto_generated)
+            .cloned()
+            .collect::<Vec<_>>();
+        self.unigram_user_stats.record_entries(&candidates, now);
+
+        // 数字は、値ごとではなく「漢数字が好き」のように表記の好みとしても学習する。
+        if self.learn_number_forms {
+            let number_forms = candidates
+                .iter()
+                .filter_map(|candidate| NumberForm::detect(&candidate.surface, &candidate.yomi))
+                .map(|form| Candidate::new(NUMBER_LEARNING_YOMI, form.learning_key(), 0_f32))
+                .collect::<Vec<_>>();
+            self.number_form_stats.record_entries(&number_forms, now);
+        }
 
         // 複合語として覚えておくべきものがあれば、学習する。
         candidates
@@ -185,6 +212,90 @@ impl UserData {
         self.need_save = true;
     }
 
+    /// ユーザー辞書に単語を登録する。
+    /// 登録した単語はすぐに変換できるように、かなトライも更新する。
+    pub fn register_word(&mut self, yomi: &str, surface: &str) {
+        let surfaces = self.dict.entry(yomi.to_string()).or_default();
+        // 登録した単語が優先されるように、先頭に置く。
+        surfaces.retain(|it| it != surface);
+        surfaces.insert(0, surface.to_string());
+
+        let mut kana_trie = self.kana_trie.lock().unwrap();
+        if !kana_trie.contains(yomi) {
+            kana_trie.update(yomi);
+        }
+
+        self.need_save = true;
+    }
+
+    /// 誤って学習してしまった単語を忘れる。
+    /// ユニグラム・バイグラムの統計データと、ユーザー辞書のその読みのエントリを削除する。
+    pub fn forget(&mut self, yomi: &str, surface: &str) {
+        let key = surface.to_string() + "/" + yomi;
+        self.unigram_user_stats.forget(&key);
+        self.bigram_user_stats.forget(&key);
+        self.unregister_word(yomi, surface);
+
+        self.need_save = true;
+    }
+
+    /// ユーザー辞書から単語を削除する。学習した統計データはそのまま残す。
+    pub fn unregister_word(&mut self, yomi: &str, surface: &str) {
+        if let Some(surfaces) = self.dict.get_mut(yomi) {
+            surfaces.retain(|it| it != surface);
+            if surfaces.is_empty() {
+                self.dict.remove(yomi);
+            }
+        }
+
+        self.need_save = true;
+    }
+
+    /// ユーザー辞書のファイルのパス
+    pub fn dict_path(&self) -> Option<&str> {
+        self.dict_path.as_deref()
+    }
+
+    /// ユーザー辞書だけをファイルに書き出す。
+    /// akaza-conf などから、学習中の統計データを上書きせずにユーザー辞書を更新するために使う。
+    /// ほかのプロセスが追加・削除した単語を消してしまわないように、書き出す前にファイルの内容とマージする。
+    pub fn write_user_dict(&mut self) -> Result<()> {
+        self.reload_user_dict()?;
+        let Some(dict_path) = self.dict_path.clone() else {
+            return Ok(());
+        };
+        write_file_with_backup(&dict_path, |w| {
+            write_skk_dict_to(w, vec![self.dict.clone()])
+        })?;
+        self.dict_base = self.dict.clone();
+        Ok(())
+    }
+
+    /// ユーザー辞書をファイルから読み直す。
+    /// ほかのプロセス(akaza-conf など)がユーザー辞書を更新したときに呼ぶ。
+    /// 手元で追加・削除した単語のうち、まだ書き出していないものは、読み直した内容にマージする。
+    /// 更新時刻の精度は粗いことがあるので、更新されたかどうかは内容で判断する。
+    pub fn reload_user_dict(&mut self) -> Result<()> {
+        let Some(dict_path) = &self.dict_path else {
+            return Ok(());
+        };
+        let theirs = if Path::new(dict_path).exists() {
+            read_file_with_backup(dict_path, |path| read_skkdict(Path::new(path), UTF_8))?
+        } else {
+            HashMap::new()
+        };
+        if theirs == self.dict_base {
+            return Ok(());
+        }
+
+        self.dict = merge_user_dict(&self.dict_base, &self.dict, &theirs);
+        self.dict_base = theirs;
+
+        // 削除された読みも消えるように、作り直す。
+        *self.kana_trie.lock().unwrap() = build_kana_trie(&self.unigram_user_stats, &self.dict);
+        Ok(())
+    }
+
     pub fn write_user_files(&mut self) -> Result<()> {
         if self.need_save {
             info!(
@@ -195,14 +306,27 @@ impl UserData {
                 self.bigram_user_stats.word_count.len(),
             );
             if let Some(unigram_path) = &self.unigram_path {
-                write_user_stats_file(unigram_path, &self.unigram_user_stats.word_count)?;
+                write_user_stats_file(
+                    unigram_path,
+                    &self.unigram_user_stats.word_count,
+                    &self.unigram_user_stats.last_used,
+                )?;
             }
             if let Some(bigram_path) = &self.bigram_path {
-                write_user_stats_file(bigram_path, &self.bigram_user_stats.word_count)?;
+                write_user_stats_file(
+                    bigram_path,
+                    &self.bigram_user_stats.word_count,
+                    &self.bigram_user_stats.last_used,
+                )?;
             }
-            if let Some(dict_path) = &self.dict_path {
-                write_skk_dict(dict_path, vec![self.dict.clone()])?;
+            if let Some(number_form_path) = &self.number_form_path {
+                write_user_stats_file(
+                    number_form_path,
+                    &self.number_form_stats.word_count,
+                    &self.number_form_stats.last_used,
+                )?;
             }
+            self.write_user_dict()?;
 
             self.need_save = false;
         }
@@ -210,19 +334,205 @@ impl UserData {
         Ok(())
     }
 
+    /// 学習結果を減衰させる半減期を設定する。0 以下なら減衰させない。
+    pub fn set_learning_half_life_days(&mut self, days: f32) {
+        let half_life = if days > 0_f32 {
+            Some(days * 24_f32 * 60_f32 * 60_f32)
+        } else {
+            None
+        };
+        self.unigram_user_stats.set_half_life(half_life);
+        self.bigram_user_stats.set_half_life(half_life);
+        self.number_form_stats.set_half_life(half_life);
+    }
+
+    /// 数字の表記の好みを学習するかどうかを設定する。
+    pub fn set_learn_number_forms(&mut self, enabled: bool) {
+        self.learn_number_forms = enabled;
+    }
+
+    /// 学習データを、ほかのマシンに持っていけるように一つにまとめる。
+    pub fn export_bundle(&self) -> UserDataBundle {
+        let to_entries = |word_count: &HashMap<String, f32>, last_used: &HashMap<String, u64>| {
+            word_count
+                .iter()
+                .map(|(key, count)| {
+                    (
+                        key.clone(),
+                        UserStatsEntry {
+                            count: *count,
+                            last_used: *last_used.get(key).unwrap_or(&0),
+                        },
+                    )
+                })
+                .collect()
+        };
+
+        UserDataBundle {
+            version: USER_DATA_BUNDLE_VERSION,
+            unigram: to_entries(
+                &self.unigram_user_stats.word_count,
+                &self.unigram_user_stats.last_used,
+            ),
+            bigram: to_entries(
+                &self.bigram_user_stats.word_count,
+                &self.bigram_user_stats.last_used,
+            ),
+            dict: self
+                .dict
+                .iter()
+                .map(|(yomi, kanjis)| (yomi.clone(), kanjis.clone()))
+                .collect(),
+        }
+    }
+
+    /// 学習データを bundle の内容で置き換える。
+    /// 手元の学習データとマージしたい場合は、先に `export_bundle` したものと `UserDataBundle::merge` しておくこと。
+    /// 数字の表記の好みはバンドルに入らないので、手元のものを残す。
+    pub fn import_bundle(&mut self, bundle: &UserDataBundle) {
+        let half_life = self.unigram_user_stats.half_life;
+
+        let (word_count, last_used) = from_entries(&bundle.unigram);
+        self.unigram_user_stats = UniGramUserStats::new(word_count, last_used);
+        self.unigram_user_stats.set_half_life(half_life);
+
+        let (word_count, last_used) = from_entries(&bundle.bigram);
+        self.bigram_user_stats = BiGramUserStats::new(word_count, last_used);
+        self.bigram_user_stats.set_half_life(half_life);
+
+        self.dict = bundle
+            .dict
+            .iter()
+            .map(|(yomi, kanjis)| (yomi.clone(), kanjis.clone()))
+            .collect();
+
+        *self.kana_trie.lock().unwrap() = build_kana_trie(&self.unigram_user_stats, &self.dict);
+
+        self.need_save = true;
+    }
+
     pub fn get_unigram_cost(&self, node: &WordNode) -> Option<f32> {
         self.unigram_user_stats.get_cost(node.key())
     }
 
+    /// 数字の表記の好みのコスト。NumberProvider が、初めて入力する数字でもよく使う表記を先に並べるために使う。
+    pub(crate) fn get_number_form_cost(&self, form: NumberForm) -> Option<f32> {
+        self.number_form_stats
+            .get_cost(form.learning_key().to_string() + "/" + NUMBER_LEARNING_YOMI)
+    }
+
     pub fn get_bigram_cost(&self, node1: &WordNode, node2: &WordNode) -> Option<f32> {
         self.bigram_user_stats
             .get_cost(node1.key().as_str(), node2.key().as_str())
     }
 }
 
+/// ユーザー辞書を 3-way マージする。
+/// base は最後に読み書きしたときのファイルの内容、ours は手元の内容、theirs は今のファイルの内容。
+/// theirs に、base から ours への変更(単語の追加・削除・並べかえ)を適用したものを返す。
+fn merge_user_dict(
+    base: &HashMap<String, Vec<String>>,
+    ours: &HashMap<String, Vec<String>>,
+    theirs: &HashMap<String, Vec<String>>,
+) -> HashMap<String, Vec<String>> {
+    let empty = Vec::new();
+    let mut merged = theirs.clone();
+    let yomis = base.keys().chain(ours.keys()).collect::<HashSet<_>>();
+    for yomi in yomis {
+        let base_surfaces = base.get(yomi).unwrap_or(&empty);
+        let our_surfaces = ours.get(yomi).unwrap_or(&empty);
+        if base_surfaces == our_surfaces {
+            continue;
+        }
+
+        let surfaces = merged.entry(yomi.clone()).or_default();
+        // 手元で削除したもの
+        surfaces.retain(|it| our_surfaces.contains(it) || !base_surfaces.contains(it));
+        // 手元で追加したものや、位置を変えたもの(登録しなおして先頭に来たものなど)。
+        // ほかのプロセスで削除されたものは、位置を変えただけなら戻さない。
+        for (i, surface) in our_surfaces.iter().enumerate() {
+            let added = !base_surfaces.contains(surface);
+            let moved = base_surfaces.iter().position(|it| it == surface) != Some(i)
+                && surfaces.contains(surface);
+            if added || moved {
+                surfaces.retain(|it| it != surface);
+                surfaces.insert(i.min(surfaces.len()), surface.clone());
+            }
+        }
+        if surfaces.is_empty() {
+            merged.remove(yomi);
+        }
+    }
+    merged
+}
+
+/// ユニグラムの統計データを読み込む。読み込めなければ、空の統計データから始める。
+fn load_unigram_user_stats(path: &String) -> UniGramUserStats {
+    match read_file_with_backup(path, read_user_stats_file) {
+        Ok(dat) => {
+            let mut word_count: HashMap<String, f32> = HashMap::new();
+            let mut last_used: HashMap<String, u64> = HashMap::new();
+            for (word, count, time) in dat {
+                last_used.insert(word.clone(), time);
+                word_count.insert(word, count);
+            }
+            UniGramUserStats::new(word_count, last_used)
+        }
+        Err(err) => {
+            warn!("Cannot load user unigram data from {}: {}", path, err);
+
+            UniGramUserStats::default()
+        }
+    }
+}
+
+/// ユーザーが変換したことのある読みと、ユーザー辞書の読みからかなトライを構築する。
+/// キャッシュせずに動的に構築する方向性。
+fn build_kana_trie(
+    unigram_user_stats: &UniGramUserStats,
+    dict: &HashMap<String, Vec<String>>,
+) -> CedarwoodKanaTrie {
+    let t1 = SystemTime::now();
+    let mut yomis = unigram_user_stats
+        .word_count
+        .keys()
+        .filter_map(|it| it.split_once('/'))
+        .map(|(_, yomi)| yomi.to_string())
+        .collect::<Vec<_>>();
+    // ユーザー辞書の内容も追加
+    dict.keys().for_each(|yomi| yomis.push(yomi.to_string()));
+    let yomi_len = yomis.len();
+    let kana_trie = CedarwoodKanaTrie::build(yomis);
+    let t2 = SystemTime::now();
+    info!(
+        "Built kana trie in {}msec({} entries)",
+        t2.duration_since(t1).unwrap().as_millis(),
+        yomi_len
+    );
+    kana_trie
+}
+
+/// バンドルの統計データを、(出現頻度, 最後に使われた時刻) に変換する。
+fn from_entries(
+    entries: &BTreeMap<String, UserStatsEntry>,
+) -> (HashMap<String, f32>, HashMap<String, u64>) {
+    let word_count = entries
+        .iter()
+        .map(|(key, entry)| (key.clone(), entry.count))
+        .collect();
+    let last_used = entries
+        .iter()
+        .map(|(key, entry)| (key.clone(), entry.last_used))
+        .collect();
+    (word_count, last_used)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
     use log::LevelFilter;
+    use tempfile::tempdir;
 
     use super::*;
 
@@ -273,4 +583,277 @@ mod tests {
         info!("{}, {}", cost2, cost3);
         assert!(cost2 > cost3);
     }
+
+    #[test]
+    fn test_skip_auto_generated() {
+        let mut user_data = UserData::default();
+        let date = Candidate {
+            auto_generated: true,
+            ..Candidate::new("きょう", "2026-10-18", 0_f32)
+        };
+        user_data.record_entries(&[
+            Candidate::new("あした", "明日", 0_f32),
+            date.clone(),
+            Candidate::new("は", "は", 0_f32),
+        ]);
+
+        let node = |surface: &str, yomi: &str| WordNode::new(0, surface, yomi, None, true);
+        assert!(user_data
+            .get_unigram_cost(&node("明日", "あした"))
+            .is_some());
+        assert_eq!(
+            user_data.get_unigram_cost(&node("2026-10-18", "きょう")),
+            None
+        );
+        // 学習しない候補をはさんだものは、隣りあっていない。
+        assert_eq!(
+            user_data.get_bigram_cost(&node("明日", "あした"), &node("は", "は")),
+            None
+        );
+        assert!(!user_data.kana_trie.lock().unwrap().contains("きょう"));
+    }
+
+    #[test]
+    fn test_number_form() -> Result<()> {
+        let tmpdir = tempdir()?;
+        let path = |name: &str| tmpdir.path().join(name).to_string_lossy().to_string();
+        let (unigram_path, bigram_path, number_form_path, dict_path) = (
+            path("unigram.v2.txt"),
+            path("bigram.v2.txt"),
+            path("number_form.v2.txt"),
+            path("SKK-JISYO.user"),
+        );
+        let candidate = Candidate::new("123", "百二十三", 0_f32);
+        let node = WordNode::new(0, "百二十三", "123", None, false);
+
+        let mut user_data =
+            UserData::load(&unigram_path, &bigram_path, &number_form_path, &dict_path);
+        let mut plain = UserData::default();
+        assert_eq!(user_data.get_number_form_cost(NumberForm::Kanji), None);
+
+        // 数字の候補を出さない設定なら、学習しない。
+        user_data.record_entries(&[candidate.clone()]);
+        plain.record_entries(&[candidate.clone()]);
+        assert_eq!(user_data.get_number_form_cost(NumberForm::Kanji), None);
+
+        user_data.set_learn_number_forms(true);
+        // 漢数字で確定すると、漢数字の表記の好みとして学習される。
+        user_data.record_entries(&[candidate.clone()]);
+        plain.record_entries(&[candidate.clone()]);
+        assert!(user_data.get_number_form_cost(NumberForm::Kanji).is_some());
+        assert_eq!(user_data.get_number_form_cost(NumberForm::Arabic), None);
+        // 確定した単語のコストは、表記の好みを学習しなかったときと変わらない。
+        assert!(user_data.get_unigram_cost(&node).is_some());
+        assert_eq!(
+            user_data.get_unigram_cost(&node),
+            plain.get_unigram_cost(&node)
+        );
+        // 表記の好みはエクスポートしないが、インポートしても消えない。
+        let bundle = user_data.export_bundle();
+        assert_eq!(bundle, plain.export_bundle());
+        user_data.import_bundle(&bundle);
+        assert!(user_data.get_number_form_cost(NumberForm::Kanji).is_some());
+        // 表記の好みは、かなトライには入れない。
+        assert!(!user_data
+            .kana_trie
+            .lock()
+            .unwrap()
+            .contains(NUMBER_LEARNING_YOMI));
+
+        // 保存して読み直しても残る。
+        user_data.write_user_files()?;
+        let saved = UserData::load(&unigram_path, &bigram_path, &number_form_path, &dict_path);
+        assert!(saved.get_number_form_cost(NumberForm::Kanji).is_some());
+        assert_eq!(saved.export_bundle(), plain.export_bundle());
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_from_backup() -> Result<()> {
+        let tmpdir = tempdir()?;
+        let path = |name: &str| tmpdir.path().join(name).to_string_lossy().to_string();
+        let (unigram_path, bigram_path, number_form_path, dict_path) = (
+            path("unigram.v2.txt"),
+            path("bigram.v2.txt"),
+            path("number_form.v2.txt"),
+            path("SKK-JISYO.user"),
+        );
+        let node = WordNode::new(0, "アグリゲーション", "あぐりげーしょん", None, false);
+
+        let mut user_data =
+            UserData::load(&unigram_path, &bigram_path, &number_form_path, &dict_path);
+        user_data.record_entries(&[Candidate::new(
+            "あぐりげーしょん",
+            "アグリゲーション",
+            0_f32,
+        )]);
+        user_data.write_user_files()?;
+        // 二回保存すると、一回目のファイルがバックアップとして残る。
+        user_data.need_save = true;
+        user_data.write_user_files()?;
+
+        // 書き込み途中にクラッシュしたことにする。
+        fs::write(&unigram_path, "アグリゲーション/あぐりげーしょん broken\n")?;
+
+        let user_data = UserData::load(&unigram_path, &bigram_path, &number_form_path, &dict_path);
+        assert!(user_data.get_unigram_cost(&node).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_word() {
+        let mut user_data = UserData::default();
+        user_data.register_word("あかざ", "藜");
+        user_data.register_word("あかざ", "赤座");
+        user_data.register_word("あかざ", "藜");
+        assert_eq!(
+            user_data.dict.get("あかざ"),
+            Some(&vec!["藜".to_string(), "赤座".to_string()])
+        );
+        assert!(user_data.kana_trie.lock().unwrap().contains("あかざ"));
+        assert!(user_data.need_save);
+    }
+
+    #[test]
+    fn test_forget() {
+        let aggregation = WordNode::new(0, "アグリゲーション", "あぐりげーしょん", None, false);
+        let wo = WordNode::new(24, "を", "を", None, false);
+
+        let mut user_data = UserData::default();
+        user_data.record_entries(&[
+            Candidate::new("あぐりげーしょん", "アグリゲーション", 0_f32),
+            Candidate::new("を", "を", 0_f32),
+        ]);
+        user_data.register_word("あぐりげーしょん", "アグリゲーション");
+        user_data.register_word("あぐりげーしょん", "集約");
+        assert!(user_data.get_unigram_cost(&aggregation).is_some());
+        assert!(user_data.get_bigram_cost(&aggregation, &wo).is_some());
+
+        user_data.need_save = false;
+        user_data.forget("あぐりげーしょん", "アグリゲーション");
+        assert_eq!(user_data.get_unigram_cost(&aggregation), None);
+        assert_eq!(user_data.get_bigram_cost(&aggregation, &wo), None);
+        assert!(user_data.get_unigram_cost(&wo).is_some());
+        assert_eq!(
+            user_data.dict.get("あぐりげーしょん"),
+            Some(&vec!["集約".to_string()])
+        );
+        assert!(user_data.need_save);
+
+        user_data.forget("あぐりげーしょん", "集約");
+        assert_eq!(user_data.dict.get("あぐりげーしょん"), None);
+    }
+
+    #[test]
+    fn test_write_and_reload_user_dict() -> Result<()> {
+        let tmpdir = tempdir()?;
+        let path = |name: &str| tmpdir.path().join(name).to_string_lossy().to_string();
+        let (unigram_path, bigram_path, number_form_path, dict_path) = (
+            path("unigram.v2.txt"),
+            path("bigram.v2.txt"),
+            path("number_form.v2.txt"),
+            path("SKK-JISYO.user"),
+        );
+
+        let mut user_data =
+            UserData::load(&unigram_path, &bigram_path, &number_form_path, &dict_path);
+        let mut conf = UserData::load(&unigram_path, &bigram_path, &number_form_path, &dict_path);
+        conf.register_word("あかざ", "藜");
+        conf.register_word("あかざ", "赤座");
+        conf.unregister_word("あかざ", "赤座");
+        conf.write_user_dict()?;
+        // 統計データは書き出さない
+        assert!(!Path::new(&unigram_path).exists());
+
+        user_data.reload_user_dict()?;
+        assert_eq!(user_data.dict.get("あかざ"), Some(&vec!["藜".to_string()]));
+        assert!(user_data.kana_trie.lock().unwrap().contains("あかざ"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_user_dict_on_save() -> Result<()> {
+        let tmpdir = tempdir()?;
+        let path = |name: &str| tmpdir.path().join(name).to_string_lossy().to_string();
+        let (unigram_path, bigram_path, number_form_path, dict_path) = (
+            path("unigram.v2.txt"),
+            path("bigram.v2.txt"),
+            path("number_form.v2.txt"),
+            path("SKK-JISYO.user"),
+        );
+        fs::write(&dict_path, "あかざ /藜/赤座/\nかな /仮名/\n")?;
+
+        let mut user_data =
+            UserData::load(&unigram_path, &bigram_path, &number_form_path, &dict_path);
+        let mut conf = UserData::load(&unigram_path, &bigram_path, &number_form_path, &dict_path);
+
+        // ibus-akaza で登録した単語と、akaza-conf での削除は両方残る。
+        user_data.register_word("なかの", "中野");
+        conf.unregister_word("あかざ", "赤座");
+        conf.unregister_word("かな", "仮名");
+        conf.write_user_dict()?;
+        user_data.write_user_files()?;
+
+        let saved = UserData::load(&unigram_path, &bigram_path, &number_form_path, &dict_path);
+        assert_eq!(saved.dict.get("あかざ"), Some(&vec!["藜".to_string()]));
+        assert_eq!(saved.dict.get("なかの"), Some(&vec!["中野".to_string()]));
+        assert_eq!(saved.dict.get("かな"), None);
+        assert_eq!(user_data.dict, saved.dict);
+        // 削除された読みは、かなトライからも消える。
+        assert!(!user_data.kana_trie.lock().unwrap().contains("かな"));
+
+        // akaza-conf 側も、読み直すと ibus-akaza で登録した単語が見える。
+        conf.reload_user_dict()?;
+        assert_eq!(conf.dict, saved.dict);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_user_dict() {
+        let dict = |entries: &[(&str, &[&str])]| {
+            entries
+                .iter()
+                .map(|(yomi, surfaces)| {
+                    (
+                        yomi.to_string(),
+                        surfaces.iter().map(|it| it.to_string()).collect(),
+                    )
+                })
+                .collect::<HashMap<String, Vec<String>>>()
+        };
+
+        let base = dict(&[("あかざ", &["藜", "赤座"])]);
+        // 手元では「赤座」を登録しなおして先頭にし、「あか」を追加した。
+        let ours = dict(&[("あかざ", &["赤座", "藜"]), ("あか", &["赤"])]);
+        // ほかのプロセスでは「藜」を削除し、「アカザ」を追加した。
+        let theirs = dict(&[("あかざ", &["赤座", "アカザ"])]);
+        assert_eq!(
+            merge_user_dict(&base, &ours, &theirs),
+            dict(&[("あかざ", &["赤座", "アカザ"]), ("あか", &["赤"])])
+        );
+    }
+
+    #[test]
+    fn test_export_and_import_bundle() {
+        let node = WordNode::new(0, "アグリゲーション", "あぐりげーしょん", None, false);
+
+        let mut src = UserData::default();
+        src.record_entries(&[Candidate::new(
+            "あぐりげーしょん",
+            "アグリゲーション",
+            0_f32,
+        )]);
+        src.dict
+            .insert("あかざ".to_string(), vec!["藜".to_string()]);
+        let bundle = src.export_bundle();
+        assert_eq!(bundle.unigram.len(), 1);
+
+        let mut dst = UserData::default();
+        assert_eq!(dst.get_unigram_cost(&node), None);
+        dst.import_bundle(&bundle);
+        assert_eq!(dst.get_unigram_cost(&node), src.get_unigram_cost(&node));
+        assert!(dst.kana_trie.lock().unwrap().contains("あかざ"));
+        assert!(dst.need_save);
+        assert_eq!(dst.export_bundle(), bundle);
+    }
 }
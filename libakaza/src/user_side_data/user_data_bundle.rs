@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufReader;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::atomic_file::write_file_atomically;
+use crate::cost::decay_count;
+use crate::dict::merge_dict::merge_dict;
+
+/// バンドルファイルのフォーマットのバージョン。
+/// 互換性のない変更をしたときに上げること。
+/// - 2: 出現回数を、減衰させた小数で持つようにした。
+pub const USER_DATA_BUNDLE_VERSION: u32 = 2;
+
+/// 統計データの1エントリ。
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct UserStatsEntry {
+    /// 出現回数。最後に使われた時点まで減衰させた値。
+    pub count: f32,
+    /// 最後に使われた時刻(UNIX time)
+    pub last_used: u64,
+}
+
+/// ユーザーの学習データ(ユニグラム・バイグラムの統計と、ユーザー辞書)をまとめたもの。
+///
+/// 複数のマシン間で学習結果を同期するために、一つのファイルとして書き出せるようにしている。
+/// dotfiles などで管理したときに差分が読みやすいように、キーはソートして出力する。
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct UserDataBundle {
+    pub version: u32,
+    /// 「漢字/かな」がキー。
+    #[serde(default)]
+    pub unigram: BTreeMap<String, UserStatsEntry>,
+    /// 「漢字/かな\t漢字/かな」がキー。
+    #[serde(default)]
+    pub bigram: BTreeMap<String, UserStatsEntry>,
+    /// 読み → 漢字のリスト
+    #[serde(default)]
+    pub dict: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for UserDataBundle {
+    fn default() -> Self {
+        UserDataBundle {
+            version: USER_DATA_BUNDLE_VERSION,
+            unigram: BTreeMap::new(),
+            bigram: BTreeMap::new(),
+            dict: BTreeMap::new(),
+        }
+    }
+}
+
+impl UserDataBundle {
+    pub fn load(path: &str) -> Result<UserDataBundle> {
+        let bundle: UserDataBundle = serde_yaml::from_reader(BufReader::new(File::open(path)?))?;
+        if bundle.version > USER_DATA_BUNDLE_VERSION {
+            bail!(
+                "Unsupported user data bundle version: {} (supported: {}): {}",
+                bundle.version,
+                USER_DATA_BUNDLE_VERSION,
+                path
+            );
+        }
+        Ok(bundle)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        // 書き出し先はユーザーが指定するので、バックアップは作らない。
+        write_file_atomically(path, |w| Ok(serde_yaml::to_writer(w, self)?))
+    }
+
+    /// other の内容をマージする。
+    /// 統計データは、古いほうの出現回数を新しいほうの最後に使われた時刻まで半減期 `half_life_days` 日で
+    /// 減衰させてから足しあわせ、最後に使われた時刻は新しいほうを採用する。0 以下なら減衰させない。
+    /// 辞書は和集合をとる。
+    pub fn merge(&mut self, other: &UserDataBundle, half_life_days: f32) {
+        let half_life = if half_life_days > 0_f32 {
+            Some(half_life_days * 24_f32 * 60_f32 * 60_f32)
+        } else {
+            None
+        };
+        merge_stats(&mut self.unigram, &other.unigram, half_life);
+        merge_stats(&mut self.bigram, &other.bigram, half_life);
+
+        let dict = merge_dict(vec![
+            self.dict.clone().into_iter().collect::<HashMap<_, _>>(),
+            other.dict.clone().into_iter().collect::<HashMap<_, _>>(),
+        ]);
+        self.dict = dict.into_iter().collect();
+    }
+
+    /// 出現回数の多いユニグラムを n 件返す。
+    pub fn top_unigram(&self, n: usize) -> Vec<(String, UserStatsEntry)> {
+        top_entries(&self.unigram, n)
+    }
+
+    /// 出現回数の多いバイグラムを n 件返す。
+    pub fn top_bigram(&self, n: usize) -> Vec<(String, UserStatsEntry)> {
+        top_entries(&self.bigram, n)
+    }
+}
+
+/// 出現回数の多い順に、同じ回数なら最近使われた順に並べる。
+fn top_entries(
+    stats: &BTreeMap<String, UserStatsEntry>,
+    n: usize,
+) -> Vec<(String, UserStatsEntry)> {
+    let mut entries = stats
+        .iter()
+        .map(|(key, entry)| (key.clone(), *entry))
+        .collect::<Vec<_>>();
+    entries.sort_by(|(_, a), (_, b)| {
+        b.count
+            .total_cmp(&a.count)
+            .then_with(|| b.last_used.cmp(&a.last_used))
+    });
+    entries.truncate(n);
+    entries
+}
+
+fn merge_stats(
+    dst: &mut BTreeMap<String, UserStatsEntry>,
+    src: &BTreeMap<String, UserStatsEntry>,
+    half_life: Option<f32>,
+) {
+    // それぞれの出現回数は、そのエントリが最後に使われた時点まで減衰させた値なので、
+    // 時点をそろえてから足しあわせる。
+    let decay = |entry: &UserStatsEntry, now: u64| match half_life {
+        Some(half_life) => decay_count(entry.count, now - entry.last_used, half_life),
+        None => entry.count,
+    };
+    for (key, entry) in src {
+        dst.entry(key.clone())
+            .and_modify(|it| {
+                let last_used = it.last_used.max(entry.last_used);
+                it.count = decay(it, last_used) + decay(entry, last_used);
+                it.last_used = last_used;
+            })
+            .or_insert(*entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn entry(count: f32, last_used: u64) -> UserStatsEntry {
+        UserStatsEntry { count, last_used }
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = UserDataBundle {
+            unigram: BTreeMap::from([
+                ("渡し/わたし".to_string(), entry(3_f32, 100)),
+                ("私/わたし".to_string(), entry(1_f32, 300)),
+            ]),
+            dict: BTreeMap::from([("あかざ".to_string(), vec!["藜".to_string()])]),
+            ..Default::default()
+        };
+        let b = UserDataBundle {
+            unigram: BTreeMap::from([
+                ("渡し/わたし".to_string(), entry(2_f32, 200)),
+                ("綿/わた".to_string(), entry(1_f32, 50)),
+            ]),
+            bigram: BTreeMap::from([("私/わたし\tは/は".to_string(), entry(4_f32, 10))]),
+            dict: BTreeMap::from([(
+                "あかざ".to_string(),
+                vec!["赤座".to_string(), "藜".to_string()],
+            )]),
+            ..Default::default()
+        };
+
+        a.merge(&b, 0_f32);
+
+        assert_eq!(
+            a.unigram,
+            BTreeMap::from([
+                ("渡し/わたし".to_string(), entry(5_f32, 200)),
+                ("私/わたし".to_string(), entry(1_f32, 300)),
+                ("綿/わた".to_string(), entry(1_f32, 50)),
+            ])
+        );
+        assert_eq!(
+            a.bigram,
+            BTreeMap::from([("私/わたし\tは/は".to_string(), entry(4_f32, 10))])
+        );
+        assert_eq!(
+            a.dict,
+            BTreeMap::from([(
+                "あかざ".to_string(),
+                vec!["藜".to_string(), "赤座".to_string()]
+            )])
+        );
+    }
+
+    #[test]
+    fn test_merge_decay() {
+        let day = 24 * 60 * 60;
+        let mut a = UserDataBundle {
+            unigram: BTreeMap::from([("渡し/わたし".to_string(), entry(4_f32, 10 * day))]),
+            bigram: BTreeMap::from([("私/わたし\tは/は".to_string(), entry(2_f32, 40 * day))]),
+            ..Default::default()
+        };
+        let b = UserDataBundle {
+            unigram: BTreeMap::from([("渡し/わたし".to_string(), entry(2_f32, 40 * day))]),
+            bigram: BTreeMap::from([("私/わたし\tは/は".to_string(), entry(4_f32, 10 * day))]),
+            ..Default::default()
+        };
+
+        // 古いほうを、新しいほうの時点まで半減期 30 日で減衰させてから足しあわせる。
+        // どちらが古くても同じ結果になる。
+        a.merge(&b, 30_f32);
+
+        assert_eq!(
+            a.unigram,
+            BTreeMap::from([("渡し/わたし".to_string(), entry(4_f32, 40 * day))])
+        );
+        assert_eq!(
+            a.bigram,
+            BTreeMap::from([("私/わたし\tは/は".to_string(), entry(4_f32, 40 * day))])
+        );
+    }
+
+    #[test]
+    fn test_top_unigram() {
+        let bundle = UserDataBundle {
+            unigram: BTreeMap::from([
+                ("渡し/わたし".to_string(), entry(3_f32, 100)),
+                ("私/わたし".to_string(), entry(5_f32, 300)),
+                ("綿/わた".to_string(), entry(3_f32, 200)),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            bundle.top_unigram(2),
+            vec![
+                ("私/わたし".to_string(), entry(5_f32, 300)),
+                ("綿/わた".to_string(), entry(3_f32, 200)),
+            ]
+        );
+        assert!(bundle.top_bigram(2).is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let tmpdir = tempdir()?;
+        let path = tmpdir.path().join("bundle.yml");
+        let path = path.to_str().unwrap();
+
+        let bundle = UserDataBundle {
+            unigram: BTreeMap::from([("渡し/わたし".to_string(), entry(3_f32, 100))]),
+            bigram: BTreeMap::from([("私/わたし\tは/は".to_string(), entry(4_f32, 10))]),
+            dict: BTreeMap::from([("あかざ".to_string(), vec!["藜".to_string()])]),
+            ..Default::default()
+        };
+        bundle.save(path)?;
+        assert_eq!(UserDataBundle::load(path)?, bundle);
+
+        // 未来のバージョンのファイルは読めない
+        let future = UserDataBundle {
+            version: USER_DATA_BUNDLE_VERSION + 1,
+            ..Default::default()
+        };
+        future.save(path)?;
+        assert!(UserDataBundle::load(path).is_err());
+        assert!(!tmpdir.path().join("bundle.yml.bak").exists());
+        Ok(())
+    }
+}
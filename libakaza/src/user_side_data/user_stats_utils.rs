@@ -1,11 +1,25 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use std::collections::HashMap;
-use std::fs;
-use std::fs::{File, OpenOptions};
+use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
-use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub(crate) fn read_user_stats_file(path: &String) -> Result<Vec<(String, u32)>> {
+use log::info;
+
+use crate::atomic_file::{backup_path, write_file_with_backup};
+
+/// 現在時刻(UNIX time)
+pub(crate) fn now_epoch_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_secs())
+        .unwrap_or(0)
+}
+
+/// v1 形式のファイルを読む。
+/// 1行に「キー 出現回数」が書かれている。
+pub(crate) fn read_user_stats_file_v1(path: &str) -> Result<Vec<(String, u32)>> {
     let file = File::open(path)?;
 
     let mut result: Vec<(String, u32)> = Vec::new();
@@ -27,38 +41,145 @@ pub(crate) fn read_user_stats_file(path: &String) -> Result<Vec<(String, u32)>>
     Ok(result)
 }
 
-pub(crate) fn write_user_stats_file(path: &str, word_count: &HashMap<String, u32>) -> Result<()> {
-    let mut tmpfile = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .mode(0o600)
-        .open(path.to_string() + ".tmp")?;
-
-    for (key, cnt) in word_count {
-        tmpfile.write_all(key.as_bytes())?;
-        tmpfile.write_all(" ".as_bytes())?;
-        tmpfile.write_all(cnt.to_string().as_bytes())?;
-        tmpfile.write_all("\n".as_bytes())?;
+/// v2 形式のファイルを読む。
+/// 1行に「キー 出現回数 最後に使われた時刻(UNIX time)」が書かれている。
+/// 出現回数は、最後に使われた時点まで減衰させた値なので、小数のこともある。
+pub(crate) fn read_user_stats_file(path: &str) -> Result<Vec<(String, f32, u64)>> {
+    let file = File::open(path)?;
+
+    let mut result: Vec<(String, f32, u64)> = Vec::new();
+
+    for line in BufReader::new(file).lines() {
+        let line = line.context("Cannot read user language model file")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((key, count, last_used)) =
+            line.trim().rsplit_once(' ').and_then(|(rest, last_used)| {
+                rest.rsplit_once(' ')
+                    .map(|(key, count)| (key, count, last_used))
+            })
+        else {
+            bail!("Invalid line in user language model: {}", line);
+        };
+
+        let count = count
+            .parse::<f32>()
+            .with_context(|| format!("Invalid line in user language model: {}", line))?;
+        let last_used = last_used
+            .parse::<u64>()
+            .with_context(|| format!("Invalid line in user language model: {}", line))?;
+
+        result.push((key.to_string(), count, last_used));
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn write_user_stats_file(
+    path: &str,
+    word_count: &HashMap<String, f32>,
+    last_used: &HashMap<String, u64>,
+) -> Result<()> {
+    write_file_with_backup(path, |w| {
+        for (key, cnt) in word_count {
+            let last_used = last_used.get(key).unwrap_or(&0);
+            w.write_fmt(format_args!("{} {} {}\n", key, cnt, last_used))?;
+        }
+        Ok(())
+    })
+}
+
+/// v1 形式のファイルを v2 形式に変換する。
+/// v2 のファイルがすでにある場合や、v1 のファイルがない場合は何もしない。
+/// v1 には最後に使われた時刻が記録されていないので、ファイルの更新時刻を使う。
+pub(crate) fn migrate_user_stats_file(v1_path: &str, v2_path: &str) -> Result<()> {
+    if Path::new(v2_path).exists()
+        || Path::new(&backup_path(v2_path)).exists()
+        || !Path::new(v1_path).exists()
+    {
+        return Ok(());
     }
-    fs::rename(path.to_owned() + ".tmp", path)?;
 
-    Ok(())
+    info!("Migrating user stats file: {} -> {}", v1_path, v2_path);
+    let last_used = File::open(v1_path)?
+        .metadata()?
+        .modified()?
+        .duration_since(UNIX_EPOCH)?
+        .as_secs();
+    let mut word_count: HashMap<String, f32> = HashMap::new();
+    let mut last_used_map: HashMap<String, u64> = HashMap::new();
+    for (key, count) in read_user_stats_file_v1(v1_path)? {
+        last_used_map.insert(key.clone(), last_used);
+        word_count.insert(key, count as f32);
+    }
+    // v1 のファイルは、古いバージョンに戻したときのためにそのまま残しておく。
+    write_user_stats_file(v2_path, &word_count, &last_used_map)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
     use std::io::Read;
-    use tempfile::NamedTempFile;
+    use tempfile::{tempdir, NamedTempFile};
 
     #[test]
     fn test_write() {
         let tmpfile = NamedTempFile::new().unwrap();
         let path = tmpfile.path().to_str().unwrap().to_string();
-        write_user_stats_file(&path, &HashMap::from([("渡し".to_string(), 3_u32)])).unwrap();
+        write_user_stats_file(
+            &path,
+            &HashMap::from([("渡し".to_string(), 3_f32)]),
+            &HashMap::from([("渡し".to_string(), 1677000000_u64)]),
+        )
+        .unwrap();
         let mut buf = String::new();
-        File::open(path).unwrap().read_to_string(&mut buf).unwrap();
-        assert_eq!(buf, "渡し 3\n");
+        File::open(&path).unwrap().read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "渡し 3 1677000000\n");
+
+        assert_eq!(
+            read_user_stats_file(&path).unwrap(),
+            vec![("渡し".to_string(), 3_f32, 1677000000_u64)]
+        );
+
+        // 減衰させた出現回数は小数になる。
+        write_user_stats_file(
+            &path,
+            &HashMap::from([("渡し".to_string(), 2.5_f32)]),
+            &HashMap::from([("渡し".to_string(), 1677000000_u64)]),
+        )
+        .unwrap();
+        assert_eq!(
+            read_user_stats_file(&path).unwrap(),
+            vec![("渡し".to_string(), 2.5_f32, 1677000000_u64)]
+        );
+    }
+
+    #[test]
+    fn test_migrate() -> Result<()> {
+        let tmpdir = tempdir()?;
+        let v1_path = tmpdir.path().join("unigram.v1.txt");
+        let v1_path = v1_path.to_str().unwrap();
+        let v2_path = tmpdir.path().join("unigram.v2.txt");
+        let v2_path = v2_path.to_str().unwrap();
+
+        // v1 がなければ何もしない
+        migrate_user_stats_file(v1_path, v2_path)?;
+        assert!(!Path::new(v2_path).exists());
+
+        fs::write(v1_path, "渡し/わたし 3\n")?;
+        migrate_user_stats_file(v1_path, v2_path)?;
+        let got = read_user_stats_file(v2_path)?;
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].0, "渡し/わたし");
+        assert_eq!(got[0].1, 3_f32);
+        assert!(got[0].2 > 0);
+
+        // v2 があれば上書きしない
+        fs::write(v1_path, "渡し/わたし 5\n")?;
+        migrate_user_stats_file(v1_path, v2_path)?;
+        assert_eq!(read_user_stats_file(v2_path)?[0].1, 3_f32);
+        Ok(())
     }
 }
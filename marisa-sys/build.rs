@@ -1,6 +1,11 @@
 extern crate cc;
 
 fn main() {
+    // pure-rust feature が有効な場合は、marisa-trie をリンクしない。
+    if std::env::var("CARGO_FEATURE_PURE_RUST").is_ok() {
+        return;
+    }
+
     cc::Build::new()
         .cpp(true)
         .file("wrapper.cc")
@@ -37,6 +37,9 @@ extern "C" {
     fn marisa_build(self_: *mut marisa_obj, keyset: *mut marisa_keyset);
     fn marisa_load(self_: *mut marisa_obj, filename: *const c_char) -> *mut marisa_exception;
     fn marisa_save(self_: *mut marisa_obj, filename: *const c_char) -> *mut marisa_exception;
+    fn marisa_map(self_: *mut marisa_obj, ptr: *const c_void, size: usize)
+        -> *mut marisa_exception;
+    fn marisa_mmap(self_: *mut marisa_obj, filename: *const c_char) -> *mut marisa_exception;
     fn marisa_predictive_search(
         self_: *mut marisa_obj,
         query: *const u8,
@@ -68,12 +71,33 @@ pub type PredictiveSearchCallback = dyn FnMut(&[u8], usize) -> bool;
 
 pub struct Marisa {
     marisa: *mut marisa_obj,
+    /// load_from_bytes で読み込んだデータ。marisa はこの領域を直接参照する。
+    /// marisa が 8 バイト単位でアクセスするので、アラインメントを揃えるために u64 で保持している。
+    buffer: Vec<u64>,
 }
 
+// 構築後の marisa::Trie は読み込み専用で、検索時の状態は Agent に持つので、
+// 複数のスレッドから参照しても問題ない。
+unsafe impl Send for Marisa {}
+unsafe impl Sync for Marisa {}
+
 impl Default for Marisa {
     fn default() -> Marisa {
         let marisa = unsafe { marisa_new() };
-        Marisa { marisa }
+        Marisa {
+            marisa,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+// エンジンを作り直すたびに古いトライを解放しないと、メモリやメモリマップがリークする。
+// buffer は marisa から参照されているので、フィールドより先に marisa を解放する Drop で行う。
+impl Drop for Marisa {
+    fn drop(&mut self) {
+        unsafe {
+            marisa_release(self.marisa);
+        }
     }
 }
 
@@ -96,6 +120,51 @@ impl Marisa {
         }
     }
 
+    /// ファイルをメモリマップして読み込む。
+    /// ページキャッシュを複数のプロセスで共有できるし、起動時にファイル全体を読む必要もない。
+    /// マップしている間にファイルが書き換えられると壊れるので、ファイルの更新は rename で行うこと。
+    pub fn mmap(&mut self, filename: &str) -> Result<()> {
+        unsafe {
+            let cstring = CString::new(filename).unwrap();
+            let exc = marisa_mmap(self.marisa, cstring.as_ptr());
+            if exc.is_null() {
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "Cannot mmap file: {}, file={}",
+                    CString::from_raw((*exc).error_message)
+                        .into_string()
+                        .unwrap(),
+                    filename
+                ))
+            }
+        }
+    }
+
+    /// ファイルシステムを経由せずに、メモリ上のデータを読み込む。
+    pub fn load_from_bytes(&mut self, bytes: Vec<u8>) -> Result<()> {
+        let mut buffer: Vec<u64> = vec![0; bytes.len().div_ceil(8)];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                buffer.as_mut_ptr() as *mut u8,
+                bytes.len(),
+            );
+            let exc = marisa_map(self.marisa, buffer.as_ptr() as *const c_void, bytes.len());
+            if exc.is_null() {
+                self.buffer = buffer;
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "Cannot load trie from bytes: {}",
+                    CString::from_raw((*exc).error_message)
+                        .into_string()
+                        .unwrap()
+                ))
+            }
+        }
+    }
+
     pub fn build(&mut self, keyset: &Keyset) {
         unsafe {
             marisa_build(self.marisa, keyset.keyset);
@@ -257,6 +326,86 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_load_from_bytes() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let tmpfile = tmpfile.path().to_str().unwrap().to_string();
+
+        let mut keyset = Keyset::default();
+        keyset.push_back("apple".as_bytes());
+        keyset.push_back("age".as_bytes());
+        let mut marisa = Marisa::default();
+        marisa.build(&keyset);
+        marisa.save(&tmpfile).unwrap();
+
+        let mut marisa = Marisa::default();
+        marisa
+            .load_from_bytes(std::fs::read(&tmpfile).unwrap())
+            .unwrap();
+        assert_eq!(marisa.num_keys(), 2);
+
+        let mut got: Vec<String> = Vec::new();
+        marisa.predictive_search("ap".as_bytes(), |bytes, _| {
+            got.push(String::from_utf8(bytes.to_vec()).unwrap());
+            true
+        });
+        assert_eq!(got, vec!["apple"]);
+    }
+
+    #[test]
+    fn test_mmap() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let tmpfile = tmpfile.path().to_str().unwrap().to_string();
+
+        let mut keyset = Keyset::default();
+        keyset.push_back("apple".as_bytes());
+        keyset.push_back("age".as_bytes());
+        let mut marisa = Marisa::default();
+        marisa.build(&keyset);
+        marisa.save(&tmpfile).unwrap();
+
+        let mut marisa = Marisa::default();
+        marisa.mmap(&tmpfile).unwrap();
+        assert_eq!(marisa.num_keys(), 2);
+
+        let mut got: Vec<String> = Vec::new();
+        marisa.predictive_search("a".as_bytes(), |bytes, _| {
+            got.push(String::from_utf8(bytes.to_vec()).unwrap());
+            true
+        });
+        got.sort();
+        assert_eq!(got, vec!["age", "apple"]);
+    }
+
+    #[test]
+    fn test_drop() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let tmpfile = tmpfile.path().to_str().unwrap().to_string();
+
+        let mut keyset = Keyset::default();
+        keyset.push_back("apple".as_bytes());
+        let mut marisa = Marisa::default();
+        marisa.build(&keyset);
+        marisa.save(&tmpfile).unwrap();
+
+        // 読み込んでは捨てるのを繰り返しても、二重解放などで落ちない。
+        for _ in 0..100 {
+            let mut marisa = Marisa::default();
+            marisa.mmap(&tmpfile).unwrap();
+            assert_eq!(marisa.num_keys(), 1);
+
+            let mut marisa = Marisa::default();
+            marisa.load(&tmpfile).unwrap();
+            assert_eq!(marisa.num_keys(), 1);
+
+            let mut marisa = Marisa::default();
+            marisa
+                .load_from_bytes(std::fs::read(&tmpfile).unwrap())
+                .unwrap();
+            assert_eq!(marisa.num_keys(), 1);
+        }
+    }
+
     #[test]
     fn test_exc() {
         {
@@ -4,4 +4,19 @@
 #![allow(dead_code)]
 
 extern crate alloc;
-include!("bindings.rs");
+
+/// marisa-trie(C++) の binding
+#[cfg(not(feature = "pure-rust"))]
+pub mod ffi {
+    include!("bindings.rs");
+}
+
+/// pure Rust のトライ実装
+pub mod louds;
+
+#[cfg(not(feature = "pure-rust"))]
+pub use ffi::{Keyset, Marisa};
+
+// pure-rust feature が有効な場合は、marisa-trie のかわりに LOUDS トライを利用する。
+#[cfg(feature = "pure-rust")]
+pub use louds::{LoudsKeyset as Keyset, LoudsTrie as Marisa};
@@ -0,0 +1,589 @@
+// ---------------------------------------------------
+// pure Rust implementation of the trie
+// ---------------------------------------------------
+//
+// marisa-trie と同じ操作を提供する、LOUDS(Level-Order Unary Degree Sequence) による簡潔トライです。
+// C++ のビルド環境が不要なので、wasm32 や static musl などのターゲットでも利用できます。
+//
+// ファイルフォーマットは marisa-trie とは互換性がありません。
+// marisa-trie で作成したモデルファイルは `akaza-data convert-model` で変換してください。
+//
+// ファイルフォーマット(リトルエンディアン):
+//
+//   magic           8 bytes  "AKZLOUDS"
+//   version         u32
+//   num_keys        u32
+//   num_nodes       u32
+//   louds_len       u32      LOUDS ビット列のビット数
+//   louds words     u64 * ceil(louds_len / 64)
+//   louds ranks     u32 * (words + 1)
+//   terminal words  u64 * ceil(num_nodes / 64)
+//   terminal ranks  u32 * (words + 1)
+//   labels          u8 * (num_nodes - 1)
+//
+// ノード番号は幅優先順で振られていて、ルートが 0 です。
+// キーの ID は、終端ノードの中での順番です。
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::ops::Deref;
+
+use anyhow::{bail, Result};
+use memmap2::Mmap;
+
+const MAGIC: &[u8; 8] = b"AKZLOUDS";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 24;
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// ビット列の構築用
+#[derive(Default)]
+struct BitVectorBuilder {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitVectorBuilder {
+    fn push(&mut self, bit: bool) {
+        if self.len / 64 == self.words.len() {
+            self.words.push(0);
+        }
+        if bit {
+            *self.words.last_mut().unwrap() |= 1 << (self.len % 64);
+        }
+        self.len += 1;
+    }
+
+    /// ビット列と rank 用の補助テーブルを書き出す。
+    fn write_to(&self, buf: &mut Vec<u8>) {
+        for word in &self.words {
+            buf.extend(word.to_le_bytes());
+        }
+        let mut rank = 0_u32;
+        for word in &self.words {
+            buf.extend(rank.to_le_bytes());
+            rank += word.count_ones();
+        }
+        buf.extend(rank.to_le_bytes());
+    }
+}
+
+/// バイト列上のビット列への参照。rank/select 操作を提供する。
+struct BitVector<'a> {
+    words: &'a [u8],
+    ranks: &'a [u8],
+    num_words: usize,
+}
+
+impl<'a> BitVector<'a> {
+    fn size_of(len: usize) -> usize {
+        let num_words = len.div_ceil(64);
+        num_words * 8 + (num_words + 1) * 4
+    }
+
+    fn new(bytes: &'a [u8], len: usize) -> BitVector<'a> {
+        let num_words = len.div_ceil(64);
+        let (words, ranks) = bytes.split_at(num_words * 8);
+        BitVector {
+            words,
+            ranks: &ranks[0..(num_words + 1) * 4],
+            num_words,
+        }
+    }
+
+    fn word(&self, i: usize) -> u64 {
+        read_u64(self.words, i * 8)
+    }
+
+    fn get(&self, pos: usize) -> bool {
+        self.word(pos / 64) & (1 << (pos % 64)) != 0
+    }
+
+    /// [0, pos) にある 1 の数
+    fn rank1(&self, pos: usize) -> usize {
+        let word_idx = pos / 64;
+        let rank = read_u32(self.ranks, word_idx * 4) as usize;
+        let bit_idx = pos % 64;
+        if bit_idx == 0 {
+            rank
+        } else {
+            rank + (self.word(word_idx) & ((1 << bit_idx) - 1)).count_ones() as usize
+        }
+    }
+
+    /// k 番目(0-origin)の 0 の位置
+    fn select0(&self, k: usize) -> usize {
+        let zeros_before = |w: usize| w * 64 - read_u32(self.ranks, w * 4) as usize;
+
+        // zeros_before(w) <= k となる最大の w を探す。
+        let mut lo = 0;
+        let mut hi = self.num_words;
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if zeros_before(mid) <= k {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        let mut word = !self.word(lo);
+        for _ in 0..(k - zeros_before(lo)) {
+            // 下位の 0 を順に消していく
+            word &= word - 1;
+        }
+        lo * 64 + word.trailing_zeros() as usize
+    }
+}
+
+/// トライに登録するキーの集合
+#[derive(Default)]
+pub struct LoudsKeyset {
+    keys: Vec<Vec<u8>>,
+}
+
+impl LoudsKeyset {
+    pub fn push_back(&mut self, key: &[u8]) {
+        self.keys.push(key.to_vec());
+    }
+}
+
+/// トライのデータの置き場所
+enum Storage {
+    Owned(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for Storage {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Storage::Owned(data) => data,
+            Storage::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+pub struct LoudsTrie {
+    data: Storage,
+    num_keys: usize,
+    num_nodes: usize,
+    louds_len: usize,
+    louds_offset: usize,
+    terminal_offset: usize,
+    labels_offset: usize,
+}
+
+impl Default for LoudsTrie {
+    fn default() -> LoudsTrie {
+        LoudsTrie::from_bytes(Storage::Owned(Self::serialize(&LoudsKeyset::default()))).unwrap()
+    }
+}
+
+impl LoudsTrie {
+    fn serialize(keyset: &LoudsKeyset) -> Vec<u8> {
+        let mut keys: Vec<&[u8]> = keyset.keys.iter().map(|it| it.as_slice()).collect();
+        keys.sort();
+        keys.dedup();
+
+        // 幅優先でノードを作っていく。
+        // キューには、そのノード以下のキーの範囲と、ノードの深さを入れる。
+        let mut louds = BitVectorBuilder::default();
+        let mut terminal = BitVectorBuilder::default();
+        let mut labels: Vec<u8> = Vec::new();
+        louds.push(true);
+        louds.push(false);
+
+        let mut queue = VecDeque::from([(0, keys.len(), 0)]);
+        while let Some((lo, hi, depth)) = queue.pop_front() {
+            let mut i = lo;
+            // ソート済みなので、終端になるキーは範囲の先頭にある。
+            let is_terminal = i < hi && keys[i].len() == depth;
+            terminal.push(is_terminal);
+            if is_terminal {
+                i += 1;
+            }
+            while i < hi {
+                let label = keys[i][depth];
+                let mut j = i + 1;
+                while j < hi && keys[j][depth] == label {
+                    j += 1;
+                }
+                louds.push(true);
+                labels.push(label);
+                queue.push_back((i, j, depth + 1));
+                i = j;
+            }
+            louds.push(false);
+        }
+
+        let mut buf: Vec<u8> = Vec::new();
+        buf.extend(MAGIC);
+        buf.extend(VERSION.to_le_bytes());
+        buf.extend((keys.len() as u32).to_le_bytes());
+        buf.extend((terminal.len as u32).to_le_bytes());
+        buf.extend((louds.len as u32).to_le_bytes());
+        louds.write_to(&mut buf);
+        terminal.write_to(&mut buf);
+        buf.extend(labels);
+        buf
+    }
+
+    fn from_bytes(data: Storage) -> Result<LoudsTrie> {
+        if data.len() < HEADER_SIZE || &data[0..8] != MAGIC {
+            bail!("Unsupported trie file format. If it's created by marisa-trie, convert it by `akaza-data convert-model`.");
+        }
+        let version = read_u32(&data, 8);
+        if version != VERSION {
+            bail!("Unsupported trie file version: {}", version);
+        }
+        let num_keys = read_u32(&data, 12) as usize;
+        let num_nodes = read_u32(&data, 16) as usize;
+        let louds_len = read_u32(&data, 20) as usize;
+
+        let louds_offset = HEADER_SIZE;
+        let terminal_offset = louds_offset + BitVector::size_of(louds_len);
+        let labels_offset = terminal_offset + BitVector::size_of(num_nodes);
+        if num_nodes == 0 || data.len() != labels_offset + num_nodes - 1 {
+            bail!("Broken trie file: size mismatch");
+        }
+
+        Ok(LoudsTrie {
+            data,
+            num_keys,
+            num_nodes,
+            louds_len,
+            louds_offset,
+            terminal_offset,
+            labels_offset,
+        })
+    }
+
+    fn louds(&self) -> BitVector<'_> {
+        BitVector::new(&self.data[self.louds_offset..], self.louds_len)
+    }
+
+    fn terminal(&self) -> BitVector<'_> {
+        BitVector::new(&self.data[self.terminal_offset..], self.num_nodes)
+    }
+
+    fn labels(&self) -> &[u8] {
+        &self.data[self.labels_offset..]
+    }
+
+    /// node の子ノードの ID の範囲を返す。
+    fn children(&self, louds: &BitVector, node: usize) -> (usize, usize) {
+        let start = louds.select0(node) + 1;
+        let end = louds.select0(node + 1);
+        let first_child = louds.rank1(start);
+        (first_child, first_child + (end - start))
+    }
+
+    fn find_child(&self, louds: &BitVector, node: usize, label: u8) -> Option<usize> {
+        let (first, last) = self.children(louds, node);
+        // ラベルは子ノードの中でソートされている。
+        self.labels()[first - 1..last - 1]
+            .binary_search(&label)
+            .ok()
+            .map(|i| first + i)
+    }
+
+    fn key_id(&self, terminal: &BitVector, node: usize) -> Option<usize> {
+        if terminal.get(node) {
+            Some(terminal.rank1(node))
+        } else {
+            None
+        }
+    }
+
+    pub fn load(&mut self, filename: &str) -> Result<()> {
+        let mut data: Vec<u8> = Vec::new();
+        match File::open(filename) {
+            Ok(mut file) => file.read_to_end(&mut data)?,
+            Err(err) => bail!("Cannot load file: {}, file={}", err, filename),
+        };
+        *self = Self::from_bytes(Storage::Owned(data))?;
+        Ok(())
+    }
+
+    /// ファイルをメモリマップして読み込む。
+    /// マップしている間にファイルが書き換えられると壊れるので、ファイルの更新は rename で行うこと。
+    pub fn mmap(&mut self, filename: &str) -> Result<()> {
+        let file = match File::open(filename) {
+            Ok(file) => file,
+            Err(err) => bail!("Cannot mmap file: {}, file={}", err, filename),
+        };
+        let mmap = unsafe { Mmap::map(&file)? };
+        *self = Self::from_bytes(Storage::Mapped(mmap))?;
+        Ok(())
+    }
+
+    /// ファイルシステムを経由せずに、メモリ上のデータを読み込む。
+    pub fn load_from_bytes(&mut self, bytes: Vec<u8>) -> Result<()> {
+        *self = Self::from_bytes(Storage::Owned(bytes))?;
+        Ok(())
+    }
+
+    pub fn build(&mut self, keyset: &LoudsKeyset) {
+        *self = Self::from_bytes(Storage::Owned(Self::serialize(keyset))).unwrap();
+    }
+
+    pub fn save(&self, filename: &str) -> Result<()> {
+        let mut file = File::create(filename)?;
+        file.write_all(&self.data)?;
+        Ok(())
+    }
+
+    pub fn num_keys(&self) -> usize {
+        self.num_keys
+    }
+
+    /// 完全一致するキーの ID を返す。
+    pub fn lookup(&self, key: &[u8]) -> Option<usize> {
+        let louds = self.louds();
+        let mut node = 0;
+        for label in key {
+            node = self.find_child(&louds, node, *label)?;
+        }
+        self.key_id(&self.terminal(), node)
+    }
+
+    pub fn predictive_search<F>(&self, query: &[u8], callback: F)
+    where
+        F: FnMut(&[u8], usize) -> bool,
+    {
+        let mut callback = callback;
+        let louds = self.louds();
+        let terminal = self.terminal();
+
+        let mut node = 0;
+        for label in query {
+            let Some(child) = self.find_child(&louds, node, *label) else {
+                return;
+            };
+            node = child;
+        }
+
+        // 辞書順に深さ優先でたどる。
+        let mut key = query.to_vec();
+        let mut stack: Vec<(usize, usize)> = vec![self.children(&louds, node)];
+        if let Some(id) = self.key_id(&terminal, node) {
+            if !callback(&key, id) {
+                return;
+            }
+        }
+        while let Some((next, last)) = stack.last_mut() {
+            if *next == *last {
+                stack.pop();
+                key.pop();
+                continue;
+            }
+            let child = *next;
+            *next += 1;
+            key.push(self.labels()[child - 1]);
+            if let Some(id) = self.key_id(&terminal, child) {
+                if !callback(&key, id) {
+                    return;
+                }
+            }
+            stack.push(self.children(&louds, child));
+        }
+    }
+
+    pub fn common_prefix_search<F>(&self, query: &str, callback: F)
+    where
+        F: FnMut(&[u8], usize) -> bool,
+    {
+        let mut callback = callback;
+        let louds = self.louds();
+        let terminal = self.terminal();
+        let query = query.as_bytes();
+
+        let mut node = 0;
+        for i in 0..=query.len() {
+            if let Some(id) = self.key_id(&terminal, node) {
+                if !callback(&query[0..i], id) {
+                    return;
+                }
+            }
+            if i == query.len() {
+                break;
+            }
+            let Some(child) = self.find_child(&louds, node, query[i]) else {
+                return;
+            };
+            node = child;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn build(keys: &[&str]) -> LoudsTrie {
+        let mut keyset = LoudsKeyset::default();
+        for key in keys {
+            keyset.push_back(key.as_bytes());
+        }
+        let mut trie = LoudsTrie::default();
+        trie.build(&keyset);
+        trie
+    }
+
+    #[test]
+    fn test() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let tmpfile = tmpfile.path().to_str().unwrap().to_string();
+
+        {
+            let trie = build(&["apple", "age", "hola"]);
+            trie.save(&tmpfile)?;
+            assert_eq!(trie.num_keys(), 3)
+        }
+
+        // read it
+        {
+            let mut trie = LoudsTrie::default();
+            trie.load(&tmpfile)?;
+            assert_eq!(trie.num_keys(), 3);
+
+            let mut got: Vec<String> = Vec::new();
+            trie.predictive_search("a".as_bytes(), |bytes, _| {
+                got.push(String::from_utf8(bytes.to_vec()).unwrap());
+                true
+            });
+            assert_eq!(got, vec!["age", "apple"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_from_bytes() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let tmpfile = tmpfile.path().to_str().unwrap().to_string();
+        build(&["apple", "age"]).save(&tmpfile)?;
+
+        let mut trie = LoudsTrie::default();
+        trie.load_from_bytes(std::fs::read(&tmpfile)?)?;
+        assert_eq!(trie.num_keys(), 2);
+        assert_eq!(
+            trie.lookup("age".as_bytes()),
+            build(&["apple", "age"]).lookup("age".as_bytes())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmap() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let tmpfile = tmpfile.path().to_str().unwrap().to_string();
+        build(&["apple", "age", "banana"]).save(&tmpfile)?;
+
+        let mut trie = LoudsTrie::default();
+        trie.mmap(&tmpfile)?;
+        assert_eq!(trie.num_keys(), 3);
+        let mut got: Vec<String> = Vec::new();
+        trie.predictive_search("a".as_bytes(), |bytes, _| {
+            got.push(String::from_utf8(bytes.to_vec()).unwrap());
+            true
+        });
+        assert_eq!(got, vec!["age", "apple"]);
+
+        assert!(LoudsTrie::default().mmap("UNKNOWN_PATH").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ids() {
+        let keys = ["a", "ab", "abc", "b", "bcd", "わたし", "わたしの", ""];
+        let trie = build(&keys);
+        assert_eq!(trie.num_keys(), keys.len());
+
+        // ID は 0..num_keys の範囲で重複なく振られる。
+        let mut ids: Vec<usize> = Vec::new();
+        trie.predictive_search("".as_bytes(), |key, id| {
+            assert_eq!(trie.lookup(key), Some(id));
+            ids.push(id);
+            true
+        });
+        ids.sort();
+        assert_eq!(ids, (0..keys.len()).collect::<Vec<_>>());
+
+        assert_eq!(trie.lookup("abcd".as_bytes()), None);
+        assert_eq!(trie.lookup("bc".as_bytes()), None);
+    }
+
+    #[test]
+    fn test_predictive_search_stop() {
+        let trie = build(&["a", "ab", "abc", "b"]);
+        let mut got: Vec<String> = Vec::new();
+        trie.predictive_search("a".as_bytes(), |key, _| {
+            got.push(String::from_utf8(key.to_vec()).unwrap());
+            false
+        });
+        assert_eq!(got, vec!["a"]);
+
+        let mut got: Vec<String> = Vec::new();
+        trie.predictive_search("c".as_bytes(), |key, _| {
+            got.push(String::from_utf8(key.to_vec()).unwrap());
+            true
+        });
+        assert!(got.is_empty());
+    }
+
+    #[test]
+    fn test_common_prefix_search() {
+        let trie = build(&["わ", "わた", "わたし", "わたしの", "たし"]);
+        let mut got: Vec<String> = Vec::new();
+        trie.common_prefix_search("わたしは", |key, _| {
+            got.push(String::from_utf8(key.to_vec()).unwrap());
+            true
+        });
+        assert_eq!(got, vec!["わ", "わた", "わたし"]);
+    }
+
+    #[test]
+    fn test_large() {
+        // select0 が複数のワードをまたぐケース
+        let keys: Vec<String> = (0..5000).map(|i| format!("key{}", i * 7)).collect();
+        let mut keyset = LoudsKeyset::default();
+        for key in &keys {
+            keyset.push_back(key.as_bytes());
+        }
+        let mut trie = LoudsTrie::default();
+        trie.build(&keyset);
+        assert_eq!(trie.num_keys(), keys.len());
+
+        for key in &keys {
+            let mut got: Vec<Vec<u8>> = Vec::new();
+            trie.predictive_search(key.as_bytes(), |k, _| {
+                got.push(k.to_vec());
+                false
+            });
+            assert_eq!(got, vec![key.as_bytes().to_vec()]);
+        }
+    }
+
+    #[test]
+    fn test_invalid_format() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let path = tmpfile.path().to_str().unwrap().to_string();
+        std::fs::write(&path, b"this is not a trie").unwrap();
+
+        let mut trie = LoudsTrie::default();
+        assert!(trie.load(&path).is_err());
+        assert!(trie.load("UNKNOWN_PATH").is_err());
+    }
+}